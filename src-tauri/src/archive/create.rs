@@ -0,0 +1,244 @@
+use super::formats::to_zip_time;
+use super::ArchiveFormat;
+use crate::fs::AppError;
+use crate::jobs::{Job, JobReader};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+
+/// Highest compression level each format accepts.
+fn max_level(format: ArchiveFormat) -> Option<u32> {
+    match format {
+        ArchiveFormat::Zip | ArchiveFormat::TarGz | ArchiveFormat::TarXz => Some(9),
+        ArchiveFormat::TarZst => Some(22),
+        ArchiveFormat::Tar | ArchiveFormat::SevenZ => None,
+    }
+}
+
+/// Rejects formats that cannot be written and levels they do not support.
+pub fn validate_level(format: ArchiveFormat, level: Option<u32>) -> Result<(), AppError> {
+    if format == ArchiveFormat::SevenZ {
+        return Err(AppError::InvalidOperation(
+            "7z archives can be browsed and extracted but not created".into(),
+        ));
+    }
+
+    match (level, max_level(format)) {
+        (Some(level), Some(max)) if level > max => Err(AppError::InvalidOperation(format!(
+            "Compression level {} is out of range 0-{}",
+            level, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+enum Kind {
+    File(u64),
+    Dir,
+    Link,
+}
+
+/// One file, folder or link to store, with its `/`-separated name in the archive.
+struct Item {
+    path: PathBuf,
+    name: String,
+    kind: Kind,
+    modified: Option<i64>,
+}
+
+/// Packs `sources` into a new archive at `dest`, each under its own name, with a level
+/// already checked by `validate_level`. The archive is written next to `dest` first and
+/// only moved into place once it is complete.
+pub fn create(
+    sources: &[PathBuf],
+    dest: &Path,
+    format: ArchiveFormat,
+    level: Option<u32>,
+    job: &Job,
+) -> Result<(), AppError> {
+    // Collected before the partial archive exists, so it never ends up inside itself
+    let items = collect(sources, job)?;
+    let total_bytes = items
+        .iter()
+        .map(|item| match item.kind {
+            Kind::File(len) => len,
+            _ => 0,
+        })
+        .sum();
+    job.set_totals(items.len(), total_bytes);
+
+    let name = dest
+        .file_name()
+        .ok_or_else(|| AppError::InvalidOperation("Invalid archive path".into()))?;
+    let partial = dest.with_file_name(format!(".{}.part", name.to_string_lossy()));
+
+    let result = File::create(&partial)
+        .and_then(|file| write_archive(file, format, level, &items, job))
+        .and_then(|()| fs::rename(&partial, dest));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        job.check_cancelled()?;
+        return Err(AppError::Io(format!("{}: {}", dest.display(), e)));
+    }
+
+    Ok(())
+}
+
+fn collect(sources: &[PathBuf], job: &Job) -> Result<Vec<Item>, AppError> {
+    let mut items = Vec::new();
+
+    for source in sources {
+        let base = source.parent().unwrap_or(source);
+        for entry in WalkDir::new(source).follow_links(false) {
+            job.check_cancelled()?;
+            let entry = entry.map_err(|e| AppError::Io(e.to_string()))?;
+            let metadata = entry.metadata().map_err(|e| AppError::Io(e.to_string()))?;
+
+            let name = entry
+                .path()
+                .strip_prefix(base)
+                .unwrap_or(entry.path())
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let kind = if metadata.is_symlink() {
+                Kind::Link
+            } else if metadata.is_dir() {
+                Kind::Dir
+            } else if metadata.is_file() {
+                Kind::File(metadata.len())
+            } else {
+                // Sockets, fifos and devices cannot be archived portably
+                continue;
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64);
+
+            items.push(Item {
+                path: entry.into_path(),
+                name,
+                kind,
+                modified,
+            });
+        }
+    }
+
+    Ok(items)
+}
+
+fn write_archive(
+    file: File,
+    format: ArchiveFormat,
+    level: Option<u32>,
+    items: &[Item],
+    job: &Job,
+) -> io::Result<()> {
+    let writer = BufWriter::new(file);
+
+    match format {
+        ArchiveFormat::Zip => write_zip(writer, level, items, job),
+        ArchiveFormat::Tar => write_tar(writer, items, job)?.flush(),
+        ArchiveFormat::TarGz => {
+            let level = flate2::Compression::new(level.unwrap_or(6));
+            write_tar(flate2::write::GzEncoder::new(writer, level), items, job)?
+                .finish()?
+                .flush()
+        }
+        ArchiveFormat::TarXz => {
+            let encoder = xz2::write::XzEncoder::new(writer, level.unwrap_or(6));
+            write_tar(encoder, items, job)?.finish()?.flush()
+        }
+        ArchiveFormat::TarZst => {
+            // Level 0 selects zstd's default
+            let encoder = zstd::stream::write::Encoder::new(writer, level.unwrap_or(0) as i32)?;
+            write_tar(encoder, items, job)?.finish()?.flush()
+        }
+        ArchiveFormat::SevenZ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "7z archives cannot be created",
+        )),
+    }
+}
+
+fn write_zip<W: Write + io::Seek>(
+    writer: W,
+    level: Option<u32>,
+    items: &[Item],
+    job: &Job,
+) -> io::Result<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+    let base_options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(level.map(i64::from));
+
+    for item in items {
+        check(job)?;
+        job.start_item(&item.path);
+
+        let mut options = base_options;
+        if let Some(time) = item.modified.and_then(to_zip_time) {
+            options = options.last_modified_time(time);
+        }
+
+        match item.kind {
+            Kind::Dir => zip
+                .add_directory(item.name.as_str(), options)
+                .map_err(io::Error::other)?,
+            Kind::Link => {
+                let target = fs::read_link(&item.path)?;
+                zip.add_symlink(item.name.as_str(), target.to_string_lossy(), options)
+                    .map_err(io::Error::other)?;
+            }
+            Kind::File(len) => {
+                // Zip64 headers are only needed, and only understood by all readers, past 4 GiB
+                let options = options.large_file(len >= u64::from(u32::MAX));
+                zip.start_file(item.name.as_str(), options)
+                    .map_err(io::Error::other)?;
+                io::copy(
+                    &mut JobReader::new(File::open(&item.path)?, Some(job)),
+                    &mut zip,
+                )?;
+            }
+        }
+        job.finish_item();
+    }
+
+    zip.finish().map_err(io::Error::other)?.flush()
+}
+
+fn write_tar<W: Write>(writer: W, items: &[Item], job: &Job) -> io::Result<W> {
+    let mut tar = tar::Builder::new(writer);
+    tar.follow_symlinks(false);
+
+    for item in items {
+        check(job)?;
+        job.start_item(&item.path);
+
+        match item.kind {
+            Kind::Dir | Kind::Link => tar.append_path_with_name(&item.path, &item.name)?,
+            Kind::File(_) => {
+                let file = File::open(&item.path)?;
+                let metadata = file.metadata()?;
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&metadata);
+                // A file growing while it is read must not run past its header's size
+                let data = JobReader::new(file.take(metadata.len()), Some(job));
+                tar.append_data(&mut header, &item.name, data)?;
+            }
+        }
+        job.finish_item();
+    }
+
+    tar.into_inner()
+}
+
+fn check(job: &Job) -> io::Result<()> {
+    job.check_cancelled()
+        .map_err(|e| io::Error::other(e.to_string()))
+}
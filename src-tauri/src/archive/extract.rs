@@ -0,0 +1,306 @@
+use super::formats::{open_tar, zip_time};
+use super::{normalize, ArchiveFormat};
+use crate::fs::{AppError, ConflictPolicy};
+use crate::jobs::{Job, JobReader};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Extracts `selected` entries of one archive into `dest`, folders with everything below
+/// them. An empty entry selects the whole archive. `conflict` decides once per top-level
+/// item what happens to items already in `dest`. Returns the number of entries written.
+pub fn extract(
+    archive: &Path,
+    format: ArchiveFormat,
+    selected: &[String],
+    dest: &Path,
+    conflict: ConflictPolicy,
+    job: Option<&Job>,
+) -> Result<usize, AppError> {
+    let selected: Vec<String> = selected.iter().map(|s| normalize(s)).collect();
+    let mut extraction = Extraction {
+        targets: Targets {
+            selected: &selected,
+            dest,
+            conflict,
+            decisions: HashMap::new(),
+            failure: None,
+        },
+        job,
+        written: 0,
+    };
+
+    let result = match format {
+        ArchiveFormat::Zip => extraction.zip(archive),
+        ArchiveFormat::SevenZ => extraction.sevenz(archive),
+        _ => extraction.tar(archive, format),
+    };
+
+    match result {
+        Ok(()) => Ok(extraction.written),
+        Err(_) if job.is_some_and(Job::is_cancelled) => Err(AppError::Cancelled),
+        Err(e) => Err(extraction
+            .targets
+            .failure
+            .take()
+            .unwrap_or_else(|| AppError::Io(format!("{}: {}", archive.display(), e)))),
+    }
+}
+
+struct Targets<'a> {
+    selected: &'a [String],
+    dest: &'a Path,
+    conflict: ConflictPolicy,
+    /// Where each top-level item goes after applying the conflict policy, `None` if skipped.
+    decisions: HashMap<PathBuf, Option<PathBuf>>,
+    /// Set when the conflict policy refused to continue.
+    failure: Option<AppError>,
+}
+
+impl Targets<'_> {
+    /// The top-level item a normalized entry path belongs to and the rest of the path below
+    /// it, if the entry is selected. Entries whose path would escape `dest` ("zip slip") are
+    /// never selected.
+    fn locate<'p>(&self, path: &'p str) -> Option<(PathBuf, &'p str)> {
+        if path.is_empty() || !path.split('/').all(is_safe_component) {
+            return None;
+        }
+
+        self.selected.iter().find_map(|selected| {
+            let (top, rest) = if selected.is_empty() {
+                path.split_once('/').unwrap_or((path, ""))
+            } else {
+                let rest = if path == selected {
+                    ""
+                } else {
+                    path.strip_prefix(selected.as_str())?.strip_prefix('/')?
+                };
+                (selected.rsplit('/').next()?, rest)
+            };
+            Some((self.dest.join(top), rest))
+        })
+    }
+
+    fn is_selected(&self, entry_path: &str) -> bool {
+        self.locate(&normalize(entry_path)).is_some()
+    }
+
+    /// Where an archive entry goes, or `None` if it is not selected or its item is skipped.
+    fn target(&mut self, entry_path: &str) -> io::Result<Option<PathBuf>> {
+        let path = normalize(entry_path);
+        let (top, rest) = match self.locate(&path) {
+            Some(located) => located,
+            None => return Ok(None),
+        };
+
+        let top = match self.decisions.get(&top) {
+            Some(decision) => decision.clone(),
+            None => {
+                let decision = match self.conflict.resolve(&top) {
+                    Ok(decision) => decision,
+                    Err(e) => {
+                        let message = e.to_string();
+                        self.failure = Some(e);
+                        return Err(io::Error::other(message));
+                    }
+                };
+                self.decisions.insert(top, decision.clone());
+                decision
+            }
+        };
+
+        Ok(top
+            .map(|top| {
+                rest.split('/')
+                    .filter(|c| !c.is_empty())
+                    .fold(top, |target, c| target.join(c))
+            })
+            .filter(|target| target.starts_with(self.dest)))
+    }
+}
+
+/// Rejects `..` as well as drive prefixes, alternate data streams and backslashes that
+/// Windows would read as separators.
+fn is_safe_component(component: &str) -> bool {
+    component != ".." && !component.contains([':', '\\'])
+}
+
+fn is_zip_link(unix_mode: Option<u32>) -> bool {
+    unix_mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+}
+
+struct Extraction<'a> {
+    targets: Targets<'a>,
+    job: Option<&'a Job>,
+    written: usize,
+}
+
+impl Extraction<'_> {
+    fn write(
+        &mut self,
+        target: &Path,
+        is_dir: bool,
+        reader: &mut dyn Read,
+        modified: Option<i64>,
+    ) -> io::Result<()> {
+        if let Some(job) = self.job {
+            if job.is_cancelled() {
+                return Err(io::Error::other(AppError::Cancelled.to_string()));
+            }
+            job.start_item(target);
+        }
+
+        write_entry(target, is_dir, reader, modified)?;
+
+        if let Some(job) = self.job {
+            job.finish_item();
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    fn zip(&mut self, archive: &Path) -> io::Result<()> {
+        let mut zip =
+            zip::ZipArchive::new(BufReader::new(File::open(archive)?)).map_err(io::Error::other)?;
+
+        if let Some(job) = self.job {
+            let (mut items, mut bytes) = (0, 0);
+            for index in 0..zip.len() {
+                let file = zip.by_index_raw(index).map_err(io::Error::other)?;
+                if !is_zip_link(file.unix_mode()) && self.targets.is_selected(file.name()) {
+                    items += 1;
+                    bytes += file.size();
+                }
+            }
+            job.set_totals(items, bytes);
+        }
+
+        for index in 0..zip.len() {
+            let file = zip.by_index(index).map_err(io::Error::other)?;
+            // Links could point anywhere, so they are not recreated
+            if is_zip_link(file.unix_mode()) {
+                continue;
+            }
+            let target = match self.targets.target(file.name())? {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let is_dir = file.is_dir();
+            let modified = file.last_modified().and_then(zip_time);
+            self.write(
+                &target,
+                is_dir,
+                &mut JobReader::new(file, self.job),
+                modified,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn tar(&mut self, archive: &Path, format: ArchiveFormat) -> io::Result<()> {
+        let file = File::open(archive)?;
+        if let Some(job) = self.job {
+            // Tars have no index, so progress follows how much of the archive has been read
+            job.set_totals(0, file.metadata()?.len());
+        }
+        let mut tar = open_tar(JobReader::new(file, self.job), format)?;
+
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let kind = entry.header().entry_type();
+            if !kind.is_file() && !kind.is_dir() {
+                continue;
+            }
+
+            let path = entry.path()?.to_string_lossy().to_string();
+            if let Some(target) = self.targets.target(&path)? {
+                let modified = entry.header().mtime().ok().map(|t| t as i64);
+                self.write(&target, kind.is_dir(), &mut entry, modified)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sevenz(&mut self, archive: &Path) -> io::Result<()> {
+        let mut reader = sevenz_rust::SevenZReader::open(archive, sevenz_rust::Password::empty())
+            .map_err(io::Error::other)?;
+
+        if let Some(job) = self.job {
+            let (items, bytes) = reader
+                .archive()
+                .files
+                .iter()
+                .filter(|file| !file.is_anti_item() && self.targets.is_selected(file.name()))
+                .fold((0, 0), |(items, bytes), file| {
+                    (items + 1, bytes + file.size())
+                });
+            job.set_totals(items, bytes);
+        }
+
+        let job = self.job;
+        reader
+            .for_each_entries(|entry, data| {
+                let target = if entry.is_anti_item() {
+                    None
+                } else {
+                    self.targets.target(entry.name())?
+                };
+
+                match target {
+                    Some(target) => {
+                        let modified = entry
+                            .has_last_modified_date
+                            .then(|| entry.last_modified_date().to_unix_time());
+                        let mut data = JobReader::new(data, job);
+                        self.write(&target, entry.is_directory(), &mut data, modified)?;
+                    }
+                    // Solid archives decode as one stream, so skipped entries are read through
+                    None => {
+                        if job.is_some_and(Job::is_cancelled) {
+                            return Err(io::Error::other(AppError::Cancelled.to_string()).into());
+                        }
+                        io::copy(data, &mut io::sink())?;
+                    }
+                }
+                Ok(true)
+            })
+            .map_err(io::Error::other)
+    }
+}
+
+fn write_entry(
+    target: &Path,
+    is_dir: bool,
+    reader: &mut dyn Read,
+    modified: Option<i64>,
+) -> io::Result<()> {
+    if is_dir {
+        return fs::create_dir_all(target);
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Overwriting must not write through a link to somewhere outside `dest`
+    if target
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink())
+    {
+        fs::remove_file(target)?;
+    }
+    let mut file = File::create(target)?;
+    io::copy(reader, &mut file)?;
+
+    if let Some(modified) = modified.and_then(|m| u64::try_from(m).ok()) {
+        let _ = file.set_modified(UNIX_EPOCH + Duration::from_secs(modified));
+    }
+    Ok(())
+}
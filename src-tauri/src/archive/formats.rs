@@ -0,0 +1,127 @@
+use super::{ArchiveEntry, ArchiveFormat};
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Timelike};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Reads the entry list of an archive without extracting anything.
+pub fn read_entries(archive: &Path, format: ArchiveFormat) -> io::Result<Vec<ArchiveEntry>> {
+    match format {
+        ArchiveFormat::Zip => read_zip(archive),
+        ArchiveFormat::SevenZ => read_7z(archive),
+        _ => read_tar(archive, format),
+    }
+}
+
+fn read_zip(archive: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let mut zip =
+        zip::ZipArchive::new(BufReader::new(File::open(archive)?)).map_err(io::Error::other)?;
+
+    let mut entries = Vec::with_capacity(zip.len());
+    for index in 0..zip.len() {
+        // Raw access skips decompression, and with it any password prompt
+        let file = zip.by_index_raw(index).map_err(io::Error::other)?;
+        entries.push(ArchiveEntry {
+            path: file.name().to_string(),
+            is_dir: file.is_dir(),
+            size: file.size(),
+            modified: file.last_modified().and_then(zip_time),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Zip stores local time without a zone.
+pub(super) fn zip_time(time: zip::DateTime) -> Option<i64> {
+    let naive = NaiveDate::from_ymd_opt(
+        i32::from(time.year()),
+        u32::from(time.month()),
+        u32::from(time.day()),
+    )?
+    .and_hms_opt(
+        u32::from(time.hour()),
+        u32::from(time.minute()),
+        u32::from(time.second()),
+    )?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|t| t.timestamp())
+}
+
+/// Converts back to zip's local time, for timestamps within the range zip can store.
+pub(super) fn to_zip_time(timestamp: i64) -> Option<zip::DateTime> {
+    let time = Local.timestamp_opt(timestamp, 0).single()?;
+    zip::DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
+
+/// Reads a tar archive from `input`, decompressing it on the fly as its format requires.
+pub(super) fn open_tar<'a>(
+    input: impl Read + 'a,
+    format: ArchiveFormat,
+) -> io::Result<tar::Archive<Box<dyn Read + 'a>>> {
+    let file = BufReader::new(input);
+    let reader: Box<dyn Read + 'a> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+        _ => Box::new(file),
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+fn read_tar(archive: &Path, format: ArchiveFormat) -> io::Result<Vec<ArchiveEntry>> {
+    let mut tar = open_tar(File::open(archive)?, format)?;
+
+    let mut entries = Vec::new();
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let kind = header.entry_type();
+
+        // Links and special files are neither listed nor extracted
+        if !kind.is_file() && !kind.is_dir() {
+            continue;
+        }
+
+        entries.push(ArchiveEntry {
+            path: entry.path()?.to_string_lossy().to_string(),
+            is_dir: kind.is_dir(),
+            size: entry.size(),
+            modified: header.mtime().ok().map(|t| t as i64),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn read_7z(archive: &Path) -> io::Result<Vec<ArchiveEntry>> {
+    let reader = sevenz_rust::SevenZReader::open(archive, sevenz_rust::Password::empty())
+        .map_err(io::Error::other)?;
+
+    Ok(reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| !file.is_anti_item())
+        .map(|file| ArchiveEntry {
+            path: file.name().to_string(),
+            is_dir: file.is_directory(),
+            size: file.size(),
+            modified: file
+                .has_last_modified_date
+                .then(|| file.last_modified_date().to_unix_time()),
+        })
+        .collect())
+}
@@ -0,0 +1,307 @@
+mod create;
+mod extract;
+mod formats;
+
+pub use create::{create, validate_level};
+pub use extract::extract;
+
+use crate::fs::{sort_entries, AppError, FileEntry};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Separates an archive from the path inside it, as in `backup.zip!/docs/a.txt`. The
+/// archive's root is `backup.zip!`, so its parent is the folder holding the archive.
+const SEPARATOR: char = '!';
+const CACHED_ARCHIVES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    #[serde(rename = "tar.zst")]
+    TarZst,
+    #[serde(rename = "7z")]
+    SevenZ,
+}
+
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        const SUFFIXES: &[(&str, ArchiveFormat)] = &[
+            (".zip", ArchiveFormat::Zip),
+            (".tar", ArchiveFormat::Tar),
+            (".tar.gz", ArchiveFormat::TarGz),
+            (".tgz", ArchiveFormat::TarGz),
+            (".tar.xz", ArchiveFormat::TarXz),
+            (".txz", ArchiveFormat::TarXz),
+            (".tar.zst", ArchiveFormat::TarZst),
+            (".tzst", ArchiveFormat::TarZst),
+            (".7z", ArchiveFormat::SevenZ),
+        ];
+
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        SUFFIXES
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix))
+            .map(|(_, format)| *format)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::SevenZ => "7z",
+        }
+    }
+}
+
+/// A location inside an archive file.
+#[derive(Debug, Clone)]
+pub struct ArchivePath {
+    pub archive: PathBuf,
+    pub format: ArchiveFormat,
+    /// `/`-separated path inside the archive; empty for its root.
+    pub inner: String,
+}
+
+impl ArchivePath {
+    /// Recognizes `{archive}!` and `{archive}!/{inner}` where `archive` is an existing
+    /// file in a supported format.
+    pub fn parse(path: &str) -> Option<Self> {
+        for (index, _) in path.match_indices(SEPARATOR) {
+            let rest = &path[index + 1..];
+            if !rest.is_empty() && !rest.starts_with(['/', '\\']) {
+                continue;
+            }
+
+            let archive = PathBuf::from(&path[..index]);
+            let format = match ArchiveFormat::from_path(&archive) {
+                Some(format) if archive.is_file() => format,
+                _ => continue,
+            };
+
+            let inner = rest
+                .split(['/', '\\'])
+                .filter(|c| !c.is_empty())
+                .collect::<Vec<_>>()
+                .join("/");
+            return Some(Self {
+                archive,
+                format,
+                inner,
+            });
+        }
+
+        None
+    }
+
+    pub fn to_path_string(&self) -> String {
+        entry_path(&self.archive, &self.inner)
+    }
+
+    /// Name of the entry this path points at, or of the archive for its root.
+    pub fn name(&self) -> String {
+        match self.inner.rsplit('/').next() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => self
+                .archive
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn entry_path(archive: &Path, inner: &str) -> String {
+    if inner.is_empty() {
+        format!("{}{}", archive.display(), SEPARATOR)
+    } else {
+        format!("{}{}/{}", archive.display(), SEPARATOR, inner)
+    }
+}
+
+/// Archives are browsed read-only, so commands that change files refuse paths inside them.
+pub fn read_only(path: &str) -> AppError {
+    AppError::InvalidOperation(format!("Archives are read-only: {}", path))
+}
+
+/// One file or folder inside an archive, with a normalized `/`-separated path.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<i64>,
+}
+
+struct CachedListing {
+    archive: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+    entries: Arc<Vec<ArchiveEntry>>,
+}
+
+/// Entry lists of recently browsed archives, so entering each folder of a compressed tar
+/// does not decompress the whole archive again.
+pub struct ArchiveCache {
+    listings: Mutex<VecDeque<CachedListing>>,
+}
+
+impl Default for ArchiveCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveCache {
+    pub fn new() -> Self {
+        Self {
+            listings: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn entries(&self, path: &ArchivePath) -> Result<Arc<Vec<ArchiveEntry>>, AppError> {
+        let metadata = std::fs::metadata(&path.archive).map_err(|e| AppError::Io(e.to_string()))?;
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        {
+            let mut listings = self.listings.lock();
+            let position = listings.iter().position(|l| l.archive == path.archive);
+            if let Some(listing) = position.and_then(|p| listings.remove(p)) {
+                if listing.modified == modified && listing.len == len {
+                    let entries = listing.entries.clone();
+                    listings.push_front(listing);
+                    return Ok(entries);
+                }
+            }
+        }
+
+        let entries = formats::read_entries(&path.archive, path.format)
+            .map_err(|e| AppError::Io(format!("{}: {}", path.archive.display(), e)))?;
+        let entries = Arc::new(with_implied_dirs(entries));
+
+        let mut listings = self.listings.lock();
+        listings.push_front(CachedListing {
+            archive: path.archive.clone(),
+            modified,
+            len,
+            entries: entries.clone(),
+        });
+        listings.truncate(CACHED_ARCHIVES);
+
+        Ok(entries)
+    }
+
+    /// Lists one folder of an archive the way `list_directory` lists a real one.
+    pub fn list(&self, path: &ArchivePath) -> Result<Vec<FileEntry>, AppError> {
+        let entries = self.entries(path)?;
+
+        if !path.inner.is_empty() {
+            match entries.iter().find(|e| e.path == path.inner) {
+                Some(entry) if entry.is_dir => {}
+                Some(_) => return Err(AppError::NotADirectory(path.to_path_string())),
+                None => return Err(AppError::NotFound(path.to_path_string())),
+            }
+        }
+
+        let mut listing: Vec<FileEntry> = entries
+            .iter()
+            .filter(|e| parent_of(&e.path) == path.inner)
+            .map(|e| to_file_entry(&path.archive, e))
+            .collect();
+        sort_entries(&mut listing);
+
+        Ok(listing)
+    }
+
+    pub fn file_entry(&self, path: &ArchivePath) -> Result<FileEntry, AppError> {
+        if path.inner.is_empty() {
+            return Ok(FileEntry::new(
+                path.name(),
+                path.to_path_string(),
+                true,
+                None,
+                None,
+            ));
+        }
+
+        self.entries(path)?
+            .iter()
+            .find(|e| e.path == path.inner)
+            .map(|e| to_file_entry(&path.archive, e))
+            .ok_or_else(|| AppError::NotFound(path.to_path_string()))
+    }
+}
+
+/// Normalizes entry paths and adds folders that only exist implicitly, as in archives
+/// that store `a/b/c.txt` without separate entries for `a` and `a/b`.
+fn with_implied_dirs(entries: Vec<ArchiveEntry>) -> Vec<ArchiveEntry> {
+    let mut by_path: BTreeMap<String, ArchiveEntry> = BTreeMap::new();
+
+    for mut entry in entries {
+        entry.path = normalize(&entry.path);
+        // Entries escaping the archive are never listed, nor extracted
+        if entry.path.is_empty() || entry.path.split('/').any(|c| c == "..") {
+            continue;
+        }
+
+        let mut parent = parent_of(&entry.path);
+        while !parent.is_empty() && !by_path.contains_key(parent) {
+            by_path.insert(
+                parent.to_string(),
+                ArchiveEntry {
+                    path: parent.to_string(),
+                    is_dir: true,
+                    size: 0,
+                    modified: None,
+                },
+            );
+            parent = parent_of(parent);
+        }
+
+        // Later entries win, as when a tar is extracted
+        by_path.insert(entry.path.clone(), entry);
+    }
+
+    by_path.into_values().collect()
+}
+
+fn normalize(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn to_file_entry(archive: &Path, entry: &ArchiveEntry) -> FileEntry {
+    let name = entry
+        .path
+        .rsplit('/')
+        .next()
+        .unwrap_or(&entry.path)
+        .to_string();
+    FileEntry::new(
+        name,
+        entry_path(archive, &entry.path),
+        entry.is_dir,
+        Some(entry.size),
+        entry.modified,
+    )
+}
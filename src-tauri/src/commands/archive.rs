@@ -0,0 +1,118 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::fs::{AppError, ConflictPolicy};
+use crate::jobs::JobRegistry;
+use crate::vfs::{ArchiveFs, VfsRegistry};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Packs `sources` into a new archive and returns the id of the job doing it. A `dest`
+/// without the format's extension gets it appended.
+#[tauri::command]
+pub async fn create_archive(
+    sources: Vec<String>,
+    dest: String,
+    format: ArchiveFormat,
+    level: Option<u32>,
+    conflict: Option<ConflictPolicy>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    let vfs = app.state::<VfsRegistry>();
+    archive::validate_level(format, level)?;
+    if sources.is_empty() {
+        return Err(AppError::InvalidOperation("Nothing to archive".into()));
+    }
+
+    let sources = sources
+        .iter()
+        .map(|source| local_path(&vfs, source))
+        .collect::<Result<Vec<_>, _>>()?;
+    for source in &sources {
+        if source.symlink_metadata().is_err() {
+            return Err(AppError::NotFound(source.display().to_string()));
+        }
+    }
+
+    let mut dest = local_path(&vfs, &dest)?;
+    if ArchiveFormat::from_path(&dest) != Some(format) {
+        let name = dest
+            .file_name()
+            .ok_or_else(|| AppError::InvalidOperation("Invalid archive path".into()))?
+            .to_string_lossy()
+            .to_string();
+        dest.set_file_name(format!("{}.{}", name, format.extension()));
+    }
+    ensure_directory(dest.parent().unwrap_or(Path::new("")))?;
+
+    let conflict = conflict.unwrap_or_default();
+    Ok(app
+        .state::<JobRegistry>()
+        .spawn(&app, "create_archive", move |job| {
+            match conflict.resolve(&dest)? {
+                Some(dest) => archive::create(&sources, &dest, format, level, job),
+                None => Ok(()),
+            }
+        }))
+}
+
+/// Extracts an archive, or the `selection` of entries in it, into `dest` and returns the
+/// id of the job doing it. Entries are paths inside the archive, such as `docs/a.txt`, or
+/// full `{archive}!/{inner}` paths as listed by `read_directory`.
+#[tauri::command]
+pub async fn extract_archive(
+    archive: String,
+    dest: String,
+    selection: Option<Vec<String>>,
+    conflict: Option<ConflictPolicy>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    let vfs = app.state::<VfsRegistry>();
+    let archive_path = local_path(&vfs, archive.trim_end_matches('!'))?;
+    let format = match ArchiveFormat::from_path(&archive_path) {
+        Some(format) if archive_path.is_file() => format,
+        Some(_) => return Err(AppError::NotFound(archive)),
+        None => {
+            return Err(AppError::InvalidOperation(format!(
+                "Not a supported archive: {}",
+                archive
+            )))
+        }
+    };
+    let dest = local_path(&vfs, &dest)?;
+    ensure_directory(&dest)?;
+
+    let selected: Vec<String> = match selection {
+        Some(selection) if !selection.is_empty() => selection
+            .into_iter()
+            .map(|entry| match ArchiveFs::parse(&entry) {
+                Some(path) if path.archive == archive_path => path.inner,
+                _ => entry,
+            })
+            .collect(),
+        _ => vec![String::new()],
+    };
+
+    let conflict = conflict.unwrap_or_default();
+    Ok(app
+        .state::<JobRegistry>()
+        .spawn(&app, "extract_archive", move |job| {
+            archive::extract(&archive_path, format, &selected, &dest, conflict, Some(job))
+                .map(|_| ())
+        }))
+}
+
+/// Archives are packed and unpacked on the local disk only.
+fn local_path(vfs: &VfsRegistry, path: &str) -> Result<PathBuf, AppError> {
+    vfs.resolve(path)
+        .local_path(path)
+        .ok_or_else(|| AppError::InvalidOperation(format!("Not a local file or folder: {}", path)))
+}
+
+fn ensure_directory(path: &Path) -> Result<(), AppError> {
+    if !path.exists() {
+        return Err(AppError::NotFound(path.display().to_string()));
+    }
+    if !path.is_dir() {
+        return Err(AppError::NotADirectory(path.display().to_string()));
+    }
+    Ok(())
+}
@@ -29,6 +29,11 @@ fn log_debug(msg: &str) {
         .and_then(|mut f| std::io::Write::write_all(&mut f, line.as_bytes()));
 }
 
+/// When the config was last saved, so long-running tasks can tell when to load it again.
+pub fn config_modified() -> Option<std::time::SystemTime> {
+    std::fs::metadata(config_path()).and_then(|m| m.modified()).ok()
+}
+
 #[tauri::command]
 pub fn load_config() -> Config {
     let path = config_path();
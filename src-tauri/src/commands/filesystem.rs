@@ -1,73 +1,109 @@
+use crate::commands::config::load_config;
+use crate::frecency::{FrecencyStore, VisitKind};
+use crate::fs::volumes::{self, DiskSpace};
 use crate::fs::{FileEntry, AppError};
-use std::path::PathBuf;
+use crate::indexer::state::IndexerState;
+use crate::indexer::watcher::DirectoryWatcher;
+use crate::search::saved;
+use crate::tags::TagStore;
+use crate::vfs::VfsRegistry;
+use tauri::{AppHandle, Manager, State};
 
+/// Lists a directory of any provider and counts it as visited, unless `record_visit` is
+/// false (e.g. for path completion or refreshes). `search://{name}` paths list a saved
+/// search's results.
 #[tauri::command]
-pub async fn read_directory(path: String) -> Result<Vec<FileEntry>, AppError> {
-    let path = PathBuf::from(&path);
-
-    if !path.exists() {
-        return Err(AppError::NotFound(path.display().to_string()));
-    }
-
-    if !path.is_dir() {
-        return Err(AppError::NotADirectory(path.display().to_string()));
+pub async fn read_directory(
+    path: String,
+    record_visit: Option<bool>,
+    frecency: State<'_, FrecencyStore>,
+    tags: State<'_, TagStore>,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<Vec<FileEntry>, AppError> {
+    if let Some(name) = saved::name_from_path(&path) {
+        let search = saved::find(name)?;
+        // Walks folders or scans the index, which can take a while
+        return tokio::task::spawn_blocking(move || {
+            let source = saved::Source::resolve(&search, &app.state::<IndexerState>())?;
+            Ok(saved::run(&search, &source, &app.state::<TagStore>()))
+        })
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?;
     }
 
-    let mut entries = Vec::new();
-
-    let read_dir = std::fs::read_dir(&path)
-        .map_err(|e| AppError::Io(e.to_string()))?;
+    let provider = vfs.resolve(&path);
+    let local = provider.local_path(&path);
+    // Remote providers wait on the server, which can take a while
+    let mut entries = tokio::task::spawn_blocking(move || provider.list(&path))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))??;
 
-    for entry in read_dir {
-        let entry = entry.map_err(|e| AppError::Io(e.to_string()))?;
-        let metadata = entry.metadata().map_err(|e| AppError::Io(e.to_string()))?;
-        let file_name = entry.file_name().to_string_lossy().to_string();
+    // Tags and frecency follow local files only
+    if let Some(local) = local {
+        tags.annotate(&mut entries);
+        if record_visit.unwrap_or(true) {
+            frecency.record(&local, VisitKind::Directory);
+        }
+    }
+    Ok(entries)
+}
 
-        let extension = if metadata.is_file() {
-            PathBuf::from(&file_name)
-                .extension()
-                .map(|e| e.to_string_lossy().to_string())
-        } else {
-            None
-        };
+#[tauri::command]
+pub async fn watch_directory(
+    pane_id: String,
+    path: String,
+    watcher: State<'_, DirectoryWatcher>,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    let settings = load_config().watcher;
+    if let Some(name) = saved::name_from_path(&path) {
+        return watcher.watch_search(pane_id, saved::find(name)?, &settings, app);
+    }
 
-        let modified = metadata
-            .modified()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs() as i64);
+    // Only the local disk sends change notifications
+    match vfs.resolve(&path).local_path(&path) {
+        Some(local) => watcher.watch(pane_id, local, &settings, app),
+        None => {
+            watcher.unwatch(&pane_id);
+            Ok(())
+        }
+    }
+}
 
-        let is_hidden = file_name.starts_with('.');
+#[tauri::command]
+pub async fn unwatch_directory(
+    pane_id: String,
+    watcher: State<'_, DirectoryWatcher>,
+) -> Result<(), AppError> {
+    watcher.unwatch(&pane_id);
+    Ok(())
+}
 
-        entries.push(FileEntry {
-            name: file_name,
-            path: entry.path().display().to_string(),
-            extension,
-            size: if metadata.is_file() { Some(metadata.len()) } else { None },
-            modified,
-            is_dir: metadata.is_dir(),
-            is_hidden,
-            is_symlink: metadata.is_symlink(),
-        });
+#[tauri::command]
+pub async fn get_parent_directory(
+    path: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<Option<String>, AppError> {
+    // Saved searches are virtual folders without a parent
+    if saved::name_from_path(&path).is_some() {
+        return Ok(None);
     }
 
-    // Sort: directories first, then by name
-    entries.sort_by(|a, b| {
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
-    });
-
-    Ok(entries)
+    Ok(vfs.resolve(&path).parent(&path))
 }
 
+/// Space on the volume holding a local path, for the status bar.
 #[tauri::command]
-pub async fn get_parent_directory(path: String) -> Option<String> {
-    PathBuf::from(&path)
-        .parent()
-        .map(|p| p.display().to_string())
+pub async fn get_disk_space(
+    path: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<DiskSpace, AppError> {
+    let local = vfs.resolve(&path).local_path(&path).ok_or_else(|| {
+        AppError::InvalidOperation(format!("Disk space is only known for local paths: {}", path))
+    })?;
+    volumes::disk_space(&local).ok_or(AppError::NotFound(path))
 }
 
 #[tauri::command]
@@ -75,7 +111,24 @@ pub async fn get_home_directory() -> Option<String> {
     dirs::home_dir().map(|p| p.display().to_string())
 }
 
+/// Opens a file with its default application. Files that are not on the local disk, such
+/// as those inside archives, are copied to a temporary folder and opened from there.
 #[tauri::command]
-pub async fn open_file(path: String) -> Result<(), AppError> {
-    open::that(&path).map_err(|e| AppError::Io(e.to_string()))
+pub async fn open_file(
+    path: String,
+    frecency: State<'_, FrecencyStore>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), AppError> {
+    let provider = vfs.resolve(&path);
+    let is_local = provider.local_path(&path).is_some();
+    // Files elsewhere are downloaded first
+    let local = tokio::task::spawn_blocking(move || provider.fetch(&path))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))??;
+    open::that(&local).map_err(|e| AppError::Io(e.to_string()))?;
+
+    if is_local {
+        frecency.record(&local, VisitKind::File);
+    }
+    Ok(())
 }
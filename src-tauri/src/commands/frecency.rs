@@ -0,0 +1,30 @@
+use crate::frecency::{FrecencyStore, FrecentPath, VisitKind};
+use crate::fs::AppError;
+use tauri::State;
+
+/// Best matching directories for a `z`-style query such as `proj api`.
+#[tauri::command]
+pub async fn jump_to(
+    query: String,
+    limit: Option<usize>,
+    frecency: State<'_, FrecencyStore>,
+) -> Result<Vec<FrecentPath>, AppError> {
+    Ok(frecency.jump(&query, limit.unwrap_or(10)))
+}
+
+#[tauri::command]
+pub async fn get_frecent_paths(
+    kind: Option<VisitKind>,
+    limit: Option<usize>,
+    frecency: State<'_, FrecencyStore>,
+) -> Result<Vec<FrecentPath>, AppError> {
+    Ok(frecency.top(kind, limit.unwrap_or(50)))
+}
+
+#[tauri::command]
+pub async fn forget_frecent_path(
+    path: String,
+    frecency: State<'_, FrecencyStore>,
+) -> Result<bool, AppError> {
+    Ok(frecency.forget(&path))
+}
@@ -1,96 +1,237 @@
+use crate::commands::config::load_config;
+use crate::frecency::FrecencyStore;
 use crate::fs::{AppError, FileEntry};
-use crate::indexer::entry::{IndexProgress, IndexStatus};
-use crate::indexer::{cache, scanner, watcher::FileWatcher, IndexManager};
-use parking_lot::Mutex;
-use std::path::PathBuf;
-use std::sync::Arc;
+use crate::indexer::cache;
+use crate::indexer::entry::{IndexProgress, IndexStats, IndexStatus, RootProgress};
+use crate::indexer::scanner::ScanOptions;
+use crate::indexer::scope::{validate_scope_name, DEFAULT_SCOPE};
+use crate::indexer::state::{start_scope, IndexerState};
+use crate::search::query::SearchQuery;
+use crate::tags::TagStore;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, State};
 
-pub struct IndexerState {
-    pub manager: Arc<IndexManager>,
-    pub watcher: Mutex<FileWatcher>,
-}
-
-impl IndexerState {
-    pub fn new() -> Self {
-        Self {
-            manager: Arc::new(IndexManager::new()),
-            watcher: Mutex::new(FileWatcher::new()),
-        }
-    }
-}
-
-impl Default for IndexerState {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Merges per-scope results, dropping paths that overlapping scopes both returned.
+fn merge_results(
+    results: impl IntoIterator<Item = Vec<FileEntry>>,
+    limit: usize,
+) -> Vec<FileEntry> {
+    let mut seen = HashSet::new();
+    results
+        .into_iter()
+        .flatten()
+        .filter(|entry| seen.insert(entry.path.clone()))
+        .take(limit)
+        .collect()
 }
 
 #[tauri::command]
 pub async fn start_indexing(
     roots: Vec<String>,
+    index_content: Option<bool>,
+    scan_options: Option<ScanOptions>,
+    scope: Option<String>,
     state: State<'_, IndexerState>,
     app: AppHandle,
 ) -> Result<(), AppError> {
-    let root_paths: Vec<PathBuf> = roots.into_iter().map(PathBuf::from).collect();
-
-    state.watcher.lock().stop();
+    let name = scope.unwrap_or_else(|| DEFAULT_SCOPE.to_string());
+    start_scope(&state, &name, roots, index_content, scan_options, app).await
+}
 
-    let manager = state.manager.clone();
-    let app_clone = app.clone();
-    let roots_clone = root_paths.clone();
+#[tauri::command]
+pub async fn add_index_root(
+    scope: String,
+    root: String,
+    state: State<'_, IndexerState>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    state
+        .scope(&scope)?
+        .add_root(PathBuf::from(root), app)
+        .await
+}
 
-    if cache::load_cache(&manager).unwrap_or(false) && manager.get_count() > 0 {
-        manager.set_status(IndexStatus::Watching, Some(&app));
-        manager.emit_progress(&app, None);
+#[tauri::command]
+pub async fn remove_index_root(
+    scope: String,
+    root: String,
+    state: State<'_, IndexerState>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    state
+        .scope(&scope)?
+        .remove_root(&PathBuf::from(root), app)
+        .await
+}
 
-        let _ = state
-            .watcher
-            .lock()
-            .start(manager.clone(), root_paths, app);
-        return Ok(());
+/// Lists running scopes plus configured ones that have not been started yet.
+#[tauri::command]
+pub async fn get_index_scopes(
+    state: State<'_, IndexerState>,
+) -> Result<Vec<IndexProgress>, AppError> {
+    let mut scopes: Vec<IndexProgress> = state
+        .scopes()
+        .iter()
+        .map(|scope| scope.manager().progress(None))
+        .collect();
+
+    for defined in load_config().index_scopes {
+        if scopes.iter().any(|s| s.scope == defined.name) {
+            continue;
+        }
+        scopes.push(IndexProgress {
+            scope: defined.name,
+            status: IndexStatus::Idle,
+            indexed_count: 0,
+            content_count: 0,
+            current_path: None,
+            roots: defined
+                .roots
+                .iter()
+                .map(|root| RootProgress::pending(&PathBuf::from(root)))
+                .collect(),
+            stats: IndexStats::default(),
+        });
     }
 
-    tokio::spawn(async move {
-        scanner::scan_directories(manager.clone(), roots_clone.clone(), app_clone.clone()).await;
-
-        let _ = cache::save_cache(&manager);
+    Ok(scopes)
+}
 
-        let mut watcher = FileWatcher::new();
-        let _ = watcher.start(manager, roots_clone, app_clone);
-    });
+#[tauri::command]
+pub async fn remove_index_scope(
+    scope: String,
+    state: State<'_, IndexerState>,
+) -> Result<(), AppError> {
+    if scope == DEFAULT_SCOPE {
+        return Err(AppError::InvalidOperation(
+            "The default index scope cannot be removed".into(),
+        ));
+    }
 
-    Ok(())
+    let removed = state.remove(&scope);
+    match removed {
+        Some(removed) => {
+            removed.shutdown().await;
+            cache::clear_cache(Some(&scope)).map_err(|e| AppError::Io(e.to_string()))
+        }
+        None => Err(AppError::NotFound(format!("Index scope '{}'", scope))),
+    }
 }
 
+/// Searches the index, ranking frequently and recently used paths first. `tag:` filters
+/// start from the tag store rather than the index, which is far larger.
 #[tauri::command]
 pub async fn search_index(
     query: String,
     limit: Option<usize>,
+    scope: Option<String>,
     state: State<'_, IndexerState>,
+    frecency: State<'_, FrecencyStore>,
+    tags: State<'_, TagStore>,
 ) -> Result<Vec<FileEntry>, AppError> {
     let limit = limit.unwrap_or(1000);
-    Ok(state.manager.search(&query, limit))
+    let scopes = state.selected(scope.as_deref())?;
+    let parsed = SearchQuery::parse(&query);
+
+    let mut results = if parsed.tags.is_empty() {
+        // Frecent matches may lie past the point where the plain index search stops
+        let frecent: Vec<FileEntry> = frecency
+            .matching(&query, limit)
+            .into_iter()
+            .filter_map(|f| {
+                scopes
+                    .iter()
+                    .find_map(|s| s.manager().get_entry(Path::new(&f.path)))
+            })
+            .collect();
+
+        merge_results(
+            std::iter::once(frecent)
+                .chain(scopes.iter().map(|s| s.manager().search(&query, limit))),
+            limit,
+        )
+    } else {
+        tags.paths_with_tags(&parsed.tags)
+            .into_iter()
+            .filter(|path| parsed.matches_text(path))
+            .filter_map(|path| {
+                scopes
+                    .iter()
+                    .find_map(|s| s.manager().get_entry(Path::new(&path)))
+            })
+            .take(limit)
+            .collect()
+    };
+    tags.annotate(&mut results);
+
+    let scores: HashMap<String, f64> = results
+        .iter()
+        .map(|entry| (entry.path.clone(), frecency.score(&entry.path)))
+        .collect();
+    // Stable, so paths without a score keep the index order
+    results.sort_by(|a, b| scores[&b.path].total_cmp(&scores[&a.path]));
+
+    Ok(results)
 }
 
 #[tauri::command]
-pub async fn get_index_status(state: State<'_, IndexerState>) -> Result<IndexProgress, AppError> {
-    Ok(IndexProgress {
-        status: state.manager.get_status(),
-        indexed_count: state.manager.get_count(),
-        current_path: None,
-    })
+pub async fn search_content_index(
+    query: String,
+    limit: Option<usize>,
+    scope: Option<String>,
+    state: State<'_, IndexerState>,
+    tags: State<'_, TagStore>,
+) -> Result<Vec<FileEntry>, AppError> {
+    let limit = limit.unwrap_or(1000);
+    let scopes: Vec<_> = state
+        .selected(scope.as_deref())?
+        .into_iter()
+        .filter(|s| s.manager().content().is_enabled())
+        .collect();
+
+    if scopes.is_empty() {
+        return Err(AppError::InvalidOperation(
+            "Content indexing is not enabled".into(),
+        ));
+    }
+
+    let mut results = merge_results(
+        scopes
+            .iter()
+            .map(|s| s.manager().search_content(&query, limit)),
+        limit,
+    );
+    tags.annotate(&mut results);
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn get_index_status(
+    scope: Option<String>,
+    state: State<'_, IndexerState>,
+) -> Result<IndexProgress, AppError> {
+    let scope = state.scope(scope.as_deref().unwrap_or(DEFAULT_SCOPE))?;
+    Ok(scope.manager().progress(None))
 }
 
 #[tauri::command]
-pub async fn stop_indexing(state: State<'_, IndexerState>, app: AppHandle) -> Result<(), AppError> {
-    state.manager.request_stop();
-    state.watcher.lock().stop();
-    state.manager.set_status(IndexStatus::Idle, Some(&app));
+pub async fn stop_indexing(
+    scope: Option<String>,
+    state: State<'_, IndexerState>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    for scope in state.selected(scope.as_deref())? {
+        scope.shutdown().await;
+        scope.manager().set_status(IndexStatus::Idle, Some(&app));
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub async fn clear_index_cache() -> Result<(), AppError> {
-    cache::clear_cache().map_err(|e| AppError::Io(e.to_string()))
+pub async fn clear_index_cache(scope: Option<String>) -> Result<(), AppError> {
+    if let Some(scope) = &scope {
+        validate_scope_name(scope)?;
+    }
+    cache::clear_cache(scope.as_deref()).map_err(|e| AppError::Io(e.to_string()))
 }
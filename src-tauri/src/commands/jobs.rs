@@ -0,0 +1,15 @@
+use crate::fs::AppError;
+use crate::jobs::{JobProgress, JobRegistry};
+use tauri::State;
+
+#[tauri::command]
+pub async fn cancel_job(job_id: String, jobs: State<'_, JobRegistry>) -> Result<(), AppError> {
+    jobs.cancel(&job_id);
+    Ok(())
+}
+
+/// Jobs that are still running, e.g. for a window that was reloaded while they ran.
+#[tauri::command]
+pub async fn get_jobs(jobs: State<'_, JobRegistry>) -> Result<Vec<JobProgress>, AppError> {
+    Ok(jobs.list())
+}
@@ -4,3 +4,9 @@ pub mod wsl;
 pub mod operations;
 pub mod search;
 pub mod indexer;
+pub mod frecency;
+pub mod tags;
+pub mod archive;
+pub mod jobs;
+pub mod remotes;
+pub mod queue;
@@ -1,78 +1,110 @@
-use crate::fs::{AppError, UndoToken};
-use std::path::PathBuf;
-
+use crate::frecency::{FrecencyStore, VisitKind};
+use crate::fs::{AppError, ConflictPolicy, UndoToken};
+use crate::jobs::JobRegistry;
+use crate::tags::TagStore;
+use crate::transfer::{copy_items, move_items};
+use crate::vfs::{self, CopyPlan, VfsRegistry};
+use tauri::{AppHandle, Manager, State};
+
+/// Runs as a job, so progress and cancellation work the same for every provider.
+/// Refuses to start on the problems `plan_copy` reports, unless `ignore_problems` says
+/// to go ahead anyway.
 #[tauri::command]
-pub async fn copy_files(sources: Vec<String>, dest: String) -> Result<(), AppError> {
-    let dest_path = PathBuf::from(&dest);
-
-    if !dest_path.exists() {
-        return Err(AppError::NotFound(dest));
-    }
-
-    if !dest_path.is_dir() {
-        return Err(AppError::NotADirectory(dest));
-    }
-
-    for source in sources {
-        let source_path = PathBuf::from(&source);
-        let file_name = source_path
-            .file_name()
-            .ok_or_else(|| AppError::InvalidOperation("Invalid source path".into()))?;
-
-        let target = dest_path.join(file_name);
+pub async fn copy_files(
+    sources: Vec<String>,
+    dest: String,
+    conflict: Option<ConflictPolicy>,
+    ignore_problems: Option<bool>,
+    frecency: State<'_, FrecencyStore>,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    let conflict = conflict.unwrap_or_default();
+    let local_dest = vfs.resolve(&dest).local_path(&dest);
+
+    let handle = app.clone();
+    let ignore_problems = ignore_problems.unwrap_or(false);
+    let jobs = app.state::<JobRegistry>();
+    jobs.run(&app, "copy_files", move |job| {
+        copy_items(
+            &handle,
+            &sources,
+            &dest,
+            conflict,
+            ignore_problems,
+            None,
+            job,
+        )
+    })
+    .await?;
 
-        if source_path.is_dir() {
-            copy_dir_recursive(&source_path, &target)?;
-        } else {
-            std::fs::copy(&source_path, &target)
-                .map_err(|e| AppError::Io(e.to_string()))?;
-        }
+    if let Some(dest_path) = local_dest {
+        frecency.record(&dest_path, VisitKind::Directory);
     }
-
     Ok(())
 }
 
+/// Works out what copying `sources` into `dest` involves and what would stop it, so it
+/// can be confirmed before it starts. `copy_files` refuses to start on the same problems.
 #[tauri::command]
-pub async fn move_files(sources: Vec<String>, dest: String) -> Result<(), AppError> {
-    let dest_path = PathBuf::from(&dest);
-
-    if !dest_path.exists() {
-        return Err(AppError::NotFound(dest));
-    }
+pub async fn plan_copy(
+    sources: Vec<String>,
+    dest: String,
+    conflict: Option<ConflictPolicy>,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<CopyPlan, AppError> {
+    let conflict = conflict.unwrap_or_default();
+    let dest_fs = vfs.resolve(&dest);
+    let sources: Vec<_> = sources
+        .into_iter()
+        .map(|source| (vfs.resolve(&source), source))
+        .collect();
+
+    tokio::task::spawn_blocking(move || vfs::plan_copy(&sources, &*dest_fs, &dest, conflict, None))
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+}
 
-    if !dest_path.is_dir() {
-        return Err(AppError::NotADirectory(dest));
-    }
+/// Runs as a job like `copy_files`.
+#[tauri::command]
+pub async fn move_files(
+    sources: Vec<String>,
+    dest: String,
+    conflict: Option<ConflictPolicy>,
+    ignore_problems: Option<bool>,
+    frecency: State<'_, FrecencyStore>,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    let conflict = conflict.unwrap_or_default();
+    let local_dest = vfs.resolve(&dest).local_path(&dest);
+
+    let handle = app.clone();
+    let ignore_problems = ignore_problems.unwrap_or(false);
+    let jobs = app.state::<JobRegistry>();
+    jobs.run(&app, "move_files", move |job| {
+        move_items(
+            &handle,
+            &sources,
+            &dest,
+            conflict,
+            ignore_problems,
+            None,
+            job,
+        )
+    })
+    .await?;
 
-    for source in sources {
-        let source_path = PathBuf::from(&source);
-        let file_name = source_path
-            .file_name()
-            .ok_or_else(|| AppError::InvalidOperation("Invalid source path".into()))?;
-
-        let target = dest_path.join(file_name);
-
-        // Try rename first (faster for same filesystem)
-        if std::fs::rename(&source_path, &target).is_err() {
-            // Fall back to copy + delete for cross-filesystem moves
-            if source_path.is_dir() {
-                copy_dir_recursive(&source_path, &target)?;
-                std::fs::remove_dir_all(&source_path)
-                    .map_err(|e| AppError::Io(e.to_string()))?;
-            } else {
-                std::fs::copy(&source_path, &target)
-                    .map_err(|e| AppError::Io(e.to_string()))?;
-                std::fs::remove_file(&source_path)
-                    .map_err(|e| AppError::Io(e.to_string()))?;
-            }
-        }
+    if let Some(dest_path) = local_dest {
+        frecency.record(&dest_path, VisitKind::Directory);
     }
-
     Ok(())
 }
 
+/// Moves local files to the trash; other providers have none, so their files are removed.
+/// Tags of deleted local files are dropped.
 #[tauri::command]
-pub async fn delete_files(paths: Vec<String>) -> Result<UndoToken, AppError> {
+pub async fn delete_files(paths: Vec<String>, app: AppHandle) -> Result<UndoToken, AppError> {
     let token = UndoToken {
         id: uuid::Uuid::new_v4().to_string(),
         operation: "delete".to_string(),
@@ -80,105 +112,109 @@ pub async fn delete_files(paths: Vec<String>) -> Result<UndoToken, AppError> {
         backup_paths: Vec::new(),
     };
 
-    for path in &paths {
-        let path = PathBuf::from(path);
-        trash::delete(&path).map_err(|e| AppError::Io(e.to_string()))?;
-    }
-
+    // Providers reach disks and servers, which can take a while
+    tokio::task::spawn_blocking(move || {
+        let (vfs, tags) = (app.state::<VfsRegistry>(), app.state::<TagStore>());
+        for path in &paths {
+            vfs.resolve(path).check_writable(path)?;
+        }
+        let result = delete_each(&paths, &vfs, &tags);
+        let _ = tags.save();
+        result
+    })
+    .await
+    .map_err(|e| AppError::Io(e.to_string()))??;
     Ok(token)
 }
 
-#[tauri::command]
-pub async fn create_directory(parent_path: String, name: String) -> Result<String, AppError> {
-    let new_path = PathBuf::from(&parent_path).join(&name);
-
-    if new_path.exists() {
-        return Err(AppError::InvalidOperation(format!(
-            "Directory already exists: {}",
-            name
-        )));
+fn delete_each(paths: &[String], vfs: &VfsRegistry, tags: &TagStore) -> Result<(), AppError> {
+    for path in paths {
+        let provider = vfs.resolve(path);
+        match provider.local_path(path) {
+            Some(local) => {
+                trash::delete(&local).map_err(|e| AppError::Io(e.to_string()))?;
+                tags.forget(&local);
+            }
+            None => provider.remove(path)?,
+        }
     }
-
-    std::fs::create_dir(&new_path).map_err(|e| AppError::Io(e.to_string()))?;
-
-    Ok(new_path.display().to_string())
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn rename_file(path: String, new_name: String) -> Result<String, AppError> {
-    let source = PathBuf::from(&path);
-    let parent = source
-        .parent()
-        .ok_or_else(|| AppError::InvalidOperation("Cannot rename root".into()))?;
-
-    let target = parent.join(&new_name);
-
-    if target.exists() {
-        return Err(AppError::InvalidOperation(format!(
-            "File already exists: {}",
-            new_name
-        )));
-    }
+pub async fn create_directory(
+    parent_path: String,
+    name: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<String, AppError> {
+    let provider = vfs.resolve(&parent_path);
+    tokio::task::spawn_blocking(move || {
+        provider.check_writable(&parent_path)?;
+        let new_path = provider.join(&parent_path, &name);
+
+        if provider.stat(&new_path).is_ok() {
+            return Err(AppError::InvalidOperation(format!(
+                "Directory already exists: {}",
+                name
+            )));
+        }
 
-    std::fs::rename(&source, &target).map_err(|e| AppError::Io(e.to_string()))?;
+        provider.mkdir(&new_path)?;
 
-    Ok(target.display().to_string())
+        Ok(new_path)
+    })
+    .await
+    .map_err(|e| AppError::Io(e.to_string()))?
 }
 
 #[tauri::command]
-pub async fn get_file_info(path: String) -> Result<crate::fs::FileEntry, AppError> {
-    let path = PathBuf::from(&path);
-
-    if !path.exists() {
-        return Err(AppError::NotFound(path.display().to_string()));
-    }
-
-    let metadata = std::fs::metadata(&path)
-        .map_err(|e| AppError::Io(e.to_string()))?;
-
-    let file_name = path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_default();
+pub async fn rename_file(
+    path: String,
+    new_name: String,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    let provider = vfs.resolve(&path);
+    tokio::task::spawn_blocking(move || {
+        provider.check_writable(&path)?;
+        let parent = provider
+            .parent(&path)
+            .ok_or_else(|| AppError::InvalidOperation("Cannot rename root".into()))?;
+
+        let target = provider.join(&parent, &new_name);
+
+        if provider.stat(&target).is_ok() {
+            return Err(AppError::InvalidOperation(format!(
+                "File already exists: {}",
+                new_name
+            )));
+        }
 
-    let extension = if metadata.is_file() {
-        path.extension().map(|e| e.to_string_lossy().to_string())
-    } else {
-        None
-    };
+        provider.rename(&path, &target)?;
+        if let (Some(from), Some(to)) = (provider.local_path(&path), provider.local_path(&target)) {
+            let tags = app.state::<TagStore>();
+            tags.rename(&from, &to);
+            let _ = tags.save();
+        }
 
-    let modified = metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64);
-
-    Ok(crate::fs::FileEntry {
-        name: file_name.clone(),
-        path: path.display().to_string(),
-        extension,
-        size: if metadata.is_file() { Some(metadata.len()) } else { None },
-        modified,
-        is_dir: metadata.is_dir(),
-        is_hidden: file_name.starts_with('.'),
-        is_symlink: metadata.is_symlink(),
+        Ok(target)
     })
+    .await
+    .map_err(|e| AppError::Io(e.to_string()))?
 }
 
-fn copy_dir_recursive(src: &PathBuf, dest: &PathBuf) -> Result<(), AppError> {
-    std::fs::create_dir_all(dest).map_err(|e| AppError::Io(e.to_string()))?;
-
-    for entry in std::fs::read_dir(src).map_err(|e| AppError::Io(e.to_string()))? {
-        let entry = entry.map_err(|e| AppError::Io(e.to_string()))?;
-        let src_path = entry.path();
-        let dest_path = dest.join(entry.file_name());
-
-        if src_path.is_dir() {
-            copy_dir_recursive(&src_path, &dest_path)?;
-        } else {
-            std::fs::copy(&src_path, &dest_path).map_err(|e| AppError::Io(e.to_string()))?;
-        }
-    }
-
-    Ok(())
+#[tauri::command]
+pub async fn get_file_info(
+    path: String,
+    vfs: State<'_, VfsRegistry>,
+    app: AppHandle,
+) -> Result<crate::fs::FileEntry, AppError> {
+    let provider = vfs.resolve(&path);
+    tokio::task::spawn_blocking(move || {
+        let mut entry = provider.stat(&path)?;
+        entry.tags = app.state::<TagStore>().tags_for(&entry.path);
+        Ok(entry)
+    })
+    .await
+    .map_err(|e| AppError::Io(e.to_string()))?
 }
@@ -0,0 +1,97 @@
+use crate::fs::{AppError, ConflictPolicy};
+use crate::journal::{InterruptedOperation, Journals};
+use crate::queue::{OperationKind, OperationQueue, QueueState};
+use tauri::{AppHandle, State};
+
+/// Queues a copy or move and returns its id. It starts once no earlier operation uses
+/// the disks it touches; `queue:changed` reports the queue, and the job's own events its
+/// progress. `ignore_problems` is as for `copy_files`.
+#[tauri::command]
+pub async fn queue_operation(
+    kind: OperationKind,
+    sources: Vec<String>,
+    dest: String,
+    conflict: Option<ConflictPolicy>,
+    ignore_problems: Option<bool>,
+    queue: State<'_, OperationQueue>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    if sources.is_empty() {
+        return Err(AppError::InvalidOperation("Nothing to copy or move".into()));
+    }
+    Ok(queue.push(
+        &app,
+        kind,
+        sources,
+        dest,
+        conflict.unwrap_or_default(),
+        ignore_problems.unwrap_or(false),
+    ))
+}
+
+#[tauri::command]
+pub async fn get_operation_queue(queue: State<'_, OperationQueue>) -> Result<QueueState, AppError> {
+    Ok(queue.snapshot())
+}
+
+/// Moves a waiting operation to `index` in the queue.
+#[tauri::command]
+pub async fn reorder_operation(
+    id: String,
+    index: usize,
+    queue: State<'_, OperationQueue>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    queue.reorder(&app, &id, index)
+}
+
+/// Takes an operation off the queue, cancelling it if it already runs.
+#[tauri::command]
+pub async fn remove_operation(
+    id: String,
+    queue: State<'_, OperationQueue>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    queue.remove(&app, &id)
+}
+
+/// Pausing stops further operations from starting; running ones finish.
+#[tauri::command]
+pub async fn set_queue_paused(
+    paused: bool,
+    queue: State<'_, OperationQueue>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    queue.set_paused(&app, paused);
+    Ok(())
+}
+
+/// Copies and moves that stopped before they were done, through a crash, a failure or
+/// being cancelled, and can be resumed.
+#[tauri::command]
+pub async fn get_interrupted_operations(
+    journals: State<'_, Journals>,
+) -> Result<Vec<InterruptedOperation>, AppError> {
+    Ok(journals.interrupted())
+}
+
+/// Queues interrupted operations again; they skip what they already did. Without `ids`
+/// this resumes the ones cut short by the app exiting, as happens on startup. Returns the
+/// ids of the queued operations.
+#[tauri::command]
+pub async fn resume_interrupted_operations(
+    ids: Option<Vec<String>>,
+    queue: State<'_, OperationQueue>,
+    app: AppHandle,
+) -> Result<Vec<String>, AppError> {
+    Ok(queue.resume_interrupted(&app, ids.as_deref()))
+}
+
+/// Forgets an interrupted operation, leaving what it wrote in place.
+#[tauri::command]
+pub async fn discard_interrupted_operation(
+    id: String,
+    journals: State<'_, Journals>,
+) -> Result<(), AppError> {
+    journals.discard(&id)
+}
@@ -0,0 +1,30 @@
+use crate::fs::AppError;
+use crate::vfs::VfsRegistry;
+use tauri::State;
+
+/// Stores the password or secret key of the remote called `name` in the OS keyring.
+#[tauri::command]
+pub async fn set_remote_secret(
+    name: String,
+    secret: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), AppError> {
+    vfs.credentials().set(&name, &secret)
+}
+
+#[tauri::command]
+pub async fn forget_remote_secret(
+    name: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<(), AppError> {
+    vfs.credentials().forget(&name)
+}
+
+/// Whether a secret is stored for the remote, which is never sent back itself.
+#[tauri::command]
+pub async fn has_remote_secret(
+    name: String,
+    vfs: State<'_, VfsRegistry>,
+) -> Result<bool, AppError> {
+    Ok(vfs.credentials().get(&name)?.is_some())
+}
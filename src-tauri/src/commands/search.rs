@@ -1,125 +1,82 @@
-use crate::fs::{FileEntry, AppError};
+use crate::commands::config::load_config;
+use crate::fs::volumes::{self, DriveInfo};
+use crate::fs::AppError;
+use crate::indexer::exclusions::ExclusionMatcher;
+use crate::search::content::{self, ContentSearchOptions};
+use crate::search::query::SearchQuery;
+use crate::search::{files, SearchState};
 use std::path::PathBuf;
-use walkdir::WalkDir;
-
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
-pub struct DriveInfo {
-    pub name: String,
-    pub path: String,
-}
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
 
 #[tauri::command]
 pub async fn search_files(
     query: String,
     root_paths: Vec<String>,
     limit: Option<usize>,
-) -> Result<Vec<FileEntry>, AppError> {
+    state: State<'_, SearchState>,
+    app: AppHandle,
+) -> Result<String, AppError> {
     let limit = limit.unwrap_or(500);
-    let query_lower = query.to_lowercase();
 
-    if query_lower.is_empty() {
-        return Ok(Vec::new());
+    let query = SearchQuery::parse(&query);
+    if query.is_empty() {
+        return Err(AppError::InvalidOperation("Empty search query".into()));
     }
 
-    let mut results = Vec::new();
-
-    for root in root_paths {
-        let root_path = PathBuf::from(&root);
+    let roots: Vec<PathBuf> = root_paths.into_iter().map(PathBuf::from).collect();
+    let exclusions = Arc::new(ExclusionMatcher::new(load_config().index_exclusions, &roots));
 
-        if !root_path.exists() {
-            continue;
-        }
+    let (search_id, cancel) = state.register();
+    let id = search_id.clone();
 
-        for entry in WalkDir::new(&root_path)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if results.len() >= limit {
-                break;
-            }
+    tokio::task::spawn_blocking(move || {
+        files::search_files(id.clone(), query, exclusions, limit, cancel, app.clone());
+        app.state::<SearchState>().finish(&id);
+    });
 
-            let file_name = entry.file_name().to_string_lossy().to_string();
-
-            // Case-insensitive substring match on filename
-            if file_name.to_lowercase().contains(&query_lower) {
-                let metadata = match entry.metadata() {
-                    Ok(m) => m,
-                    Err(_) => continue,
-                };
+    Ok(search_id)
+}
 
-                let extension = if metadata.is_file() {
-                    PathBuf::from(&file_name)
-                        .extension()
-                        .map(|e| e.to_string_lossy().to_string())
-                } else {
-                    None
-                };
+#[tauri::command]
+pub async fn search_content(
+    query: String,
+    root_paths: Vec<String>,
+    options: Option<ContentSearchOptions>,
+    state: State<'_, SearchState>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    if query.is_empty() {
+        return Err(AppError::InvalidOperation("Empty search query".into()));
+    }
 
-                let modified = metadata
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs() as i64);
+    let options = options.unwrap_or_default();
+    let matcher = content::build_matcher(&query, &options)
+        .map_err(|e| AppError::InvalidOperation(format!("Invalid pattern: {}", e)))?;
+    let roots: Vec<PathBuf> = root_paths.into_iter().map(PathBuf::from).collect();
 
-                let is_hidden = file_name.starts_with('.');
+    let (search_id, cancel) = state.register();
+    let id = search_id.clone();
 
-                results.push(FileEntry {
-                    name: file_name,
-                    path: entry.path().display().to_string(),
-                    extension,
-                    size: if metadata.is_file() { Some(metadata.len()) } else { None },
-                    modified,
-                    is_dir: metadata.is_dir(),
-                    is_hidden,
-                    is_symlink: metadata.file_type().is_symlink(),
-                });
-            }
-        }
+    tokio::task::spawn_blocking(move || {
+        content::search_content(id.clone(), matcher, roots, options, cancel, app.clone());
+        app.state::<SearchState>().finish(&id);
+    });
 
-        if results.len() >= limit {
-            break;
-        }
-    }
+    Ok(search_id)
+}
 
-    Ok(results)
+#[tauri::command]
+pub async fn cancel_search(search_id: String, state: State<'_, SearchState>) -> Result<(), AppError> {
+    state.cancel(&search_id);
+    Ok(())
 }
 
+/// Mounted volumes users can browse, with their space and flags.
 #[tauri::command]
 pub async fn get_available_drives() -> Vec<DriveInfo> {
-    let mut drives = Vec::new();
-
-    #[cfg(target_os = "windows")]
-    {
-        // Check A-Z drives on Windows
-        for letter in b'A'..=b'Z' {
-            let drive_letter = letter as char;
-            let path = format!("{}:\\", drive_letter);
-            let path_buf = PathBuf::from(&path);
-
-            if path_buf.exists() {
-                drives.push(DriveInfo {
-                    name: format!("{}: Drive", drive_letter),
-                    path,
-                });
-            }
-        }
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    {
-        // Common Linux/Unix paths
-        let paths = ["/", "/home"];
-        for p in paths {
-            let path_buf = PathBuf::from(p);
-            if path_buf.exists() {
-                drives.push(DriveInfo {
-                    name: p.to_string(),
-                    path: p.to_string(),
-                });
-            }
-        }
-    }
-
-    drives
+    // Querying a network mount that stopped responding can block
+    tokio::task::spawn_blocking(volumes::list_volumes)
+        .await
+        .unwrap_or_default()
 }
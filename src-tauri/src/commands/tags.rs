@@ -0,0 +1,91 @@
+use crate::commands::config::load_config;
+use crate::fs::{AppError, FileEntry};
+use crate::tags::{self, TagCount, TagStore};
+use std::path::PathBuf;
+use tauri::State;
+
+#[tauri::command]
+pub async fn add_tags(
+    paths: Vec<String>,
+    tags: Vec<String>,
+    store: State<'_, TagStore>,
+) -> Result<(), AppError> {
+    let tags = normalize_tags(tags)?;
+    let mirror = load_config().mirror_tags_to_xattrs;
+
+    // Tags added before a failure are kept, so the store is saved either way
+    let result = paths.iter().try_for_each(|path| {
+        let path = PathBuf::from(path);
+        let current = store.add(&path, &tags).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(path.display().to_string()),
+            _ => AppError::Io(e.to_string()),
+        })?;
+
+        if mirror {
+            tags::mirror_to_xattr(&path, &current).map_err(|e| AppError::Io(e.to_string()))?;
+        }
+        Ok(())
+    });
+
+    let saved = store.save().map_err(|e| AppError::Io(e.to_string()));
+    result.and(saved)
+}
+
+#[tauri::command]
+pub async fn remove_tags(
+    paths: Vec<String>,
+    tags: Vec<String>,
+    store: State<'_, TagStore>,
+) -> Result<(), AppError> {
+    let tags = normalize_tags(tags)?;
+    let mirror = load_config().mirror_tags_to_xattrs;
+
+    let result = paths.iter().try_for_each(|path| {
+        let path = PathBuf::from(path);
+        let remaining = store.remove(&path, &tags);
+
+        if mirror && path.exists() {
+            tags::mirror_to_xattr(&path, &remaining).map_err(|e| AppError::Io(e.to_string()))?;
+        }
+        Ok(())
+    });
+
+    let saved = store.save().map_err(|e| AppError::Io(e.to_string()));
+    result.and(saved)
+}
+
+/// Every tagged file carrying `tag` that still exists.
+#[tauri::command]
+pub async fn list_files_by_tag(
+    tag: String,
+    store: State<'_, TagStore>,
+) -> Result<Vec<FileEntry>, AppError> {
+    let mut entries: Vec<FileEntry> = store
+        .paths_with_tags(&[tag.trim().to_lowercase()])
+        .into_iter()
+        .filter_map(|path| {
+            let path = PathBuf::from(path);
+            let metadata = std::fs::symlink_metadata(&path).ok()?;
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some(FileEntry::from_metadata(name, &path, &metadata))
+        })
+        .collect();
+
+    store.annotate(&mut entries);
+    Ok(entries)
+}
+
+/// All tags in use with the number of files carrying each.
+#[tauri::command]
+pub async fn get_all_tags(store: State<'_, TagStore>) -> Result<Vec<TagCount>, AppError> {
+    Ok(store.all_tags())
+}
+
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, AppError> {
+    tags.iter()
+        .map(|tag| {
+            tags::normalize_tag(tag)
+                .ok_or_else(|| AppError::InvalidOperation(format!("Invalid tag '{}'", tag)))
+        })
+        .collect()
+}
@@ -1,57 +1,84 @@
-use crate::fs::{AppError, WslDistro};
-use std::process::Command;
+use crate::fs::{AppError, ConflictPolicy, WslDistro};
+use crate::jobs::JobRegistry;
+use crate::vfs::{self, VfsRegistry};
+use crate::wsl::{self, CopyItem};
+use tauri::{AppHandle, Manager};
 
 #[tauri::command]
 pub fn get_wsl_distros() -> Vec<WslDistro> {
-    let mut distros = Vec::new();
-
-    // Try to get WSL distributions using wsl.exe
-    if let Ok(output) = Command::new("wsl.exe")
-        .args(["--list", "--quiet"])
-        .output()
-    {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-
-            for line in stdout.lines() {
-                let name = line.trim().replace('\0', "").replace('\u{feff}', "");
-                if !name.is_empty() {
-                    let path = format!("\\\\wsl$\\{}", name);
-                    distros.push(WslDistro {
-                        name: name.clone(),
-                        path,
-                        is_default: distros.is_empty(), // First one is default
-                    });
-                }
-            }
-        }
+    wsl::list_distros()
+}
+
+/// Converts between Windows and Linux paths like `wslpath`: an absolute Linux path is
+/// mapped to where Windows finds it in `distro`, or the default distro, and any other
+/// path to where a distro finds it.
+#[tauri::command]
+pub fn convert_wsl_path(path: String, distro: Option<String>) -> Result<String, AppError> {
+    if path.starts_with('/') {
+        let distro = match distro {
+            Some(distro) => distro,
+            None => wsl::list_distros()
+                .into_iter()
+                .find(|d| d.is_default)
+                .map(|d| d.name)
+                .ok_or_else(|| AppError::InvalidOperation("No WSL distro installed".into()))?,
+        };
+        return wsl::to_windows(&distro, &path)
+            .ok_or_else(|| AppError::InvalidOperation(format!("Not a WSL path: {}", path)));
     }
 
-    distros
+    wsl::to_linux(&path)
+        .map(|linux| linux.path)
+        .ok_or_else(|| AppError::InvalidOperation(format!("Not reachable from WSL: {}", path)))
 }
 
+/// Copies `sources` into `dest` and returns the id of the job doing it. When the copy
+/// involves a distro's files, `cp` runs inside that distro instead of copying file by
+/// file over the `\\wsl$` share.
 #[tauri::command]
 pub async fn wsl_copy(
-    source: String,
+    sources: Vec<String>,
     dest: String,
-    use_wsl_native: bool,
-) -> Result<(), AppError> {
-    if use_wsl_native {
-        // Use wsl.exe cp for bulk/large operations
-        let output = Command::new("wsl.exe")
-            .args(["cp", "-r", &source, &dest])
-            .output()
-            .map_err(|e| AppError::Io(e.to_string()))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(AppError::Io(stderr.to_string()));
-        }
-    } else {
-        // Use standard Windows copy via UNC path
-        std::fs::copy(&source, &dest)
-            .map_err(|e| AppError::Io(e.to_string()))?;
-    }
+    conflict: Option<ConflictPolicy>,
+    app: AppHandle,
+) -> Result<String, AppError> {
+    let vfs = app.state::<VfsRegistry>();
+    let conflict = conflict.unwrap_or_default();
+    let distro = wsl::copy_distro(&dest, sources.iter().map(String::as_str));
+    let dest_fs = vfs.resolve(&dest);
+    let sources: Vec<_> = sources
+        .into_iter()
+        .map(|source| (vfs.resolve(&source), source))
+        .collect();
 
-    Ok(())
+    Ok(app
+        .state::<JobRegistry>()
+        .spawn(&app, "wsl_copy", move |job| {
+            vfs::ensure_dir(&*dest_fs, &dest)?;
+
+            // Targets are resolved on the Windows side, so conflicts are handled the same
+            // either way
+            let mut items = Vec::new();
+            for (source_fs, source) in sources {
+                job.check_cancelled()?;
+                let name = source_fs.stat(&source)?.name;
+                let target = match vfs::resolve_target(&*dest_fs, &dest, &name, conflict)? {
+                    Some(target) => target,
+                    None => continue,
+                };
+
+                match (&distro, wsl::to_linux(&source), wsl::to_linux(&target)) {
+                    (Some(_), Some(source), Some(target)) => items.push(CopyItem {
+                        source: source.path,
+                        target: target.path,
+                    }),
+                    _ => vfs::copy(&*source_fs, &source, &*dest_fs, &target, Some(job))?,
+                }
+            }
+
+            match &distro {
+                Some(distro) => wsl::copy(distro, &items, job),
+                None => Ok(()),
+            }
+        }))
 }
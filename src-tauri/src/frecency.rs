@@ -0,0 +1,261 @@
+use crate::fs::write_json;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+const STORE_FILE_NAME: &str = "frecency.json";
+const SAVE_INTERVAL_SECS: u64 = 30;
+/// Once all ranks add up to this much, every rank decays so old favourites fade out.
+const MAX_TOTAL_RANK: f64 = 10_000.0;
+const DECAY_FACTOR: f64 = 0.9;
+const MIN_RANK: f64 = 1.0;
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VisitKind {
+    File,
+    Directory,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FrecencyRecord {
+    kind: VisitKind,
+    rank: f64,
+    last_access: u64,
+}
+
+impl FrecencyRecord {
+    /// Rank weighted by how recently the path was used, as zoxide does.
+    fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_access);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.rank * weight
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrecentPath {
+    pub path: String,
+    pub kind: VisitKind,
+    pub score: f64,
+    pub last_access: u64,
+}
+
+/// Tracks how often and how recently files and directories were used.
+pub struct FrecencyStore {
+    records: RwLock<HashMap<String, FrecencyRecord>>,
+    dirty: AtomicBool,
+}
+
+impl FrecencyStore {
+    /// Loads the store from disk, starting empty if there is none or it cannot be read.
+    pub fn load() -> Self {
+        let records = store_path()
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+
+        Self {
+            records: RwLock::new(records),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn record(&self, path: &Path, kind: VisitKind) {
+        let now = unix_now();
+        {
+            let mut records = self.records.write();
+            let record = records
+                .entry(path.display().to_string())
+                .or_insert(FrecencyRecord {
+                    kind,
+                    rank: 0.0,
+                    last_access: now,
+                });
+            record.kind = kind;
+            record.rank += 1.0;
+            record.last_access = now;
+
+            let total: f64 = records.values().map(|r| r.rank).sum();
+            if total > MAX_TOTAL_RANK {
+                for record in records.values_mut() {
+                    record.rank *= DECAY_FACTOR;
+                }
+                records.retain(|_, r| r.rank >= MIN_RANK);
+            }
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn forget(&self, path: &str) -> bool {
+        let removed = self.records.write().remove(path).is_some();
+        if removed {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        removed
+    }
+
+    pub fn score(&self, path: &str) -> f64 {
+        self.records
+            .read()
+            .get(path)
+            .map_or(0.0, |r| r.score(unix_now()))
+    }
+
+    /// Highest scoring paths that still exist, optionally limited to one kind.
+    pub fn top(&self, kind: Option<VisitKind>, limit: usize) -> Vec<FrecentPath> {
+        self.ranked(limit, |_, record| kind.is_none_or(|k| record.kind == k))
+    }
+
+    /// zoxide-style lookup: every keyword must appear in the path in order, and the last
+    /// one in its final component.
+    pub fn jump(&self, query: &str, limit: usize) -> Vec<FrecentPath> {
+        let keywords: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if keywords.is_empty() {
+            return Vec::new();
+        }
+
+        self.ranked(limit, |path, record| {
+            record.kind == VisitKind::Directory && matches_keywords(&path.to_lowercase(), &keywords)
+        })
+    }
+
+    /// Frecent paths whose name contains `query`, matching on the full path if the query
+    /// contains a separator, like `IndexManager::search`.
+    pub fn matching(&self, query: &str, limit: usize) -> Vec<FrecentPath> {
+        let query = query.to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let full_path = query.contains('/') || query.contains('\\');
+
+        self.ranked(limit, |path, _| {
+            let path = path.to_lowercase();
+            if full_path {
+                path.contains(&query)
+            } else {
+                last_component(&path).contains(&query)
+            }
+        })
+    }
+
+    fn ranked(
+        &self,
+        limit: usize,
+        filter: impl Fn(&str, &FrecencyRecord) -> bool,
+    ) -> Vec<FrecentPath> {
+        let now = unix_now();
+        let mut candidates: Vec<FrecentPath> = self
+            .records
+            .read()
+            .iter()
+            .filter(|(path, record)| filter(path, record))
+            .map(|(path, record)| FrecentPath {
+                path: path.clone(),
+                kind: record.kind,
+                score: record.score(now),
+                last_access: record.last_access,
+            })
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        // Checking existence last keeps it to the few paths that are actually returned
+        candidates
+            .into_iter()
+            .filter(|c| Path::new(&c.path).exists())
+            .take(limit)
+            .collect()
+    }
+
+    /// Writes the store if anything changed since the last save. If writing fails, the
+    /// changes stay pending for the next save.
+    pub fn save(&self) -> io::Result<()> {
+        // Cleared before taking the records, so visits recorded meanwhile are saved next
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let result = self.write();
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let path = store_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let records = self.records.read().clone();
+        write_json(&path, &records)
+    }
+}
+
+/// Saves the store in the background every `SAVE_INTERVAL_SECS` while it has changes, so
+/// that recording a visit never waits for the disk. It is also saved on exit.
+pub fn spawn_flush(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SAVE_INTERVAL_SECS));
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            let app = app.clone();
+            let _ = tokio::task::spawn_blocking(move || app.state::<FrecencyStore>().save()).await;
+        }
+    });
+}
+
+fn matches_keywords(path: &str, keywords: &[String]) -> bool {
+    let mut rest = path;
+    for keyword in keywords {
+        match rest.find(keyword.as_str()) {
+            Some(index) => rest = &rest[index + keyword.len()..],
+            None => return false,
+        }
+    }
+
+    keywords
+        .last()
+        .is_some_and(|last| last_component(path).contains(last.as_str()))
+}
+
+fn last_component(path: &str) -> &str {
+    path.trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(path)
+}
+
+fn store_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("filemanager").join(STORE_FILE_NAME))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
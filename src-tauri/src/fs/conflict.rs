@@ -0,0 +1,78 @@
+use super::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// What copies, moves and extractions do when a target already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Replace files and merge into folders.
+    #[default]
+    Overwrite,
+    Skip,
+    /// Write next to the existing item as `name (2).ext`.
+    Rename,
+    Fail,
+}
+
+impl ConflictPolicy {
+    /// Where to write `target`, or `None` if it should be skipped.
+    pub fn resolve(self, target: &Path) -> Result<Option<PathBuf>, AppError> {
+        let name = match target.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Ok(Some(target.to_path_buf())),
+        };
+
+        // Broken links count as existing too
+        let resolved = self.resolve_name(&name, |candidate| {
+            target
+                .with_file_name(candidate)
+                .symlink_metadata()
+                .ok()
+                .map(|m| m.is_dir())
+        })?;
+        Ok(resolved.map(|name| target.with_file_name(name)))
+    }
+
+    /// The name to write an item called `name` under, or `None` if it should be skipped.
+    /// `lookup` tells whether a name is taken in the target folder, and by a folder.
+    pub fn resolve_name(
+        self,
+        name: &str,
+        lookup: impl Fn(&str) -> Option<bool>,
+    ) -> Result<Option<String>, AppError> {
+        let is_dir = match lookup(name) {
+            Some(is_dir) => is_dir,
+            None => return Ok(Some(name.to_string())),
+        };
+
+        match self {
+            ConflictPolicy::Overwrite => Ok(Some(name.to_string())),
+            ConflictPolicy::Skip => Ok(None),
+            ConflictPolicy::Rename => Ok(Some(unique_name(name, is_dir, lookup))),
+            ConflictPolicy::Fail => Err(AppError::InvalidOperation(format!(
+                "File already exists: {}",
+                name
+            ))),
+        }
+    }
+}
+
+/// First free `name (n).ext`, keeping `.tar.gz` style suffixes together. Folder names are
+/// numbered at the end.
+fn unique_name(name: &str, is_dir: bool, lookup: impl Fn(&str) -> Option<bool>) -> String {
+    let split = match name.to_ascii_lowercase().find(".tar.") {
+        _ if is_dir => name.len(),
+        Some(index) if index > 0 => index,
+        _ => name
+            .rfind('.')
+            .filter(|&index| index > 0)
+            .unwrap_or(name.len()),
+    };
+    let (stem, suffix) = name.split_at(split);
+
+    (2..)
+        .map(|n| format!("{} ({}){}", stem, n, suffix))
+        .find(|candidate| lookup(candidate).is_none())
+        .expect("unbounded range")
+}
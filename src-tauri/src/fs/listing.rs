@@ -0,0 +1,105 @@
+use super::{AppError, FileEntry};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+impl FileEntry {
+    /// Builds an entry from what a provider reports about a path, for providers without
+    /// local metadata. Folders have no extension or size.
+    pub fn new(
+        name: String,
+        path: String,
+        is_dir: bool,
+        size: Option<u64>,
+        modified: Option<i64>,
+    ) -> Self {
+        let extension = if is_dir {
+            None
+        } else {
+            Path::new(&name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+        };
+
+        FileEntry {
+            is_hidden: name.starts_with('.'),
+            name,
+            path,
+            extension,
+            size: if is_dir { None } else { size },
+            modified,
+            is_dir,
+            is_symlink: false,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Builds an entry from metadata that was read without following symlinks.
+    pub fn from_metadata(file_name: String, path: &Path, metadata: &Metadata) -> Self {
+        let extension = if metadata.is_file() {
+            PathBuf::from(&file_name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+        } else {
+            None
+        };
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+
+        let is_hidden = file_name.starts_with('.');
+
+        FileEntry {
+            name: file_name,
+            path: path.display().to_string(),
+            extension,
+            size: if metadata.is_file() { Some(metadata.len()) } else { None },
+            modified,
+            is_dir: metadata.is_dir(),
+            is_hidden,
+            is_symlink: metadata.is_symlink(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Lists a directory with directories first, then by case-insensitive name.
+pub fn list_directory(path: &Path) -> Result<Vec<FileEntry>, AppError> {
+    if !path.exists() {
+        return Err(AppError::NotFound(path.display().to_string()));
+    }
+
+    if !path.is_dir() {
+        return Err(AppError::NotADirectory(path.display().to_string()));
+    }
+
+    let mut entries = Vec::new();
+
+    let read_dir = std::fs::read_dir(path)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    for entry in read_dir {
+        let entry = entry.map_err(|e| AppError::Io(e.to_string()))?;
+        let metadata = entry.metadata().map_err(|e| AppError::Io(e.to_string()))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+
+        entries.push(FileEntry::from_metadata(file_name, &entry.path(), &metadata));
+    }
+
+    sort_entries(&mut entries);
+
+    Ok(entries)
+}
+
+/// Sorts directories first, then by case-insensitive name.
+pub fn sort_entries(entries: &mut [FileEntry]) {
+    entries.sort_by(|a, b| {
+        match (a.is_dir, b.is_dir) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        }
+    });
+}
@@ -1,7 +1,18 @@
+mod conflict;
+mod listing;
+pub mod mounts;
 #[allow(dead_code)]
 mod operations;
+mod store;
+pub mod volumes;
 
+pub use conflict::ConflictPolicy;
+pub use listing::{list_directory, sort_entries};
+pub use store::write_json;
+
+use crate::wsl::DistroState;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -14,13 +25,20 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub is_hidden: bool,
     pub is_symlink: bool,
+    /// Filled in from the tag store by the commands that return entries.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WslDistro {
     pub name: String,
     pub path: String,
+    /// The distro `wsl.exe` uses when none is named.
     pub is_default: bool,
+    pub state: DistroState,
+    /// WSL 1 or 2.
+    pub version: u8,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -46,6 +64,158 @@ pub struct WindowState {
     pub maximized: bool,
 }
 
+/// Rules that keep paths out of the index, the watcher and `search_files`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IndexExclusions {
+    pub patterns: Vec<String>,
+    pub dir_names: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub include_hidden: bool,
+    pub respect_ignore_files: bool,
+    pub one_file_system: bool,
+    pub skip_virtual_filesystems: bool,
+}
+
+impl Default for IndexExclusions {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            dir_names: ["node_modules", ".git", ".hg", ".svn", "target", "__pycache__"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            max_depth: None,
+            include_hidden: true,
+            respect_ignore_files: true,
+            one_file_system: false,
+            skip_virtual_filesystems: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WatcherSettings {
+    /// Interval for polling roots where native notifications are unreliable.
+    pub poll_interval_secs: u64,
+    /// Poll every root, e.g. when native watching misbehaves on a platform.
+    pub force_polling: bool,
+}
+
+impl Default for WatcherSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 10,
+            force_polling: false,
+        }
+    }
+}
+
+/// When the backend verifies a scope on its own.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct IndexSchedule {
+    /// Standard five-field cron expression in local time, e.g. `0 3 * * *`.
+    pub cron: Option<String>,
+    /// Verify once the user has been idle this long (Windows and macOS only).
+    pub idle_minutes: Option<u64>,
+    /// Hold scheduled scans while running on battery.
+    pub pause_on_battery: bool,
+}
+
+impl Default for IndexSchedule {
+    fn default() -> Self {
+        Self {
+            cron: None,
+            idle_minutes: None,
+            pause_on_battery: true,
+        }
+    }
+}
+
+/// A named index scope that `start_indexing` can start without passing roots.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexScopeConfig {
+    pub name: String,
+    pub roots: Vec<String>,
+    /// Overrides `Config.index_exclusions` for this scope.
+    #[serde(default)]
+    pub exclusions: Option<IndexExclusions>,
+    #[serde(default)]
+    pub index_content: bool,
+    #[serde(default)]
+    pub schedule: Option<IndexSchedule>,
+}
+
+/// A search that panes open like a folder under `search://{name}`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    pub name: String,
+    /// Name text and `tag:` filters, as accepted by `search_index`.
+    #[serde(default)]
+    pub query: String,
+    /// Folders to search below; empty means everything that is indexed.
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// Extensions without the dot, e.g. `pdf`; empty matches all files.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub modified_within_days: Option<u64>,
+    #[serde(default)]
+    pub include_dirs: bool,
+}
+
+/// How `sftp://` folders connect and how long idle connections are kept.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SftpSettings {
+    /// Private keys tried after the ssh-agent, before `~/.ssh/id_ed25519`, `id_ecdsa`
+    /// and `id_rsa`. Keys with a passphrase only work through the agent.
+    pub identity_files: Vec<String>,
+    /// Applies to connecting as well as to each request on an open connection.
+    pub timeout_secs: u64,
+    pub keepalive_secs: u64,
+    /// Connections unused this long are closed.
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteKind {
+    Webdav,
+    S3,
+}
+
+/// A WebDAV or S3 server that panes open as `webdav://{name}/path` or
+/// `s3://{name}/{bucket}/key`. Its password or secret key is kept in the OS keyring.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteConfig {
+    pub name: String,
+    pub kind: RemoteKind,
+    /// The WebDAV folder, e.g. `https://cloud.example.com/remote.php/dav/files/me`, or
+    /// the S3 endpoint, e.g. `http://localhost:9000`.
+    pub url: String,
+    /// WebDAV user name or S3 access key id; empty for anonymous access.
+    #[serde(default)]
+    pub username: String,
+    /// S3 signing region, `us-east-1` if unset.
+    #[serde(default)]
+    pub region: Option<String>,
+}
+
+impl Default for SftpSettings {
+    fn default() -> Self {
+        Self {
+            identity_files: Vec::new(),
+            timeout_secs: 30,
+            keepalive_secs: 30,
+            idle_timeout_secs: 300,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub bookmarks: Vec<Bookmark>,
@@ -56,6 +226,21 @@ pub struct Config {
     pub recent_paths: Vec<String>,
     #[serde(default = "default_theme")]
     pub theme: String,
+    #[serde(default)]
+    pub index_exclusions: IndexExclusions,
+    #[serde(default)]
+    pub watcher: WatcherSettings,
+    #[serde(default)]
+    pub index_scopes: Vec<IndexScopeConfig>,
+    /// Also write tags to the `user.xdg.tags` extended attribute (Linux only).
+    #[serde(default)]
+    pub mirror_tags_to_xattrs: bool,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    #[serde(default)]
+    pub sftp: SftpSettings,
+    #[serde(default)]
+    pub remotes: Vec<RemoteConfig>,
 }
 
 fn default_theme() -> String {
@@ -96,6 +281,13 @@ impl Default for Config {
             show_hidden: false,
             recent_paths: Vec::new(),
             theme: "dark".to_string(),
+            index_exclusions: IndexExclusions::default(),
+            watcher: WatcherSettings::default(),
+            index_scopes: Vec::new(),
+            mirror_tags_to_xattrs: false,
+            saved_searches: Vec::new(),
+            sftp: SftpSettings::default(),
+            remotes: Vec::new(),
         }
     }
 }
@@ -128,3 +320,13 @@ pub enum AppError {
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
 }
+
+/// Resolves a leading `~` to the home folder.
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~") {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => dirs::home_dir()
+            .map(|home| home.join(rest.trim_start_matches(['/', '\\'])))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        _ => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+/// Filesystem types where native change notifications are missing or unreliable.
+const POLL_FS_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "drvfs", "davfs", "afpfs", "webdav", "ceph",
+    "glusterfs", "afs", "sshfs", "osxfuse", "macfuse",
+];
+
+#[derive(Debug, Clone)]
+pub struct MountInfo {
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    /// What is mounted, e.g. `/dev/sda1` or `server:/export`.
+    pub source: String,
+    /// `major:minor` of the mounted device.
+    pub device: String,
+    /// The directory of the filesystem mounted here, `/` unless this is a bind mount or
+    /// a subvolume.
+    pub root: String,
+    pub read_only: bool,
+}
+
+/// Parses `/proc/self/mountinfo` content.
+pub fn parse_mountinfo(content: &str) -> Vec<MountInfo> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (left, right) = line.split_once(" - ")?;
+            let left: Vec<&str> = left.split(' ').collect();
+            let mut right = right.split(' ');
+            let fs_type = right.next()?;
+            let source = right.next().unwrap_or_default();
+            let super_options = right.next().unwrap_or_default();
+
+            // Either the mount or the filesystem itself can be read-only
+            let read_only = [*left.get(5)?, super_options]
+                .iter()
+                .any(|options| options.split(',').any(|option| option == "ro"));
+
+            Some(MountInfo {
+                mount_point: PathBuf::from(unescape_mount_field(left.get(4)?)),
+                fs_type: fs_type.to_string(),
+                source: unescape_mount_field(source),
+                device: left.get(2)?.to_string(),
+                root: unescape_mount_field(left.get(3)?),
+                read_only,
+            })
+        })
+        .collect()
+}
+
+/// Decodes the octal escapes (`\040` for space) the kernel uses in mount tables.
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|d| bytes[i] == b'\\' && d.iter().all(|b| (b'0'..=b'7').contains(b)));
+        if let Some(digits) = octal {
+            let code = digits
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+            out.push(code as u8);
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Returns the mount containing `path`, i.e. the one with the longest matching mount point.
+pub fn mount_for_path<'a>(mounts: &'a [MountInfo], path: &Path) -> Option<&'a MountInfo> {
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+}
+
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let mounts = parse_mountinfo(&content);
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    mount_for_path(&mounts, &path).map(|m| m.fs_type.clone())
+}
+
+#[cfg(target_os = "macos")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(target_os = "windows")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    use windows_sys::Win32::Storage::FileSystem::GetVolumeInformationW;
+
+    let root = wide_volume_root(path)?;
+    let mut fs_name = [0u16; 64];
+    let ok = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            fs_name.as_mut_ptr(),
+            fs_name.len() as u32,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+
+    let len = fs_name.iter().position(|&c| c == 0).unwrap_or(fs_name.len());
+    Some(String::from_utf16_lossy(&fs_name[..len]))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// `C:\` style root of a path as a NUL-terminated wide string.
+#[cfg(target_os = "windows")]
+fn wide_volume_root(path: &Path) -> Option<Vec<u16>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut root: PathBuf = path.components().take(2).collect();
+    if root.as_os_str().is_empty() {
+        return None;
+    }
+    root.push("");
+    Some(root.as_os_str().encode_wide().chain(Some(0)).collect())
+}
+
+/// `\\server\share`, `\\wsl$\distro` and `//server/share` paths.
+pub fn is_unc_path(path: &Path) -> bool {
+    let s = path.to_string_lossy();
+    if let Some(rest) = s.strip_prefix(r"\\?\") {
+        return rest.starts_with(r"UNC\");
+    }
+    s.starts_with(r"\\") || s.starts_with("//")
+}
+
+#[cfg(target_os = "windows")]
+fn is_remote_drive(path: &Path) -> bool {
+    use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+    use windows_sys::Win32::System::WindowsProgramming::DRIVE_REMOTE;
+
+    match wide_volume_root(path) {
+        Some(root) => unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE },
+        None => false,
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_remote_drive(_path: &Path) -> bool {
+    false
+}
+
+/// Network shares, WSL paths and FUSE mounts, where native watching misses events.
+pub fn native_watch_unreliable(path: &Path) -> bool {
+    if is_unc_path(path) || is_remote_drive(path) {
+        return true;
+    }
+
+    filesystem_type(path).is_some_and(|fs_type| {
+        let fs_type = fs_type.to_lowercase();
+        fs_type.starts_with("fuse") || POLL_FS_TYPES.contains(&fs_type.as_str())
+    })
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Writes `data` as JSON to a temporary file next to `path` and then renames it over
+/// `path`, so that a crash or kill while writing leaves the previous file intact.
+pub fn write_json<T: Serialize>(path: &Path, data: &T) -> io::Result<()> {
+    let partial = path.with_extension("tmp");
+    let result = File::create(&partial)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer(&mut writer, data).map_err(io::Error::other)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()
+        })
+        .and_then(|()| fs::rename(&partial, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_file_without_leaving_the_temporary_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        fs::write(&path, "old").unwrap();
+
+        write_json(&path, &vec![1, 2, 3]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1,2,3]");
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
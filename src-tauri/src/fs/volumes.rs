@@ -0,0 +1,586 @@
+//! Mounted volumes: what `get_available_drives` lists, and `volumes:changed` events as
+//! drives come and go.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How often the volume list is compared where the OS cannot say when it changes.
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Filesystems that hold no user files, such as kernel interfaces.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "selinuxfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+    "fuse.gvfsd-fuse",
+    "fuse.lxcfs",
+    "fuse.portal",
+];
+
+/// Where the system mounts things users do not browse. Removable media below `/run/media`
+/// are the exception.
+#[cfg(target_os = "linux")]
+const SYSTEM_MOUNT_DIRS: &[&str] = &[
+    "/proc", "/sys", "/dev", "/run", "/snap", "/var/lib", "/boot",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriveInfo {
+    /// The label, or else a name derived from the mount point.
+    pub name: String,
+    /// The mount point.
+    pub path: String,
+    pub label: Option<String>,
+    pub fs_type: String,
+    #[serde(flatten)]
+    pub space: DiskSpace,
+    pub read_only: bool,
+    pub removable: bool,
+}
+
+/// Space on the volume holding a path, in bytes. Zero where it cannot be determined.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DiskSpace {
+    pub total_bytes: u64,
+    /// Available to the current user, which can be less than what is unused.
+    pub free_bytes: u64,
+    pub used_bytes: u64,
+}
+
+/// Payload of `volumes:changed`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeChange {
+    pub added: Vec<DriveInfo>,
+    /// Mount points of the volumes that went away.
+    pub removed: Vec<String>,
+}
+
+/// A mounted volume as the mount table lists it, before querying the volume itself.
+#[derive(Debug, Clone)]
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+struct Mount {
+    path: PathBuf,
+    source: String,
+    fs_type: String,
+    read_only: bool,
+}
+
+/// The volumes users can browse, in mount-table order.
+pub fn list_volumes() -> Vec<DriveInfo> {
+    mounts().iter().filter_map(describe).collect()
+}
+
+/// Emits `volumes:changed` whenever volumes are mounted or unmounted, for as long as the
+/// app runs.
+pub fn watch(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut changes = MountChanges::new();
+        let mut known: HashSet<PathBuf> = mounts().into_iter().map(|m| m.path).collect();
+
+        loop {
+            changes.wait();
+            let current = mounts();
+            let paths: HashSet<PathBuf> = current.iter().map(|m| m.path.clone()).collect();
+            if paths == known {
+                continue;
+            }
+
+            let change = VolumeChange {
+                added: current
+                    .iter()
+                    .filter(|m| !known.contains(&m.path))
+                    .filter_map(describe)
+                    .collect(),
+                removed: known
+                    .difference(&paths)
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            };
+            known = paths;
+            let _ = app.emit("volumes:changed", change);
+        }
+    });
+}
+
+/// What is known about a mounted volume only by asking the volume itself.
+struct VolumeDetails {
+    label: Option<String>,
+    fs_type: String,
+    read_only: bool,
+}
+
+fn describe(mount: &Mount) -> Option<DriveInfo> {
+    let VolumeDetails {
+        label,
+        fs_type,
+        read_only,
+    } = volume_details(mount)?;
+    let name = label
+        .clone()
+        .or_else(|| {
+            mount
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| mount.path.display().to_string());
+
+    Some(DriveInfo {
+        name,
+        path: mount.path.display().to_string(),
+        fs_type,
+        space: disk_space(&mount.path).unwrap_or_default(),
+        read_only,
+        removable: is_removable(mount),
+        label,
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn is_pseudo_fs(fs_type: &str) -> bool {
+    PSEUDO_FS_TYPES.contains(&fs_type)
+}
+
+#[cfg(unix)]
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+
+    let block = stat.f_frsize as u64;
+    let blocks = stat.f_blocks as u64;
+    Some(DiskSpace {
+        total_bytes: blocks * block,
+        free_bytes: stat.f_bavail as u64 * block,
+        used_bytes: blocks.saturating_sub(stat.f_bfree as u64) * block,
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn disk_space(path: &Path) -> Option<DiskSpace> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let (mut available, mut total, mut free) = (0u64, 0u64, 0u64);
+    if unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut free) } == 0 {
+        return None;
+    }
+
+    Some(DiskSpace {
+        total_bytes: total,
+        free_bytes: available,
+        used_bytes: total.saturating_sub(free),
+    })
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn disk_space(_path: &Path) -> Option<DiskSpace> {
+    None
+}
+
+/// Names the physical disk holding `path`, so that operations on one disk can be kept
+/// from competing for it. Partitions of a disk share its key where the OS tells which
+/// disk they are on; paths that do not exist yet count towards their nearest ancestor.
+#[cfg(target_os = "linux")]
+pub fn device_key(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = existing_ancestor(path)?.metadata().ok()?.dev();
+    let (major, minor) = (libc::major(dev), libc::minor(dev));
+    // Network and memory filesystems have no block device behind them
+    let sys_path = match Path::new("/sys/dev/block")
+        .join(format!("{}:{}", major, minor))
+        .canonicalize()
+    {
+        Ok(sys_path) => sys_path,
+        Err(_) => return Some(format!("dev:{}:{}", major, minor)),
+    };
+
+    let disk = match sys_path.join("partition").exists() {
+        true => sys_path.parent().unwrap_or(&sys_path),
+        false => &sys_path,
+    };
+    disk.file_name()
+        .map(|name| format!("disk:{}", name.to_string_lossy()))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn device_key(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = existing_ancestor(path)?.metadata().ok()?.dev();
+    Some(format!("dev:{}", dev))
+}
+
+/// Drive letters or shares; telling which drives share a disk needs admin rights.
+#[cfg(not(unix))]
+pub fn device_key(path: &Path) -> Option<String> {
+    path.components().next().map(|root| {
+        format!(
+            "volume:{}",
+            root.as_os_str().to_string_lossy().to_lowercase()
+        )
+    })
+}
+
+#[cfg(unix)]
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
+
+#[cfg(target_os = "linux")]
+fn mounts() -> Vec<Mount> {
+    match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(content) => linux_mounts(&content),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The browsable mounts in `/proc/self/mountinfo` content. A filesystem bind-mounted in
+/// several places is listed once.
+#[cfg(target_os = "linux")]
+fn linux_mounts(content: &str) -> Vec<Mount> {
+    use super::mounts::parse_mountinfo;
+
+    let mut seen = HashSet::new();
+    parse_mountinfo(content)
+        .into_iter()
+        .filter(|m| !is_pseudo_fs(&m.fs_type) && !is_system_mount(&m.mount_point))
+        .filter(|m| seen.insert((m.device.clone(), m.root.clone())))
+        .map(|m| Mount {
+            path: m.mount_point,
+            source: m.source,
+            fs_type: m.fs_type,
+            read_only: m.read_only,
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn is_system_mount(path: &Path) -> bool {
+    if path.starts_with("/run/media") {
+        return false;
+    }
+    SYSTEM_MOUNT_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+#[cfg(target_os = "linux")]
+fn volume_details(mount: &Mount) -> Option<VolumeDetails> {
+    Some(VolumeDetails {
+        label: device_label(mount),
+        fs_type: mount.fs_type.clone(),
+        read_only: mount.read_only,
+    })
+}
+
+/// The filesystem label udev found for the mounted device.
+#[cfg(target_os = "linux")]
+fn device_label(mount: &Mount) -> Option<String> {
+    let device = Path::new(&mount.source).canonicalize().ok()?;
+    std::fs::read_dir("/dev/disk/by-label")
+        .ok()?
+        .flatten()
+        .find(|entry| entry.path().canonicalize().ok().as_ref() == Some(&device))
+        .map(|entry| unescape_udev(&entry.file_name().to_string_lossy()))
+}
+
+/// Decodes the `\x20` escapes udev uses in `/dev/disk/by-label` names.
+#[cfg(target_os = "linux")]
+fn unescape_udev(name: &str) -> String {
+    let mut out = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .strip_prefix(b"x")
+            .filter(|_| byte == b'\\')
+            .and_then(|hex| hex.get(..2))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                out.push(decoded);
+                rest = &tail[3..];
+            }
+            None => {
+                out.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Whether the device is flagged removable, or sits on USB, as external disks often do
+/// without the flag.
+#[cfg(target_os = "linux")]
+fn is_removable(mount: &Mount) -> bool {
+    let device = match Path::new(&mount.source).canonicalize() {
+        Ok(device) if device.starts_with("/dev") => device,
+        _ => return false,
+    };
+    let name = match device.file_name() {
+        Some(name) => name.to_owned(),
+        None => return false,
+    };
+    let sys_path = match Path::new("/sys/class/block").join(name).canonicalize() {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    if sys_path
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with("usb"))
+    {
+        return true;
+    }
+
+    // Partitions carry the flag on the disk they belong to
+    let disk = match sys_path.join("partition").exists() {
+        true => sys_path.parent().unwrap_or(&sys_path),
+        false => &sys_path,
+    };
+    std::fs::read_to_string(disk.join("removable")).is_ok_and(|flag| flag.trim() == "1")
+}
+
+#[cfg(target_os = "macos")]
+fn mounts() -> Vec<Mount> {
+    use std::ffi::CStr;
+
+    let mut buffer: *mut libc::statfs = std::ptr::null_mut();
+    let count = unsafe { libc::getmntinfo(&mut buffer, libc::MNT_NOWAIT) };
+    if count <= 0 || buffer.is_null() {
+        return Vec::new();
+    }
+
+    // The buffer belongs to libc and is reused by the next call
+    let entries = unsafe { std::slice::from_raw_parts(buffer, count as usize) };
+    entries
+        .iter()
+        .filter(|stat| stat.f_flags & libc::MNT_DONTBROWSE as u32 == 0)
+        .filter_map(|stat| {
+            let text = |field: &[libc::c_char]| unsafe {
+                CStr::from_ptr(field.as_ptr())
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            let fs_type = text(&stat.f_fstypename);
+            if is_pseudo_fs(&fs_type) {
+                return None;
+            }
+            Some(Mount {
+                path: PathBuf::from(text(&stat.f_mntonname)),
+                source: text(&stat.f_mntfromname),
+                fs_type,
+                read_only: stat.f_flags & libc::MNT_RDONLY as u32 != 0,
+            })
+        })
+        .collect()
+}
+
+/// Volumes are mounted below `/Volumes` under their label already.
+#[cfg(target_os = "macos")]
+fn volume_details(mount: &Mount) -> Option<VolumeDetails> {
+    Some(VolumeDetails {
+        label: None,
+        fs_type: mount.fs_type.clone(),
+        read_only: mount.read_only,
+    })
+}
+
+/// Local volumes mounted besides the system volume, which covers external disks and
+/// disk images.
+#[cfg(target_os = "macos")]
+fn is_removable(mount: &Mount) -> bool {
+    mount.path.starts_with("/Volumes") && mount.source.starts_with("/dev/")
+}
+
+#[cfg(target_os = "windows")]
+fn mounts() -> Vec<Mount> {
+    use windows_sys::Win32::Storage::FileSystem::{GetDriveTypeW, GetLogicalDrives};
+    use windows_sys::Win32::System::WindowsProgramming::DRIVE_NO_ROOT_DIR;
+
+    let drives = unsafe { GetLogicalDrives() };
+    (0..26u8)
+        .filter(|index| drives & (1 << index) != 0)
+        .filter_map(|index| {
+            let path = format!("{}:\\", (b'A' + index) as char);
+            let wide: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+            if unsafe { GetDriveTypeW(wide.as_ptr()) } == DRIVE_NO_ROOT_DIR {
+                return None;
+            }
+            Some(Mount {
+                path: PathBuf::from(path),
+                source: String::new(),
+                fs_type: String::new(),
+                read_only: false,
+            })
+        })
+        .collect()
+}
+
+/// `None` for drives without a medium, such as empty card readers.
+#[cfg(target_os = "windows")]
+fn volume_details(mount: &Mount) -> Option<VolumeDetails> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetVolumeInformationW;
+
+    const FILE_READ_ONLY_VOLUME: u32 = 0x0008_0000;
+
+    let root: Vec<u16> = mount
+        .path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    let mut label = [0u16; 261];
+    let mut fs_name = [0u16; 64];
+    let mut flags = 0u32;
+    let ok = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            label.as_mut_ptr(),
+            label.len() as u32,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut flags,
+            fs_name.as_mut_ptr(),
+            fs_name.len() as u32,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+
+    let text = |buffer: &[u16]| {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    };
+    let label = text(&label);
+    Some(VolumeDetails {
+        label: (!label.is_empty()).then_some(label),
+        fs_type: text(&fs_name),
+        read_only: flags & FILE_READ_ONLY_VOLUME != 0,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn is_removable(mount: &Mount) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+    use windows_sys::Win32::System::WindowsProgramming::{DRIVE_CDROM, DRIVE_REMOVABLE};
+
+    let root: Vec<u16> = mount
+        .path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    matches!(
+        unsafe { GetDriveTypeW(root.as_ptr()) },
+        DRIVE_REMOVABLE | DRIVE_CDROM
+    )
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn mounts() -> Vec<Mount> {
+    vec![Mount {
+        path: PathBuf::from("/"),
+        source: String::new(),
+        fs_type: String::new(),
+        read_only: false,
+    }]
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn volume_details(mount: &Mount) -> Option<VolumeDetails> {
+    Some(VolumeDetails {
+        label: None,
+        fs_type: mount.fs_type.clone(),
+        read_only: mount.read_only,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn is_removable(_mount: &Mount) -> bool {
+    false
+}
+
+/// Blocks until the mount table may have changed.
+struct MountChanges {
+    #[cfg(target_os = "linux")]
+    file: Option<std::fs::File>,
+}
+
+impl MountChanges {
+    #[cfg(target_os = "linux")]
+    fn new() -> Self {
+        Self {
+            file: std::fs::File::open("/proc/self/mountinfo").ok(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new() -> Self {
+        Self {}
+    }
+
+    /// The kernel flags `mountinfo` with `POLLPRI` on every mount and unmount; reading
+    /// it again re-arms the flag.
+    #[cfg(target_os = "linux")]
+    fn wait(&mut self) {
+        use std::io::{Read, Seek, SeekFrom};
+        use std::os::unix::io::AsRawFd;
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return std::thread::sleep(Duration::from_secs(2)),
+        };
+        let mut poll = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut poll, 1, -1) } < 0 {
+            std::thread::sleep(Duration::from_secs(2));
+        }
+
+        let _ = file.seek(SeekFrom::Start(0));
+        let _ = file.read_to_end(&mut Vec::new());
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn wait(&mut self) {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
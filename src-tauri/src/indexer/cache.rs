@@ -1,26 +1,49 @@
+use super::content::ContentDocument;
 use super::entry::IndexEntry;
 use super::IndexManager;
-use std::collections::HashMap;
+use crate::fs::write_json;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io::{self, BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
-const CACHE_FILE_NAME: &str = "file_index_cache.json";
+const CACHE_DIR_NAME: &str = "index";
+/// Single-index cache files written before scopes existed; only removed, never read.
+const LEGACY_CACHE_FILE_NAMES: &[&str] = &["file_index_cache.json", "content_index_cache.json"];
 const CACHE_MAX_AGE_HOURS: u64 = 24;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct CacheData {
     timestamp: u64,
-    entries: HashMap<PathBuf, IndexEntry>,
+    /// When the entries were last confirmed by a full scan; `timestamp` only records the save.
+    #[serde(default)]
+    verified_at: u64,
+    #[serde(default)]
+    roots: Vec<PathBuf>,
+    entries: BTreeMap<PathBuf, IndexEntry>,
 }
 
-pub fn get_cache_path() -> Option<PathBuf> {
-    dirs::data_local_dir().map(|d| d.join("filemanager").join(CACHE_FILE_NAME))
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ContentCacheData {
+    timestamp: u64,
+    documents: HashMap<PathBuf, ContentDocument>,
+}
+
+fn get_cache_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("filemanager").join(CACHE_DIR_NAME))
+}
+
+pub fn get_cache_path(scope: &str) -> Option<PathBuf> {
+    get_cache_dir().map(|d| d.join(format!("{}.json", scope)))
+}
+
+pub fn get_content_cache_path(scope: &str) -> Option<PathBuf> {
+    get_cache_dir().map(|d| d.join(format!("{}.content.json", scope)))
 }
 
 pub fn save_cache(manager: &IndexManager) -> io::Result<()> {
-    let cache_path = match get_cache_path() {
+    let cache_path = match get_cache_path(manager.scope_name()) {
         Some(p) => p,
         None => {
             return Err(io::Error::new(
@@ -34,25 +57,44 @@ pub fn save_cache(manager: &IndexManager) -> io::Result<()> {
         fs::create_dir_all(parent)?;
     }
 
-    let timestamp = SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    let timestamp = unix_now();
 
     let data = CacheData {
         timestamp,
+        verified_at: manager.verified_at(),
+        roots: manager.get_roots(),
         entries: manager.get_index_clone(),
     };
 
-    let file = fs::File::create(&cache_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer(writer, &data).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    write_json(&cache_path, &data)?;
+
+    if manager.content().is_enabled() {
+        save_content_cache(manager, timestamp)?;
+    }
 
     Ok(())
 }
 
-pub fn load_cache(manager: &IndexManager) -> io::Result<bool> {
-    let cache_path = match get_cache_path() {
+fn save_content_cache(manager: &IndexManager, timestamp: u64) -> io::Result<()> {
+    let cache_path = match get_content_cache_path(manager.scope_name()) {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+
+    let data = ContentCacheData {
+        timestamp,
+        documents: manager.content().get_documents_clone(),
+    };
+
+    write_json(&cache_path, &data)?;
+
+    Ok(())
+}
+
+/// Loads the scope's cache if it was written for exactly these roots. An outdated cache is
+/// still loaded; callers check `is_stale` and verify it in the background.
+pub fn load_cache(manager: &IndexManager, roots: &[PathBuf]) -> io::Result<bool> {
+    let cache_path = match get_cache_path(manager.scope_name()) {
         Some(p) => p,
         None => return Ok(false),
     };
@@ -66,24 +108,86 @@ pub fn load_cache(manager: &IndexManager) -> io::Result<bool> {
     let data: CacheData = serde_json::from_reader(reader)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-    let now = SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    if !same_roots(&data.roots, roots) {
+        return Ok(false);
+    }
 
-    let age_secs = now.saturating_sub(data.timestamp);
-    let max_age_secs = Duration::from_secs(CACHE_MAX_AGE_HOURS * 3600).as_secs();
+    // Caches from before verification tracking count as verified when they were saved
+    let verified_at = if data.verified_at == 0 {
+        data.timestamp
+    } else {
+        data.verified_at
+    };
+    manager.set_verified_at(verified_at);
+    manager.load_from_cache(data.entries);
 
-    if age_secs > max_age_secs {
+    // A content index without its cache needs a fresh scan to be populated
+    if manager.content().is_enabled() && !load_content_cache(manager)? {
         return Ok(false);
     }
 
-    manager.load_from_cache(data.entries);
     Ok(true)
 }
 
-pub fn clear_cache() -> io::Result<()> {
-    if let Some(cache_path) = get_cache_path() {
+fn load_content_cache(manager: &IndexManager) -> io::Result<bool> {
+    let cache_path = match get_content_cache_path(manager.scope_name()) {
+        Some(p) if p.exists() => p,
+        _ => return Ok(false),
+    };
+
+    let file = fs::File::open(&cache_path)?;
+    let reader = BufReader::new(file);
+    let data: ContentCacheData = serde_json::from_reader(reader)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    manager.content().load_from_cache(data.documents);
+    Ok(true)
+}
+
+/// Whether an index last verified at `verified_at` is due for a full verification scan.
+pub fn is_stale(verified_at: u64) -> bool {
+    let max_age = Duration::from_secs(CACHE_MAX_AGE_HOURS * 3600);
+    unix_now().saturating_sub(verified_at) > max_age.as_secs()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn same_roots(cached: &[PathBuf], roots: &[PathBuf]) -> bool {
+    let mut cached: Vec<&Path> = cached.iter().map(PathBuf::as_path).collect();
+    let mut roots: Vec<&Path> = roots.iter().map(PathBuf::as_path).collect();
+    cached.sort();
+    roots.sort();
+    cached == roots
+}
+
+/// Removes the cache of one scope, or of every scope when `scope` is `None`.
+pub fn clear_cache(scope: Option<&str>) -> io::Result<()> {
+    let paths: Vec<PathBuf> = match scope {
+        Some(scope) => [get_cache_path(scope), get_content_cache_path(scope)]
+            .into_iter()
+            .flatten()
+            .collect(),
+        None => {
+            if let Some(dir) = get_cache_dir().filter(|d| d.exists()) {
+                fs::remove_dir_all(dir)?;
+            }
+            dirs::data_local_dir()
+                .map(|d| {
+                    LEGACY_CACHE_FILE_NAMES
+                        .iter()
+                        .map(|name| d.join("filemanager").join(name))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    };
+
+    for cache_path in paths {
         if cache_path.exists() {
             fs::remove_file(&cache_path)?;
         }
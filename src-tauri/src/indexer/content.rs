@@ -0,0 +1,193 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const MAX_CONTENT_FILE_SIZE: u64 = 2 * 1024 * 1024;
+const BINARY_SNIFF_LEN: usize = 8192;
+const MIN_TOKEN_LEN: usize = 2;
+const MAX_TOKEN_LEN: usize = 64;
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "adoc", "org", "log", "csv", "tsv", "json", "toml", "yaml",
+    "yml", "xml", "html", "htm", "css", "scss", "rs", "ts", "tsx", "js", "jsx", "mjs", "svelte",
+    "vue", "py", "rb", "go", "java", "kt", "c", "h", "cpp", "hpp", "cc", "cs", "swift", "php",
+    "sh", "bash", "ps1", "bat", "sql", "ini", "cfg", "conf", "tex",
+];
+
+/// Tokens of a single indexed file, kept so the file can be removed from the postings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentDocument {
+    pub modified: Option<i64>,
+    pub tokens: Vec<String>,
+}
+
+/// Inverted index from lowercase word tokens to the text files containing them.
+pub struct ContentIndex {
+    enabled: AtomicBool,
+    postings: RwLock<HashMap<String, HashSet<PathBuf>>>,
+    documents: RwLock<HashMap<PathBuf, ContentDocument>>,
+}
+
+impl Default for ContentIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            postings: RwLock::new(HashMap::new()),
+            documents: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.documents.read().len()
+    }
+
+    pub fn clear(&self) {
+        self.postings.write().clear();
+        self.documents.write().clear();
+    }
+
+    /// Indexes a file if it looks like text and changed since it was last indexed.
+    pub fn index_file(&self, path: &Path, modified: Option<i64>, size: u64) {
+        if !self.is_enabled() || !is_indexable(path, size) {
+            return;
+        }
+
+        if let Some(doc) = self.documents.read().get(path) {
+            if doc.modified.is_some() && doc.modified == modified {
+                return;
+            }
+        }
+
+        let tokens = match read_text(path) {
+            Some(text) => tokenize(&text),
+            None => {
+                self.remove_file(path);
+                return;
+            }
+        };
+
+        self.insert_document(path.to_path_buf(), ContentDocument { modified, tokens });
+    }
+
+    pub fn remove_file(&self, path: &Path) {
+        let doc = match self.documents.write().remove(path) {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let mut postings = self.postings.write();
+        for token in doc.tokens {
+            if let Some(files) = postings.get_mut(&token) {
+                files.remove(path);
+                if files.is_empty() {
+                    postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    /// Returns files containing every word of the query.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<PathBuf> {
+        let tokens = tokenize(query);
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let postings = self.postings.read();
+        let mut sets: Vec<&HashSet<PathBuf>> = Vec::with_capacity(tokens.len());
+        for token in &tokens {
+            match postings.get(token) {
+                Some(files) => sets.push(files),
+                None => return Vec::new(),
+            }
+        }
+
+        // Intersect starting from the rarest token
+        sets.sort_by_key(|s| s.len());
+        let (first, rest) = sets.split_first().expect("at least one token");
+
+        first
+            .iter()
+            .filter(|path| rest.iter().all(|s| s.contains(*path)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_documents_clone(&self) -> HashMap<PathBuf, ContentDocument> {
+        self.documents.read().clone()
+    }
+
+    pub fn load_from_cache(&self, documents: HashMap<PathBuf, ContentDocument>) {
+        self.clear();
+        for (path, doc) in documents {
+            self.insert_document(path, doc);
+        }
+    }
+
+    fn insert_document(&self, path: PathBuf, doc: ContentDocument) {
+        self.remove_file(&path);
+
+        {
+            let mut postings = self.postings.write();
+            for token in &doc.tokens {
+                postings.entry(token.clone()).or_default().insert(path.clone());
+            }
+        }
+
+        self.documents.write().insert(path, doc);
+    }
+}
+
+fn is_indexable(path: &Path, size: u64) -> bool {
+    if size > MAX_CONTENT_FILE_SIZE {
+        return false;
+    }
+
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| TEXT_EXTENSIONS.contains(&e.as_str()))
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+
+    let sniff_len = bytes.len().min(BINARY_SNIFF_LEN);
+    if bytes[..sniff_len].contains(&0) {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Splits text into unique lowercase alphanumeric words.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| (MIN_TOKEN_LEN..=MAX_TOKEN_LEN).contains(&w.chars().count()))
+        .map(|w| w.to_lowercase())
+        .filter(|w| seen.insert(w.clone()))
+        .collect()
+}
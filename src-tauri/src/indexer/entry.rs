@@ -8,6 +8,9 @@ pub struct IndexEntry {
     pub is_dir: bool,
     pub modified: Option<i64>,
     pub size: Option<u64>,
+    /// Scan that last saw this entry; lets a verification pass drop what it did not see.
+    #[serde(skip)]
+    pub scan_epoch: u32,
 }
 
 impl IndexEntry {
@@ -25,28 +28,92 @@ impl IndexEntry {
             is_dir,
             modified,
             size,
+            scan_epoch: 0,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
+/// Serialized as `status` with an optional `reason`, flattened into the containing struct.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", content = "reason", rename_all = "lowercase")]
 pub enum IndexStatus {
+    #[default]
     Idle,
     Scanning,
     Watching,
-    Error,
+    Error(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RootScanStatus {
+    Pending,
+    Scanning,
+    Done,
+    Cancelled,
+    Cached,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    Native,
+    Polling,
+    Unwatched,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootProgress {
+    pub path: String,
+    pub status: RootScanStatus,
+    pub indexed_count: usize,
+    pub watch_mode: Option<WatchMode>,
 }
 
-impl Default for IndexStatus {
-    fn default() -> Self {
-        Self::Idle
+impl RootProgress {
+    pub fn pending(root: &std::path::Path) -> Self {
+        Self {
+            path: root.display().to_string(),
+            status: RootScanStatus::Pending,
+            indexed_count: 0,
+            watch_mode: None,
+        }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatusChange {
+    pub scope: String,
+    #[serde(flatten)]
+    pub status: IndexStatus,
+}
+
+/// Figures for the current or most recent scan of a scope.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub files_scanned: usize,
+    pub dirs_scanned: usize,
+    pub bytes_scanned: u64,
+    pub error_count: usize,
+    pub permission_denied_count: usize,
+    /// The first few paths that could not be read.
+    pub error_samples: Vec<String>,
+    pub elapsed_ms: u64,
+    /// Only known while rescanning a scope whose previous size is known.
+    pub estimated_remaining_ms: Option<u64>,
+    pub watch_events_per_sec: f64,
+    /// Approximate memory held by the file index.
+    pub index_memory_bytes: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexProgress {
+    pub scope: String,
+    #[serde(flatten)]
     pub status: IndexStatus,
     pub indexed_count: usize,
+    pub content_count: usize,
     pub current_path: Option<String>,
+    pub roots: Vec<RootProgress>,
+    pub stats: IndexStats,
 }
@@ -0,0 +1,213 @@
+use crate::fs::IndexExclusions;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(target_os = "linux")]
+const VIRTUAL_FS_TYPES: &[&str] = &[
+    "proc", "sysfs", "devtmpfs", "devpts", "cgroup", "cgroup2", "debugfs", "tracefs",
+    "securityfs", "pstore", "bpf", "configfs", "fusectl", "mqueue", "hugetlbfs", "autofs",
+    "binfmt_misc", "efivarfs", "rpc_pipefs", "nsfs",
+];
+
+const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Compiled form of `IndexExclusions`, shared by the scanner, the watcher and `search_files`.
+pub struct ExclusionMatcher {
+    rules: IndexExclusions,
+    roots: Vec<PathBuf>,
+    globs: GlobSet,
+    virtual_mounts: Vec<PathBuf>,
+    root_ignores: Vec<(PathBuf, Gitignore)>,
+}
+
+impl ExclusionMatcher {
+    pub fn new(rules: IndexExclusions, roots: &[PathBuf]) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &rules.patterns {
+            if let Ok(glob) = Glob::new(pattern) {
+                builder.add(glob);
+            }
+        }
+        let globs = builder.build().unwrap_or_else(|_| GlobSet::empty());
+
+        let virtual_mounts = if rules.skip_virtual_filesystems {
+            virtual_mount_points()
+        } else {
+            Vec::new()
+        };
+
+        let root_ignores = if rules.respect_ignore_files {
+            roots
+                .iter()
+                .map(|root| (root.clone(), build_root_ignore(root)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            rules,
+            roots: roots.to_vec(),
+            globs,
+            virtual_mounts,
+            root_ignores,
+        }
+    }
+
+    /// Creates a walker over the existing roots that applies every rule while descending.
+    pub fn walk_builder(self: &Arc<Self>) -> Option<WalkBuilder> {
+        self.walk_builder_for(&self.roots)
+    }
+
+    /// Like `walk_builder`, but limited to a subset of the roots.
+    pub fn walk_builder_for(self: &Arc<Self>, roots: &[PathBuf]) -> Option<WalkBuilder> {
+        let roots: Vec<&PathBuf> = roots.iter().filter(|r| r.exists()).collect();
+        let (first, rest) = roots.split_first()?;
+
+        let mut builder = WalkBuilder::new(first);
+        for root in rest {
+            builder.add(root);
+        }
+
+        let respect = self.rules.respect_ignore_files;
+        builder
+            .follow_links(false)
+            .hidden(!self.rules.include_hidden)
+            .git_ignore(respect)
+            .git_exclude(respect)
+            .git_global(false)
+            .ignore(respect)
+            .parents(respect)
+            .require_git(false)
+            .same_file_system(self.rules.one_file_system)
+            .max_depth(self.rules.max_depth);
+
+        let matcher = self.clone();
+        builder.filter_entry(move |entry| {
+            // Roots themselves are never filtered
+            entry.depth() == 0 || !matcher.matches_rules(entry.path())
+        });
+
+        Some(builder)
+    }
+
+    /// Creates a walker for a subtree below one of the roots, keeping `max_depth` relative
+    /// to that root.
+    pub fn subtree_walk_builder(self: &Arc<Self>, path: &Path) -> Option<WalkBuilder> {
+        let mut builder = self.walk_builder_for(&[path.to_path_buf()])?;
+
+        if let Some(max_depth) = self.rules.max_depth {
+            let depth = self
+                .root_for(path)
+                .and_then(|root| path.strip_prefix(root).ok())
+                .map(|relative| relative.components().count())
+                .unwrap_or(0);
+            builder.max_depth(Some(max_depth.saturating_sub(depth)));
+        }
+
+        Some(builder)
+    }
+
+    fn root_for(&self, path: &Path) -> Option<&PathBuf> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+    }
+
+    /// Checks a single path, as reported by the watcher, against all rules.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let root = self.root_for(path);
+
+        if let Some(root) = root {
+            if let Ok(relative) = path.strip_prefix(root) {
+                if let Some(max_depth) = self.rules.max_depth {
+                    if relative.components().count() > max_depth {
+                        return true;
+                    }
+                }
+
+                if !self.rules.include_hidden
+                    && relative
+                        .components()
+                        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+                {
+                    return true;
+                }
+
+                if let Some((_, ignore)) = self.root_ignores.iter().find(|(r, _)| r == root) {
+                    if ignore
+                        .matched_path_or_any_parents(relative, is_dir)
+                        .is_ignore()
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        // Components above the root were chosen by the user and are never excluded
+        path.ancestors()
+            .take_while(|p| root.is_none_or(|root| p != root && p.starts_with(root)))
+            .any(|p| self.matches_rules(p))
+    }
+
+    /// Name, glob and virtual filesystem rules for one path component.
+    fn matches_rules(&self, path: &Path) -> bool {
+        if let Some(name) = path.file_name() {
+            let name = name.to_string_lossy();
+            if self.rules.dir_names.iter().any(|d| *d == name) {
+                return true;
+            }
+            if self.globs.is_match(name.as_ref()) {
+                return true;
+            }
+        }
+
+        if self.globs.is_match(path) {
+            return true;
+        }
+
+        self.virtual_mounts.iter().any(|m| m == path)
+    }
+}
+
+fn build_root_ignore(root: &Path) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    for name in IGNORE_FILE_NAMES {
+        let file = root.join(name);
+        if file.is_file() {
+            let _ = builder.add(file);
+        }
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+#[cfg(target_os = "linux")]
+fn virtual_mount_points() -> Vec<PathBuf> {
+    let mounts = match std::fs::read_to_string("/proc/self/mounts") {
+        Ok(m) => m,
+        Err(_) => return ["/proc", "/sys", "/dev", "/run"].iter().map(PathBuf::from).collect(),
+    };
+
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            VIRTUAL_FS_TYPES
+                .contains(&fs_type)
+                .then(|| PathBuf::from(mount_point.replace("\\040", " ")))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn virtual_mount_points() -> Vec<PathBuf> {
+    Vec::new()
+}
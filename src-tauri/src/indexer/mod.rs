@@ -2,38 +2,68 @@ pub mod entry;
 pub mod scanner;
 pub mod watcher;
 pub mod cache;
+pub mod content;
+pub mod exclusions;
+pub mod power;
+pub mod scheduler;
+pub mod scope;
+pub mod state;
+pub mod stats;
 
 use crate::fs::FileEntry;
-use entry::{IndexEntry, IndexStatus, IndexProgress};
+use content::ContentIndex;
+use entry::{
+    IndexEntry, IndexProgress, IndexStatus, IndexStatusChange, RootProgress, RootScanStatus,
+    WatchMode,
+};
 use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use stats::{estimated_entry_size, ScanStats};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
 
 pub struct IndexManager {
-    index: Arc<RwLock<HashMap<PathBuf, IndexEntry>>>,
+    scope: String,
+    index: Arc<RwLock<BTreeMap<PathBuf, IndexEntry>>>,
     status: Arc<RwLock<IndexStatus>>,
     roots: Arc<RwLock<Vec<PathBuf>>>,
+    root_progress: Arc<RwLock<Vec<RootProgress>>>,
     stop_signal: Arc<RwLock<bool>>,
-}
-
-impl Default for IndexManager {
-    fn default() -> Self {
-        Self::new()
-    }
+    content: ContentIndex,
+    stats: ScanStats,
+    /// Approximate memory held by `index`, kept up to date on every change.
+    index_bytes: AtomicUsize,
+    /// Bumped on every change so unchanged indexes are not saved again.
+    changes: AtomicU64,
+    scan_epoch: AtomicU32,
+    /// Unix time of the last complete scan, 0 if unknown.
+    verified_at: AtomicU64,
 }
 
 impl IndexManager {
-    pub fn new() -> Self {
+    pub fn new(scope: &str) -> Self {
         Self {
-            index: Arc::new(RwLock::new(HashMap::new())),
+            scope: scope.to_string(),
+            index: Arc::new(RwLock::new(BTreeMap::new())),
             status: Arc::new(RwLock::new(IndexStatus::Idle)),
             roots: Arc::new(RwLock::new(Vec::new())),
+            root_progress: Arc::new(RwLock::new(Vec::new())),
             stop_signal: Arc::new(RwLock::new(false)),
+            content: ContentIndex::new(),
+            stats: ScanStats::new(),
+            index_bytes: AtomicUsize::new(0),
+            changes: AtomicU64::new(0),
+            scan_epoch: AtomicU32::new(0),
+            verified_at: AtomicU64::new(0),
         }
     }
 
+    pub fn scope_name(&self) -> &str {
+        &self.scope
+    }
+
     pub fn get_status(&self) -> IndexStatus {
         self.status.read().clone()
     }
@@ -45,17 +75,30 @@ impl IndexManager {
     pub fn set_status(&self, status: IndexStatus, app: Option<&AppHandle>) {
         *self.status.write() = status.clone();
         if let Some(app) = app {
-            let _ = app.emit("index:status", status);
+            let _ = app.emit(
+                "index:status",
+                IndexStatusChange {
+                    scope: self.scope.clone(),
+                    status,
+                },
+            );
         }
     }
 
-    pub fn emit_progress(&self, app: &AppHandle, current_path: Option<String>) {
-        let progress = IndexProgress {
+    pub fn progress(&self, current_path: Option<String>) -> IndexProgress {
+        IndexProgress {
+            scope: self.scope.clone(),
             status: self.get_status(),
             indexed_count: self.get_count(),
+            content_count: self.content.document_count(),
             current_path,
-        };
-        let _ = app.emit("index:progress", progress);
+            roots: self.get_root_progress(),
+            stats: self.stats.snapshot(self.index_bytes.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn emit_progress(&self, app: &AppHandle, current_path: Option<String>) {
+        let _ = app.emit("index:progress", self.progress(current_path));
     }
 
     pub fn should_stop(&self) -> bool {
@@ -70,23 +113,110 @@ impl IndexManager {
         *self.stop_signal.write() = false;
     }
 
+    pub fn content(&self) -> &ContentIndex {
+        &self.content
+    }
+
+    pub fn stats(&self) -> &ScanStats {
+        &self.stats
+    }
+
+    pub fn changes(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+
+    pub fn scan_epoch(&self) -> u32 {
+        self.scan_epoch.load(Ordering::Relaxed)
+    }
+
+    /// Starts a new scan epoch; entries indexed from now on are stamped with it.
+    pub fn begin_epoch(&self) -> u32 {
+        self.scan_epoch.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn verified_at(&self) -> u64 {
+        self.verified_at.load(Ordering::Relaxed)
+    }
+
+    pub fn set_verified_at(&self, timestamp: u64) {
+        self.verified_at.store(timestamp, Ordering::Relaxed);
+    }
+
     pub fn clear(&self) {
-        self.index.write().clear();
+        {
+            let mut index = self.index.write();
+            index.clear();
+            self.index_bytes.store(0, Ordering::Relaxed);
+        }
+        self.content.clear();
+        self.changes.fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn insert(&self, path: PathBuf, entry: IndexEntry) {
-        self.index.write().insert(path, entry);
+        self.insert_batch(vec![(path, entry)]);
+    }
+
+    /// Removes a path and, if it was a directory, everything indexed below it.
+    pub fn remove_subtree(&self, path: &Path) -> usize {
+        self.remove_below(path, |_, _| false)
+    }
+
+    /// Removes everything below `root` that the scan of `epoch` did not see.
+    pub fn sweep(&self, root: &Path, epoch: u32) -> usize {
+        self.remove_below(root, |_, entry| entry.scan_epoch == epoch)
     }
 
-    pub fn remove(&self, path: &PathBuf) {
-        self.index.write().remove(path);
+    fn remove_below(&self, path: &Path, keep: impl Fn(&Path, &IndexEntry) -> bool) -> usize {
+        let removed = {
+            let mut index = self.index.write();
+            // Paths order by component, so descendants directly follow their parent
+            let keys: Vec<PathBuf> = index
+                .range::<Path, _>((std::ops::Bound::Included(path), std::ops::Bound::Unbounded))
+                .take_while(|(p, _)| p.starts_with(path))
+                .filter(|(p, entry)| !keep(p, entry))
+                .map(|(p, _)| p.clone())
+                .collect();
+            let removed: Vec<(PathBuf, IndexEntry)> = keys
+                .into_iter()
+                .filter_map(|k| index.remove(&k).map(|e| (k, e)))
+                .collect();
+
+            let freed = removed
+                .iter()
+                .map(|(path, entry)| estimated_entry_size(path, entry))
+                .sum();
+            self.adjust_bytes(0, freed);
+            removed
+        };
+
+        if !removed.is_empty() {
+            self.changes.fetch_add(1, Ordering::Relaxed);
+        }
+
+        for (path, entry) in &removed {
+            if !entry.is_dir {
+                self.content.remove_file(path);
+            }
+        }
+
+        removed.len()
     }
 
     pub fn insert_batch(&self, entries: Vec<(PathBuf, IndexEntry)>) {
-        let mut index = self.index.write();
-        for (path, entry) in entries {
-            index.insert(path, entry);
+        let mut added = 0;
+        let mut freed = 0;
+        {
+            let mut index = self.index.write();
+            for (path, entry) in entries {
+                added += estimated_entry_size(&path, &entry);
+                if let Some(old) = index.get(&path) {
+                    freed += estimated_entry_size(&path, old);
+                }
+                index.insert(path, entry);
+            }
+            self.adjust_bytes(added, freed);
         }
+        self.changes.fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn search(&self, query: &str, limit: usize) -> Vec<FileEntry> {
@@ -106,16 +236,7 @@ impl IndexManager {
             };
 
             if matches {
-                results.push(FileEntry {
-                    name: entry.name.clone(),
-                    path: path.display().to_string(),
-                    extension: entry.extension.clone(),
-                    size: entry.size,
-                    modified: entry.modified,
-                    is_dir: entry.is_dir,
-                    is_hidden: entry.name.starts_with('.'),
-                    is_symlink: false,
-                });
+                results.push(to_file_entry(path, entry));
 
                 if results.len() >= limit {
                     break;
@@ -126,19 +247,129 @@ impl IndexManager {
         results
     }
 
-    pub fn get_index_clone(&self) -> HashMap<PathBuf, IndexEntry> {
+    pub fn get_entry(&self, path: &Path) -> Option<FileEntry> {
+        self.index.read().get(path).map(|entry| to_file_entry(path, entry))
+    }
+
+    /// Entries below `path` that pass `filter`, in path order.
+    pub fn entries_below(
+        &self,
+        path: &Path,
+        limit: usize,
+        filter: impl Fn(&Path, &IndexEntry) -> bool,
+    ) -> Vec<FileEntry> {
+        self.index
+            .read()
+            .range::<Path, _>((std::ops::Bound::Excluded(path), std::ops::Bound::Unbounded))
+            .take_while(|(p, _)| p.starts_with(path))
+            .filter(|(p, entry)| filter(p, entry))
+            .take(limit)
+            .map(|(p, entry)| to_file_entry(p, entry))
+            .collect()
+    }
+
+    /// Looks up files containing all query words in the content index.
+    pub fn search_content(&self, query: &str, limit: usize) -> Vec<FileEntry> {
+        let paths = self.content.search(query, limit);
+        let index = self.index.read();
+
+        paths
+            .into_iter()
+            .filter_map(|path| index.get(&path).map(|entry| to_file_entry(&path, entry)))
+            .collect()
+    }
+
+    pub fn get_index_clone(&self) -> BTreeMap<PathBuf, IndexEntry> {
         self.index.read().clone()
     }
 
-    pub fn load_from_cache(&self, data: HashMap<PathBuf, IndexEntry>) {
-        *self.index.write() = data;
+    pub fn load_from_cache(&self, data: BTreeMap<PathBuf, IndexEntry>) {
+        let bytes = data
+            .iter()
+            .map(|(path, entry)| estimated_entry_size(path, entry))
+            .sum();
+        let mut index = self.index.write();
+        *index = data;
+        self.index_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Updates the estimated index size. Called with the index locked, so that it stays in
+    /// step with the entries; never goes below zero should the estimates drift.
+    fn adjust_bytes(&self, added: usize, freed: usize) {
+        let _ = self
+            .index_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bytes| {
+                Some((bytes + added).saturating_sub(freed))
+            });
     }
 
     pub fn set_roots(&self, roots: Vec<PathBuf>) {
+        *self.root_progress.write() = roots.iter().map(|root| RootProgress::pending(root)).collect();
         *self.roots.write() = roots;
     }
 
+    /// Appends a root and returns its index, or the existing index if it is already known.
+    pub fn add_root(&self, root: PathBuf) -> usize {
+        let mut roots = self.roots.write();
+        if let Some(index) = roots.iter().position(|r| *r == root) {
+            return index;
+        }
+
+        self.root_progress.write().push(RootProgress::pending(&root));
+        roots.push(root);
+        roots.len() - 1
+    }
+
+    /// Forgets a root along with everything below it that no other root covers.
+    pub fn remove_root(&self, root: &Path) -> bool {
+        let remaining = {
+            let mut roots = self.roots.write();
+            let position = match roots.iter().position(|r| r == root) {
+                Some(position) => position,
+                None => return false,
+            };
+            roots.remove(position);
+            self.root_progress.write().remove(position);
+            roots.clone()
+        };
+
+        self.remove_below(root, |path, _| remaining.iter().any(|r| path.starts_with(r)));
+        true
+    }
+
+    pub fn update_root_progress(&self, root_index: usize, status: RootScanStatus, count: usize) {
+        if let Some(progress) = self.root_progress.write().get_mut(root_index) {
+            progress.status = status;
+            progress.indexed_count = count;
+        }
+    }
+
+    pub fn set_root_watch_mode(&self, root: &Path, mode: WatchMode) {
+        let root = root.display().to_string();
+        if let Some(progress) = self.root_progress.write().iter_mut().find(|p| p.path == root) {
+            progress.watch_mode = Some(mode);
+        }
+    }
+
+    pub fn get_root_progress(&self) -> Vec<RootProgress> {
+        self.root_progress.read().clone()
+    }
+
     pub fn get_roots(&self) -> Vec<PathBuf> {
         self.roots.read().clone()
     }
 }
+
+fn to_file_entry(path: &std::path::Path, entry: &IndexEntry) -> FileEntry {
+    FileEntry {
+        name: entry.name.clone(),
+        path: path.display().to_string(),
+        extension: entry.extension.clone(),
+        size: entry.size,
+        modified: entry.modified,
+        is_dir: entry.is_dir,
+        is_hidden: entry.name.starts_with('.'),
+        is_symlink: false,
+        tags: Vec::new(),
+    }
+}
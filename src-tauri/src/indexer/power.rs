@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// Whether the machine is running on battery; `None` when that cannot be determined,
+/// e.g. on desktops without a battery.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> Option<bool> {
+    let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    let mut has_battery = false;
+
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+
+        match read("type").trim() {
+            "Mains" | "USB" if read("online").trim() == "1" => return Some(false),
+            // Mice and other peripherals report their batteries with a "Device" scope
+            "Battery" if read("scope").trim() != "Device" => has_battery = true,
+            _ => {}
+        }
+    }
+
+    has_battery.then_some(true)
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> Option<bool> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    if text.contains("'Battery Power'") {
+        Some(true)
+    } else if text.contains("'AC Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn on_battery() -> Option<bool> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn on_battery() -> Option<bool> {
+    None
+}
+
+/// Time since the last keyboard or mouse input; `None` where the platform does not expose it.
+#[cfg(target_os = "windows")]
+pub fn idle_time() -> Option<Duration> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+
+    // Both are 32-bit tick counts, so wrapping subtraction survives the 49 day rollover
+    let idle_ms = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+    Some(Duration::from_millis(u64::from(idle_ms)))
+}
+
+#[cfg(target_os = "macos")]
+pub fn idle_time() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4", "-r", "-k", "HIDIdleTime"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+
+    let line = text.lines().find(|l| l.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+/// Linux has no desktop-independent way to query input idle time.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn idle_time() -> Option<Duration> {
+    None
+}
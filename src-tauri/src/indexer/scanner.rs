@@ -1,98 +1,417 @@
-use super::entry::IndexEntry;
+use super::entry::{IndexEntry, IndexStatus, RootScanStatus};
+use super::cache;
+use super::exclusions::ExclusionMatcher;
 use super::IndexManager;
-use std::path::PathBuf;
+use ignore::{DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkState};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
 use tauri::AppHandle;
-use walkdir::WalkDir;
 
 const PROGRESS_BATCH_SIZE: usize = 5000;
+const PROGRESS_INTERVAL_MS: u64 = 250;
+const INSERT_BATCH_SIZE: usize = 1000;
+const THROTTLE_EVERY: usize = 500;
+const THROTTLE_SLEEP_MS: u64 = 5;
 
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScanOptions {
+    /// Worker threads per root; defaults to the number of CPUs.
+    pub threads: Option<usize>,
+    /// Fewer threads, lowered thread priority and short pauses between batches.
+    pub low_priority: bool,
+}
+
+impl ScanOptions {
+    fn thread_count(&self) -> usize {
+        let cpus = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        let threads = self.threads.unwrap_or(cpus).max(1);
+
+        if self.low_priority {
+            (threads / 4).max(1)
+        } else {
+            threads
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanMode {
+    /// Clears the index and builds it from scratch.
+    Fresh,
+    /// Updates the index in place and drops entries the walk no longer finds, keeping the
+    /// index searchable throughout.
+    Verify,
+}
+
+/// State shared by the worker threads walking a single root.
+struct ScanContext {
+    manager: Arc<IndexManager>,
+    app: AppHandle,
+    root_index: usize,
+    root_count: AtomicUsize,
+    low_priority: bool,
+    last_emit: Mutex<Instant>,
+}
+
+impl ScanContext {
+    fn flush(&self, batch: Vec<(PathBuf, IndexEntry)>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let added = batch.len();
+        let current_path = batch.last().map(|(p, _)| p.display().to_string());
+        self.manager.insert_batch(batch);
+
+        let previous = self.root_count.fetch_add(added, Ordering::Relaxed);
+        let count = previous + added;
+        self.manager
+            .update_root_progress(self.root_index, RootScanStatus::Scanning, count);
+
+        // Emit per batch of entries, and at least every interval so timings stay fresh
+        let due = {
+            let mut last_emit = self.last_emit.lock();
+            let due = previous / PROGRESS_BATCH_SIZE != count / PROGRESS_BATCH_SIZE
+                || last_emit.elapsed() >= Duration::from_millis(PROGRESS_INTERVAL_MS);
+            if due {
+                *last_emit = Instant::now();
+            }
+            due
+        };
+        if due {
+            self.manager.emit_progress(&self.app, current_path);
+        }
+    }
+}
+
+struct ScanVisitorBuilder {
+    ctx: Arc<ScanContext>,
+}
+
+impl<'s> ParallelVisitorBuilder<'s> for ScanVisitorBuilder {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(ScanVisitor {
+            ctx: self.ctx.clone(),
+            batch: Vec::with_capacity(INSERT_BATCH_SIZE),
+            visited: 0,
+        })
+    }
+}
+
+struct ScanVisitor {
+    ctx: Arc<ScanContext>,
+    batch: Vec<(PathBuf, IndexEntry)>,
+    visited: usize,
+}
+
+impl ParallelVisitor for ScanVisitor {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        if self.ctx.manager.should_stop() {
+            return WalkState::Quit;
+        }
+
+        // An entry that cannot be read is counted as an error and skipped
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let denied = err
+                    .io_error()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied);
+                self.ctx.manager.stats().record_error(error_path(&err), denied);
+                return WalkState::Continue;
+            }
+        };
+
+        if self.ctx.low_priority {
+            if self.visited == 0 {
+                lower_thread_priority();
+            }
+            if self.visited > 0 && self.visited.is_multiple_of(THROTTLE_EVERY) {
+                std::thread::sleep(Duration::from_millis(THROTTLE_SLEEP_MS));
+            }
+        }
+        self.visited += 1;
+
+        match index_entry_for(&self.ctx.manager, &entry) {
+            Some(item) => {
+                self.ctx.manager.stats().record_entry(item.1.is_dir, item.1.size);
+                self.batch.push(item);
+            }
+            None => self.ctx.manager.stats().record_error(Some(entry.path()), false),
+        }
+
+        if self.batch.len() >= INSERT_BATCH_SIZE {
+            self.ctx.flush(std::mem::take(&mut self.batch));
+        }
+
+        WalkState::Continue
+    }
+}
+
+impl Drop for ScanVisitor {
+    fn drop(&mut self) {
+        self.ctx.flush(std::mem::take(&mut self.batch));
+    }
+}
+
+/// Scans all roots on the blocking thread pool, walking each root with a parallel
+/// work-stealing walker.
 pub async fn scan_directories(
     manager: Arc<IndexManager>,
     roots: Vec<PathBuf>,
+    exclusions: Arc<ExclusionMatcher>,
+    options: ScanOptions,
+    mode: ScanMode,
     app: AppHandle,
 ) {
-    manager.clear();
+    let _ = tokio::task::spawn_blocking(move || {
+        scan_blocking(manager, roots, exclusions, options, mode, app);
+    })
+    .await;
+}
+
+fn scan_blocking(
+    manager: Arc<IndexManager>,
+    roots: Vec<PathBuf>,
+    exclusions: Arc<ExclusionMatcher>,
+    options: ScanOptions,
+    mode: ScanMode,
+    app: AppHandle,
+) {
+    // The previous index size, from an earlier run or the cache, drives the ETA
+    manager.stats().begin(manager.get_count());
+    let epoch = manager.begin_epoch();
+    if mode == ScanMode::Fresh {
+        manager.clear();
+    }
     manager.set_roots(roots.clone());
     manager.reset_stop();
-    manager.set_status(super::entry::IndexStatus::Scanning, Some(&app));
+    manager.set_status(IndexStatus::Scanning, Some(&app));
 
-    let mut batch = Vec::with_capacity(PROGRESS_BATCH_SIZE);
-    let mut total_count: usize = 0;
-
-    for root in roots {
+    let mut readable_roots = Vec::new();
+    for (root_index, root) in roots.iter().enumerate() {
         if manager.should_stop() {
             break;
         }
-
-        if !root.exists() {
-            continue;
+        if scan_one_root(&manager, root_index, root, &exclusions, &options, &app) {
+            readable_roots.push(root);
         }
+    }
 
-        for entry in WalkDir::new(&root)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if manager.should_stop() {
-                break;
-            }
+    if !roots.is_empty() && readable_roots.is_empty() && !manager.should_stop() {
+        manager.stats().finish();
+        manager.set_status(
+            IndexStatus::Error("None of the index roots could be read".to_string()),
+            Some(&app),
+        );
+        manager.emit_progress(&app, None);
+        return;
+    }
 
-            let path = entry.path().to_path_buf();
-            let file_name = entry.file_name().to_string_lossy().to_string();
-
-            let metadata = match entry.metadata() {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
-
-            let extension = if metadata.is_file() {
-                PathBuf::from(&file_name)
-                    .extension()
-                    .map(|e| e.to_string_lossy().to_string())
-            } else {
-                None
-            };
-
-            let modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64);
-
-            let size = if metadata.is_file() {
-                Some(metadata.len())
-            } else {
-                None
-            };
-
-            let index_entry = IndexEntry::new(
-                file_name,
-                extension,
-                metadata.is_dir(),
-                modified,
-                size,
-            );
-
-            batch.push((path, index_entry));
-            total_count += 1;
-
-            if batch.len() >= PROGRESS_BATCH_SIZE {
-                let current_path = entry.path().display().to_string();
-                manager.insert_batch(std::mem::take(&mut batch));
-                manager.emit_progress(&app, Some(current_path));
-                batch = Vec::with_capacity(PROGRESS_BATCH_SIZE);
+    if !manager.should_stop() {
+        // Roots that could not be read this time, e.g. an offline drive, keep their entries
+        if mode == ScanMode::Verify {
+            for root in readable_roots {
+                manager.sweep(root, epoch);
             }
         }
+        manager.set_verified_at(cache::unix_now());
     }
 
-    if !batch.is_empty() {
-        manager.insert_batch(batch);
+    finish_scan(&manager, &app);
+}
+
+/// Scans a single root that was added with `IndexManager::add_root`, leaving the rest
+/// of the index untouched.
+pub async fn scan_root(
+    manager: Arc<IndexManager>,
+    root_index: usize,
+    root: PathBuf,
+    exclusions: Arc<ExclusionMatcher>,
+    options: ScanOptions,
+    app: AppHandle,
+) {
+    let _ = tokio::task::spawn_blocking(move || {
+        manager.stats().begin(0);
+        manager.reset_stop();
+        manager.set_status(IndexStatus::Scanning, Some(&app));
+
+        // Anything already indexed below the root, e.g. through a parent root, is rewalked
+        manager.remove_subtree(&root);
+        scan_one_root(&manager, root_index, &root, &exclusions, &options, &app);
+
+        finish_scan(&manager, &app);
+    })
+    .await;
+}
+
+/// Walks one root; returns false if the root itself could not be read.
+fn scan_one_root(
+    manager: &Arc<IndexManager>,
+    root_index: usize,
+    root: &Path,
+    exclusions: &Arc<ExclusionMatcher>,
+    options: &ScanOptions,
+    app: &AppHandle,
+) -> bool {
+    if let Err(e) = std::fs::read_dir(root) {
+        let denied = e.kind() == std::io::ErrorKind::PermissionDenied;
+        manager.stats().record_error(Some(root), denied);
+        manager.update_root_progress(root_index, RootScanStatus::Done, 0);
+        return false;
     }
 
+    let mut builder = match exclusions.walk_builder_for(&[root.to_path_buf()]) {
+        Some(builder) => builder,
+        None => {
+            manager.update_root_progress(root_index, RootScanStatus::Done, 0);
+            return false;
+        }
+    };
+    builder.threads(options.thread_count());
+
+    let ctx = Arc::new(ScanContext {
+        manager: manager.clone(),
+        app: app.clone(),
+        root_index,
+        root_count: AtomicUsize::new(0),
+        low_priority: options.low_priority,
+        last_emit: Mutex::new(Instant::now()),
+    });
+
+    manager.update_root_progress(root_index, RootScanStatus::Scanning, 0);
+    builder
+        .build_parallel()
+        .visit(&mut ScanVisitorBuilder { ctx: ctx.clone() });
+
+    let status = if manager.should_stop() {
+        RootScanStatus::Cancelled
+    } else {
+        RootScanStatus::Done
+    };
+    manager.update_root_progress(root_index, status, ctx.root_count.load(Ordering::Relaxed));
+    manager.emit_progress(app, None);
+    true
+}
+
+fn finish_scan(manager: &IndexManager, app: &AppHandle) {
+    manager.stats().finish();
+
     if !manager.should_stop() {
-        manager.set_status(super::entry::IndexStatus::Watching, Some(&app));
+        manager.set_status(IndexStatus::Watching, Some(app));
     }
 
-    manager.emit_progress(&app, None);
+    manager.emit_progress(app, None);
+}
+
+/// Re-walks `path` and replaces everything the index holds below it. Used when a
+/// directory moved in or watcher events may have been lost.
+pub fn reconcile_subtree(
+    manager: &IndexManager,
+    exclusions: &Arc<ExclusionMatcher>,
+    path: &Path,
+) {
+    manager.remove_subtree(path);
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    if exclusions.is_excluded(path, metadata.is_dir()) {
+        return;
+    }
+
+    let builder = match exclusions.subtree_walk_builder(path) {
+        Some(builder) => builder,
+        None => return,
+    };
+
+    let mut batch = Vec::with_capacity(INSERT_BATCH_SIZE);
+    for entry in builder.build().filter_map(|e| e.ok()) {
+        if let Some(item) = index_entry_for(manager, &entry) {
+            batch.push(item);
+        }
+
+        if batch.len() >= INSERT_BATCH_SIZE {
+            manager.insert_batch(std::mem::take(&mut batch));
+        }
+    }
+
+    manager.insert_batch(batch);
+}
+
+/// The path an ignore error refers to, looking through depth and line number wrappers.
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+fn index_entry_for(manager: &IndexManager, entry: &DirEntry) -> Option<(PathBuf, IndexEntry)> {
+    let path = entry.path().to_path_buf();
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    let metadata = entry.metadata().ok()?;
+
+    let extension = if metadata.is_file() {
+        PathBuf::from(&file_name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+
+    let size = if metadata.is_file() {
+        Some(metadata.len())
+    } else {
+        None
+    };
+
+    if let Some(size) = size {
+        manager.content().index_file(&path, modified, size);
+    }
+
+    let mut index_entry = IndexEntry::new(file_name, extension, metadata.is_dir(), modified, size);
+    index_entry.scan_epoch = manager.scan_epoch();
+
+    Some((path, index_entry))
+}
+
+/// Drops the priority of the current walker thread so indexing yields to the desktop.
+fn lower_thread_priority() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        // On Linux the nice value is per thread, so this only affects the walker
+        libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe {
+        libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG);
+    }
+
+    #[cfg(target_os = "windows")]
+    unsafe {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+        };
+        SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN);
+    }
 }
@@ -0,0 +1,133 @@
+use super::cache;
+use super::entry::IndexStatus;
+use super::power;
+use super::scanner::ScanOptions;
+use super::state::{start_scope, IndexerState};
+use crate::commands::config::load_config;
+use crate::fs::IndexSchedule;
+use chrono::{DateTime, Local};
+use croner::Cron;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+const TICK_SECS: u64 = 60;
+const CACHE_SAVE_INTERVAL_SECS: u64 = 600;
+
+/// Scheduling state of one configured scope between ticks.
+#[derive(Default)]
+struct ScopeTimers {
+    cron_checked: Option<DateTime<Local>>,
+    last_run: Option<Instant>,
+    /// A run came due while it could not start, e.g. on battery.
+    pending: bool,
+}
+
+/// Runs scheduled and idle-time verification, re-verifies stale indexes and saves changed
+/// caches periodically for as long as the app runs.
+pub fn spawn(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut timers: HashMap<String, ScopeTimers> = HashMap::new();
+        let mut last_save = Instant::now();
+
+        let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+        // The first tick completes immediately; startup is left to the frontend
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            tick(&app, &mut timers).await;
+
+            if last_save.elapsed() >= Duration::from_secs(CACHE_SAVE_INTERVAL_SECS) {
+                app.state::<IndexerState>().save_caches();
+                last_save = Instant::now();
+            }
+        }
+    });
+}
+
+async fn tick(app: &AppHandle, timers: &mut HashMap<String, ScopeTimers>) {
+    let config = load_config();
+    let state = app.state::<IndexerState>();
+    let on_battery = power::on_battery().unwrap_or(false);
+    let idle = power::idle_time();
+    let now = Local::now();
+
+    for defined in &config.index_scopes {
+        let schedule = match &defined.schedule {
+            Some(schedule) => schedule,
+            None => continue,
+        };
+
+        let timers = timers.entry(defined.name.clone()).or_default();
+        if cron_due(schedule, timers, now) || idle_due(schedule, timers, idle) {
+            timers.pending = true;
+        }
+
+        if !timers.pending || (schedule.pause_on_battery && on_battery) {
+            continue;
+        }
+        timers.pending = false;
+        timers.last_run = Some(Instant::now());
+
+        run_scope(&state, &defined.name, app).await;
+    }
+
+    // Indexes nobody scheduled still get verified once their last full scan is too old
+    if on_battery {
+        return;
+    }
+    for scope in state.scopes() {
+        let manager = scope.manager();
+        if manager.get_status() == IndexStatus::Watching
+            && !scope.is_scanning()
+            && cache::is_stale(manager.verified_at())
+        {
+            let _ = scope.verify(app.clone()).await;
+        }
+    }
+}
+
+/// Whether a cron occurrence fell between the previous tick and `now`.
+fn cron_due(schedule: &IndexSchedule, timers: &mut ScopeTimers, now: DateTime<Local>) -> bool {
+    let expression = match &schedule.cron {
+        Some(expression) => expression,
+        None => return false,
+    };
+    let since = timers.cron_checked.replace(now).unwrap_or(now);
+
+    match Cron::new(expression).parse() {
+        Ok(cron) => cron
+            .find_next_occurrence(&since, false)
+            .is_ok_and(|next| next <= now),
+        Err(_) => false,
+    }
+}
+
+/// Fires once per idle period: only if nothing ran since the user went idle.
+fn idle_due(schedule: &IndexSchedule, timers: &ScopeTimers, idle: Option<Duration>) -> bool {
+    match (schedule.idle_minutes, idle) {
+        (Some(minutes), Some(idle)) => {
+            idle >= Duration::from_secs(minutes * 60)
+                && timers.last_run.is_none_or(|run| run.elapsed() > idle)
+        }
+        _ => false,
+    }
+}
+
+/// Verifies a running scope, or starts a configured one that is not running yet.
+async fn run_scope(state: &IndexerState, name: &str, app: &AppHandle) {
+    if let Ok(scope) = state.scope(name) {
+        if !scope.manager().get_roots().is_empty() {
+            // A scope that is already scanning is as good as verified
+            let _ = scope.verify(app.clone()).await;
+            return;
+        }
+    }
+
+    let options = ScanOptions {
+        low_priority: true,
+        ..ScanOptions::default()
+    };
+    let _ = start_scope(state, name, Vec::new(), None, Some(options), app.clone()).await;
+}
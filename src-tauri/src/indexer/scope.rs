@@ -0,0 +1,279 @@
+use super::cache;
+use super::entry::{IndexStatus, RootScanStatus};
+use super::exclusions::ExclusionMatcher;
+use super::scanner::{self, ScanMode, ScanOptions};
+use super::watcher::FileWatcher;
+use super::IndexManager;
+use crate::fs::{AppError, IndexExclusions, WatcherSettings};
+use parking_lot::{Mutex, RwLock};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::task::JoinHandle;
+
+/// Scope used when the frontend does not name one.
+pub const DEFAULT_SCOPE: &str = "default";
+
+const MAX_SCOPE_NAME_LEN: usize = 64;
+
+/// Scope names double as cache file names, so they are kept to a safe character set.
+pub fn validate_scope_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SCOPE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidOperation(format!(
+            "Invalid index scope name '{}': use letters, digits, '-' or '_'",
+            name
+        )))
+    }
+}
+
+/// Rules a scope is scanned and watched with, fixed until it is started again.
+#[derive(Debug, Clone, Default)]
+pub struct ScopeSettings {
+    pub exclusions: IndexExclusions,
+    pub watcher: WatcherSettings,
+    pub scan: ScanOptions,
+}
+
+/// A named set of roots with its own index, cache, watcher and scan task.
+pub struct IndexScope {
+    manager: Arc<IndexManager>,
+    watcher: Mutex<FileWatcher>,
+    scan_task: Mutex<Option<JoinHandle<()>>>,
+    settings: RwLock<ScopeSettings>,
+    /// `IndexManager::changes` as of the last cache save or load.
+    saved_changes: AtomicU64,
+}
+
+impl IndexScope {
+    pub fn new(name: &str) -> Self {
+        Self {
+            manager: Arc::new(IndexManager::new(name)),
+            watcher: Mutex::new(FileWatcher::new()),
+            scan_task: Mutex::new(None),
+            settings: RwLock::new(ScopeSettings::default()),
+            saved_changes: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.manager.scope_name()
+    }
+
+    pub fn manager(&self) -> &Arc<IndexManager> {
+        &self.manager
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan_task
+            .lock()
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
+    /// Stops the watcher and waits for a running scan to wind down, so a new run never
+    /// overlaps with the previous one.
+    pub async fn shutdown(&self) {
+        self.watcher.lock().stop();
+
+        let task = self.scan_task.lock().take();
+        if let Some(task) = task {
+            if !task.is_finished() {
+                self.manager.request_stop();
+                let _ = task.await;
+            }
+        }
+    }
+
+    /// Replaces the scope's roots, reusing its cache when it matches or scanning from scratch.
+    pub async fn start(
+        self: &Arc<Self>,
+        roots: Vec<PathBuf>,
+        index_content: bool,
+        settings: ScopeSettings,
+        app: AppHandle,
+    ) {
+        self.shutdown().await;
+        *self.settings.write() = settings;
+
+        let manager = &self.manager;
+        manager.content().set_enabled(index_content);
+
+        if cache::load_cache(manager, &roots).unwrap_or(false) && manager.get_count() > 0 {
+            self.saved_changes.store(manager.changes(), Ordering::Relaxed);
+            manager.set_roots(roots.clone());
+            for index in 0..roots.len() {
+                manager.update_root_progress(index, RootScanStatus::Cached, 0);
+            }
+
+            // An outdated cache stays searchable while a background pass verifies it
+            if cache::is_stale(manager.verified_at()) {
+                self.spawn_scan(roots, ScanMode::Verify, app);
+                return;
+            }
+
+            manager.set_status(IndexStatus::Watching, Some(&app));
+            self.start_watcher(&app);
+            return;
+        }
+
+        self.spawn_scan(roots, ScanMode::Fresh, app);
+    }
+
+    /// Rescans every root at low priority, updating the index in place and dropping what
+    /// no longer exists.
+    pub async fn verify(self: &Arc<Self>, app: AppHandle) -> Result<(), AppError> {
+        self.ensure_not_scanning()?;
+
+        let roots = self.manager.get_roots();
+        if roots.is_empty() {
+            return Ok(());
+        }
+
+        self.shutdown().await;
+        self.spawn_scan(roots, ScanMode::Verify, app);
+        Ok(())
+    }
+
+    fn spawn_scan(self: &Arc<Self>, roots: Vec<PathBuf>, mode: ScanMode, app: AppHandle) {
+        let scope = self.clone();
+        let task = tokio::spawn(async move {
+            let settings = scope.settings.read().clone();
+            let exclusions = Arc::new(ExclusionMatcher::new(settings.exclusions, &roots));
+
+            // Verification runs in the background, so it always yields to the desktop
+            let options = match mode {
+                ScanMode::Fresh => settings.scan,
+                ScanMode::Verify => ScanOptions {
+                    low_priority: true,
+                    ..settings.scan
+                },
+            };
+
+            scanner::scan_directories(
+                scope.manager.clone(),
+                roots,
+                exclusions,
+                options,
+                mode,
+                app.clone(),
+            )
+            .await;
+            scope.finish_scan(&app);
+        });
+        *self.scan_task.lock() = Some(task);
+    }
+
+    /// Writes the cache if the index changed since it was last saved or loaded.
+    pub fn save_cache(&self) -> io::Result<bool> {
+        let changes = self.manager.changes();
+        if changes == self.saved_changes.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        cache::save_cache(&self.manager)?;
+        self.saved_changes.store(changes, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Scans one more root without discarding what the scope has already indexed.
+    pub async fn add_root(self: &Arc<Self>, root: PathBuf, app: AppHandle) -> Result<(), AppError> {
+        if !root.is_dir() {
+            return Err(AppError::NotADirectory(root.display().to_string()));
+        }
+        if self.manager.get_roots().contains(&root) {
+            return Ok(());
+        }
+        self.ensure_not_scanning()?;
+
+        // The watcher is rebuilt below so it covers the new root as well
+        self.shutdown().await;
+        let root_index = self.manager.add_root(root.clone());
+
+        let scope = self.clone();
+        let task = tokio::spawn(async move {
+            let settings = scope.settings.read().clone();
+            let exclusions = Arc::new(ExclusionMatcher::new(
+                settings.exclusions,
+                &scope.manager.get_roots(),
+            ));
+            scanner::scan_root(
+                scope.manager.clone(),
+                root_index,
+                root,
+                exclusions,
+                settings.scan,
+                app.clone(),
+            )
+            .await;
+            scope.finish_scan(&app);
+        });
+        *self.scan_task.lock() = Some(task);
+
+        Ok(())
+    }
+
+    /// Drops one root and whatever was indexed only through it.
+    pub async fn remove_root(&self, root: &Path, app: AppHandle) -> Result<(), AppError> {
+        if !self.manager.get_roots().iter().any(|r| r == root) {
+            return Err(AppError::NotFound(root.display().to_string()));
+        }
+        self.ensure_not_scanning()?;
+
+        self.shutdown().await;
+        self.manager.remove_root(root);
+        let _ = self.save_cache();
+
+        if self.manager.get_roots().is_empty() {
+            self.manager.set_status(IndexStatus::Idle, Some(&app));
+            self.manager.emit_progress(&app, None);
+        } else {
+            self.start_watcher(&app);
+        }
+
+        Ok(())
+    }
+
+    fn ensure_not_scanning(&self) -> Result<(), AppError> {
+        if self.is_scanning() {
+            return Err(AppError::InvalidOperation(format!(
+                "Index scope '{}' is still scanning",
+                self.name()
+            )));
+        }
+        Ok(())
+    }
+
+    fn finish_scan(&self, app: &AppHandle) {
+        // stop_indexing during the scan means the user no longer wants a watcher either
+        if self.manager.should_stop() {
+            return;
+        }
+
+        let _ = self.save_cache();
+        self.start_watcher(app);
+    }
+
+    fn start_watcher(&self, app: &AppHandle) {
+        let roots = self.manager.get_roots();
+        let settings = self.settings.read().clone();
+        let exclusions = Arc::new(ExclusionMatcher::new(settings.exclusions, &roots));
+
+        let _ = self.watcher.lock().start(
+            self.manager.clone(),
+            roots,
+            exclusions,
+            settings.watcher,
+            app.clone(),
+        );
+    }
+}
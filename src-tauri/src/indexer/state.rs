@@ -0,0 +1,126 @@
+//! The indexer's scopes as app state, shared by the indexer commands, the scheduler and
+//! the watchers.
+
+use super::entry::IndexStatus;
+use super::scanner::ScanOptions;
+use super::scope::{validate_scope_name, IndexScope, ScopeSettings, DEFAULT_SCOPE};
+use crate::commands::config::load_config;
+use crate::fs::AppError;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub struct IndexerState {
+    scopes: RwLock<HashMap<String, Arc<IndexScope>>>,
+}
+
+impl IndexerState {
+    pub fn new() -> Self {
+        let mut scopes = HashMap::new();
+        scopes.insert(
+            DEFAULT_SCOPE.to_string(),
+            Arc::new(IndexScope::new(DEFAULT_SCOPE)),
+        );
+
+        Self {
+            scopes: RwLock::new(scopes),
+        }
+    }
+
+    pub fn scope(&self, name: &str) -> Result<Arc<IndexScope>, AppError> {
+        self.scopes
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Index scope '{}'", name)))
+    }
+
+    pub fn scope_or_create(&self, name: &str) -> Arc<IndexScope> {
+        self.scopes
+            .write()
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(IndexScope::new(name)))
+            .clone()
+    }
+
+    /// Takes a scope out of the state; the caller shuts it down.
+    pub fn remove(&self, name: &str) -> Option<Arc<IndexScope>> {
+        self.scopes.write().remove(name)
+    }
+
+    pub fn scopes(&self) -> Vec<Arc<IndexScope>> {
+        let mut scopes: Vec<_> = self.scopes.read().values().cloned().collect();
+        scopes.sort_by(|a, b| a.name().cmp(b.name()));
+        scopes
+    }
+
+    /// Saves every idle scope whose index changed since its cache was written. Scopes that
+    /// are mid-scan are skipped; their scan saves when it completes.
+    pub fn save_caches(&self) {
+        for scope in self.scopes() {
+            if !scope.is_scanning() && scope.manager().get_status() == IndexStatus::Watching {
+                let _ = scope.save_cache();
+            }
+        }
+    }
+
+    /// The named scope, or every scope when no name is given.
+    pub fn selected(&self, name: Option<&str>) -> Result<Vec<Arc<IndexScope>>, AppError> {
+        match name {
+            Some(name) => Ok(vec![self.scope(name)?]),
+            None => Ok(self.scopes()),
+        }
+    }
+}
+
+impl Default for IndexerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Starts a scope, filling in whatever the caller left out from its `Config.index_scopes`
+/// entry. Also used by the scheduler to bring up configured scopes.
+pub async fn start_scope(
+    state: &IndexerState,
+    name: &str,
+    roots: Vec<String>,
+    index_content: Option<bool>,
+    scan_options: Option<ScanOptions>,
+    app: AppHandle,
+) -> Result<(), AppError> {
+    validate_scope_name(name)?;
+
+    let config = load_config();
+    let defined = config.index_scopes.iter().find(|s| s.name == name).cloned();
+
+    // Roots passed by the caller win; an empty list starts the scope as configured
+    let root_paths: Vec<PathBuf> = if roots.is_empty() {
+        defined
+            .as_ref()
+            .map(|s| s.roots.iter().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    } else {
+        roots.into_iter().map(PathBuf::from).collect()
+    };
+
+    let index_content = index_content
+        .or(defined.as_ref().map(|s| s.index_content))
+        .unwrap_or(false);
+    let settings = ScopeSettings {
+        exclusions: defined
+            .and_then(|s| s.exclusions)
+            .unwrap_or(config.index_exclusions),
+        watcher: config.watcher,
+        scan: scan_options.unwrap_or_default(),
+    };
+
+    state
+        .scope_or_create(name)
+        .start(root_paths, index_content, settings, app)
+        .await;
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::entry::{IndexEntry, IndexStats};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const MAX_ERROR_SAMPLES: usize = 10;
+const EVENT_RATE_WINDOW: Duration = Duration::from_secs(5);
+/// Rough per-entry cost of a BTreeMap slot on top of the key and value themselves.
+const MAP_ENTRY_OVERHEAD: usize = 16;
+
+/// Counters for the current or most recent scan plus the watcher's event rate.
+#[derive(Default)]
+pub struct ScanStats {
+    files: AtomicUsize,
+    dirs: AtomicUsize,
+    bytes: AtomicU64,
+    errors: AtomicUsize,
+    permission_denied: AtomicUsize,
+    error_samples: Mutex<Vec<String>>,
+    timing: Mutex<ScanTiming>,
+    /// Entry count of the previous index, used to estimate the remaining time.
+    expected_total: AtomicUsize,
+    events: Mutex<EventWindow>,
+}
+
+#[derive(Default)]
+struct ScanTiming {
+    started: Option<Instant>,
+    finished: Option<Duration>,
+}
+
+#[derive(Default)]
+struct EventWindow {
+    started: Option<Instant>,
+    count: usize,
+    rate: f64,
+}
+
+impl ScanStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resets the scan counters; `expected_total` is 0 when nothing is known about the size.
+    pub fn begin(&self, expected_total: usize) {
+        self.files.store(0, Ordering::Relaxed);
+        self.dirs.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.permission_denied.store(0, Ordering::Relaxed);
+        self.error_samples.lock().clear();
+        self.expected_total.store(expected_total, Ordering::Relaxed);
+
+        *self.timing.lock() = ScanTiming {
+            started: Some(Instant::now()),
+            finished: None,
+        };
+    }
+
+    pub fn finish(&self) {
+        let mut timing = self.timing.lock();
+        if let Some(started) = timing.started {
+            timing.finished.get_or_insert(started.elapsed());
+        }
+    }
+
+    pub fn record_entry(&self, is_dir: bool, size: Option<u64>) {
+        if is_dir {
+            self.dirs.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.files.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(size) = size {
+            self.bytes.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_error(&self, path: Option<&Path>, permission_denied: bool) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        if permission_denied {
+            self.permission_denied.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if let Some(path) = path {
+            let mut samples = self.error_samples.lock();
+            if samples.len() < MAX_ERROR_SAMPLES {
+                samples.push(path.display().to_string());
+            }
+        }
+    }
+
+    /// Records one filesystem event handled by the watcher.
+    pub fn record_watch_event(&self) {
+        let mut events = self.events.lock();
+        let now = Instant::now();
+        let started = *events.started.get_or_insert(now);
+        events.count += 1;
+
+        let elapsed = now.duration_since(started);
+        if elapsed >= EVENT_RATE_WINDOW {
+            events.rate = events.count as f64 / elapsed.as_secs_f64();
+            events.count = 0;
+            events.started = Some(now);
+        }
+    }
+
+    fn watch_events_per_sec(&self) -> f64 {
+        let events = self.events.lock();
+        match events.started {
+            // A window that never closed means the watcher went quiet
+            Some(started) if started.elapsed() > EVENT_RATE_WINDOW * 2 => 0.0,
+            Some(_) => events.rate,
+            None => 0.0,
+        }
+    }
+
+    pub fn snapshot(&self, index_memory_bytes: usize) -> IndexStats {
+        let files_scanned = self.files.load(Ordering::Relaxed);
+        let dirs_scanned = self.dirs.load(Ordering::Relaxed);
+
+        let (elapsed, running) = {
+            let timing = self.timing.lock();
+            match (timing.started, timing.finished) {
+                (_, Some(finished)) => (finished, false),
+                (Some(started), None) => (started.elapsed(), true),
+                (None, None) => (Duration::ZERO, false),
+            }
+        };
+
+        let processed = files_scanned + dirs_scanned;
+        let expected = self.expected_total.load(Ordering::Relaxed);
+        let estimated_remaining_ms = (running && processed > 0 && expected > processed).then(|| {
+            let per_entry = elapsed.as_secs_f64() / processed as f64;
+            (per_entry * (expected - processed) as f64 * 1000.0) as u64
+        });
+
+        IndexStats {
+            files_scanned,
+            dirs_scanned,
+            bytes_scanned: self.bytes.load(Ordering::Relaxed),
+            error_count: self.errors.load(Ordering::Relaxed),
+            permission_denied_count: self.permission_denied.load(Ordering::Relaxed),
+            error_samples: self.error_samples.lock().clone(),
+            elapsed_ms: elapsed.as_millis() as u64,
+            estimated_remaining_ms,
+            watch_events_per_sec: self.watch_events_per_sec(),
+            index_memory_bytes,
+        }
+    }
+}
+
+/// Approximate heap and map cost of one index entry.
+pub fn estimated_entry_size(path: &Path, entry: &IndexEntry) -> usize {
+    std::mem::size_of::<PathBuf>()
+        + path.as_os_str().len()
+        + std::mem::size_of::<IndexEntry>()
+        + entry.name.len()
+        + entry.name_lower.len()
+        + entry.extension.as_ref().map_or(0, |e| e.len())
+        + MAP_ENTRY_OVERHEAD
+}
@@ -1,25 +1,90 @@
-use super::entry::IndexEntry;
-use super::IndexManager;
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::PathBuf;
+use super::entry::{IndexEntry, WatchMode};
+use super::exclusions::ExclusionMatcher;
+use super::state::IndexerState;
+use super::{scanner, IndexManager};
+use crate::fs::{list_directory, mounts, AppError, FileEntry, SavedSearch, WatcherSettings};
+use crate::search::saved;
+use crate::tags::TagStore;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{
+    Config, Event, EventHandler, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode,
+    Watcher,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
 
 const DEBOUNCE_MS: u64 = 300;
+const EVENT_CHANNEL_SIZE: usize = 1000;
+const DIR_DEBOUNCE_MS: u64 = 250;
+const SEARCH_POLL_MS: u64 = 1000;
 
+/// Owns the notify watchers and their event loop; dropping or stopping it ends both.
 pub struct FileWatcher {
-    watcher: Option<RecommendedWatcher>,
-    #[allow(dead_code)]
+    watchers: Vec<Box<dyn Watcher + Send>>,
     stop_tx: Option<mpsc::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+/// Directories whose events were dropped because the channel was full.
+struct Overflow {
+    flag: AtomicBool,
+    paths: Mutex<HashSet<PathBuf>>,
+    roots: Vec<PathBuf>,
+}
+
+impl Overflow {
+    fn new(roots: Vec<PathBuf>) -> Self {
+        Self {
+            flag: AtomicBool::new(false),
+            paths: Mutex::new(HashSet::new()),
+            roots,
+        }
+    }
+
+    /// Records the folder holding each path, or the path itself for a root, so that
+    /// nothing outside the roots is reconciled.
+    fn record(&self, event: &Event) {
+        let mut paths = self.paths.lock();
+        for path in &event.paths {
+            let dir = match path.parent() {
+                Some(parent) if self.roots.iter().any(|root| parent.starts_with(root)) => parent,
+                _ => path,
+            };
+            paths.insert(dir.to_path_buf());
+        }
+        self.flag.store(true, Ordering::Release);
+    }
+
+    fn take(&self) -> Option<HashSet<PathBuf>> {
+        if !self.flag.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        Some(std::mem::take(&mut *self.paths.lock()))
+    }
+}
+
+struct WatchContext {
+    manager: Arc<IndexManager>,
+    exclusions: Arc<ExclusionMatcher>,
+    roots: Vec<PathBuf>,
+    app: AppHandle,
 }
 
 impl FileWatcher {
     pub fn new() -> Self {
         Self {
-            watcher: None,
+            watchers: Vec::new(),
             stop_tx: None,
+            task: None,
         }
     }
 
@@ -27,103 +92,497 @@ impl FileWatcher {
         &mut self,
         manager: Arc<IndexManager>,
         roots: Vec<PathBuf>,
+        exclusions: Arc<ExclusionMatcher>,
+        settings: WatcherSettings,
         app: AppHandle,
     ) -> Result<(), notify::Error> {
-        let manager_clone = manager.clone();
-        let app_clone = app.clone();
+        // Restarting replaces the previous watcher instead of running alongside it
+        self.stop();
 
-        let (tx, mut rx) = mpsc::channel::<Event>(1000);
+        let (tx, mut rx) = mpsc::channel::<Event>(EVENT_CHANNEL_SIZE);
+        let overflow = Arc::new(Overflow::new(roots.clone()));
 
-        let watcher_tx = tx.clone();
-        let mut watcher = RecommendedWatcher::new(
-            move |res: Result<Event, notify::Error>| {
-                if let Ok(event) = res {
-                    let _ = watcher_tx.blocking_send(event);
+        let watcher_overflow = overflow.clone();
+        let handler = move |res: Result<Event, notify::Error>| {
+            if let Ok(event) = res {
+                // Never block the notify thread; remember what was lost instead
+                if let Err(TrySendError::Full(event)) = tx.try_send(event) {
+                    watcher_overflow.record(&event);
                 }
-            },
-            Config::default().with_poll_interval(Duration::from_millis(DEBOUNCE_MS)),
-        )?;
+            }
+        };
 
         for root in &roots {
-            if root.exists() {
-                let _ = watcher.watch(root, RecursiveMode::Recursive);
-            }
+            let mode = match create_watcher(root, handler.clone(), &settings) {
+                Ok((mut watcher, mode)) if root.exists() => {
+                    match watcher.watch(root, RecursiveMode::Recursive) {
+                        Ok(()) => {
+                            self.watchers.push(watcher);
+                            mode
+                        }
+                        Err(_) => WatchMode::Unwatched,
+                    }
+                }
+                _ => WatchMode::Unwatched,
+            };
+            manager.set_root_watch_mode(root, mode);
         }
-
-        self.watcher = Some(watcher);
+        manager.emit_progress(&app, None);
 
         let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
         self.stop_tx = Some(stop_tx);
 
-        tokio::spawn(async move {
+        let ctx = Arc::new(WatchContext {
+            manager,
+            exclusions,
+            roots,
+            app,
+        });
+
+        self.task = Some(tokio::spawn(async move {
             loop {
                 tokio::select! {
-                    Some(event) = rx.recv() => {
-                        handle_fs_event(&manager_clone, &app_clone, event).await;
+                    event = rx.recv() => {
+                        let Some(event) = event else { break };
+                        ctx.manager.stats().record_watch_event();
+                        handle_fs_event(&ctx, event).await;
+
+                        if let Some(lost) = overflow.take() {
+                            reconcile_lost(&ctx, lost).await;
+                        }
                     }
                     _ = stop_rx.recv() => {
                         break;
                     }
                 }
             }
-        });
+        }));
 
         Ok(())
     }
 
     pub fn stop(&mut self) {
-        self.watcher = None;
-        self.stop_tx = None;
-    }
-}
-
-async fn handle_fs_event(manager: &IndexManager, app: &AppHandle, event: Event) {
-    use notify::EventKind;
-
-    for path in event.paths {
-        match event.kind {
-            EventKind::Create(_) | EventKind::Modify(_) => {
-                if let Ok(metadata) = std::fs::metadata(&path) {
-                    let file_name = path
-                        .file_name()
-                        .map(|n| n.to_string_lossy().to_string())
-                        .unwrap_or_default();
-
-                    let extension = if metadata.is_file() {
-                        path.extension().map(|e| e.to_string_lossy().to_string())
-                    } else {
-                        None
-                    };
-
-                    let modified = metadata
-                        .modified()
-                        .ok()
-                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                        .map(|d| d.as_secs() as i64);
-
-                    let size = if metadata.is_file() {
-                        Some(metadata.len())
-                    } else {
-                        None
-                    };
-
-                    let entry = IndexEntry::new(
-                        file_name,
-                        extension,
-                        metadata.is_dir(),
-                        modified,
-                        size,
-                    );
-
-                    manager.insert(path, entry);
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.try_send(());
+        }
+        // Dropping the watchers unwatches all roots and closes the event channel
+        self.watchers.clear();
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Creates a watcher for `path`, polling where native notifications are unreliable.
+fn create_watcher<F>(
+    path: &Path,
+    handler: F,
+    settings: &WatcherSettings,
+) -> Result<(Box<dyn Watcher + Send>, WatchMode), notify::Error>
+where
+    F: EventHandler,
+{
+    if settings.force_polling || mounts::native_watch_unreliable(path) {
+        let interval = Duration::from_secs(settings.poll_interval_secs.max(1));
+        let watcher = PollWatcher::new(handler, Config::default().with_poll_interval(interval))?;
+        Ok((Box::new(watcher), WatchMode::Polling))
+    } else {
+        let config = Config::default().with_poll_interval(Duration::from_millis(DEBOUNCE_MS));
+        Ok((Box::new(RecommendedWatcher::new(handler, config)?), WatchMode::Native))
+    }
+}
+
+async fn handle_fs_event(ctx: &Arc<WatchContext>, event: Event) {
+    // The backend lost track (e.g. inotify queue overflow); rebuild what it points at
+    if event.need_rescan() {
+        let paths = if event.paths.is_empty() {
+            ctx.roots.clone()
+        } else {
+            event.paths.clone()
+        };
+        reconcile(ctx, paths).await;
+        ctx.manager.emit_progress(&ctx.app, None);
+        return;
+    }
+
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(mode)) => match mode {
+            RenameMode::From => {
+                for path in &event.paths {
+                    ctx.manager.remove_subtree(path);
                 }
             }
-            EventKind::Remove(_) => {
-                manager.remove(&path);
+            RenameMode::To => {
+                reconcile(ctx, event.paths).await;
+            }
+            RenameMode::Both => {
+                if let [from, to] = event.paths.as_slice() {
+                    ctx.manager.remove_subtree(from);
+                    reconcile(ctx, vec![to.clone()]).await;
+                }
+            }
+            _ => {
+                // Without a direction, what still exists is the new name
+                let (present, gone): (Vec<PathBuf>, Vec<PathBuf>) = event
+                    .paths
+                    .into_iter()
+                    .partition(|p| std::fs::symlink_metadata(p).is_ok());
+                for path in &gone {
+                    ctx.manager.remove_subtree(path);
+                }
+                reconcile(ctx, present).await;
+            }
+        },
+        EventKind::Create(_) => {
+            // A new directory may already have contents by the time it is watched
+            let (dirs, files): (Vec<PathBuf>, Vec<PathBuf>) =
+                event.paths.into_iter().partition(|p| p.is_dir());
+            for path in &files {
+                index_path(ctx, path);
+            }
+            reconcile(ctx, dirs).await;
+        }
+        EventKind::Modify(_) => {
+            for path in &event.paths {
+                index_path(ctx, path);
+            }
+        }
+        EventKind::Remove(_) => {
+            for path in &event.paths {
+                ctx.manager.remove_subtree(path);
             }
-            _ => {}
         }
+        _ => {}
+    }
+
+    ctx.manager.emit_progress(&ctx.app, None);
+}
+
+/// Reconciles the directories whose events were dropped, or all roots when unknown.
+async fn reconcile_lost(ctx: &Arc<WatchContext>, lost: HashSet<PathBuf>) {
+    let paths = if lost.is_empty() {
+        ctx.roots.clone()
+    } else {
+        // Nested paths are covered by their ancestors
+        lost.iter()
+            .filter(|p| !lost.iter().any(|other| other != *p && p.starts_with(other)))
+            .cloned()
+            .collect()
+    };
+
+    reconcile(ctx, paths).await;
+    ctx.manager.emit_progress(&ctx.app, None);
+}
+
+async fn reconcile(ctx: &Arc<WatchContext>, paths: Vec<PathBuf>) {
+    if paths.is_empty() {
+        return;
+    }
+
+    let ctx = ctx.clone();
+    let _ = tokio::task::spawn_blocking(move || {
+        for path in paths {
+            scanner::reconcile_subtree(&ctx.manager, &ctx.exclusions, &path);
+        }
+    })
+    .await;
+}
+
+/// Updates the entry for a single path without descending into it.
+fn index_path(ctx: &WatchContext, path: &Path) {
+    let metadata = match std::fs::metadata(path) {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    if ctx.exclusions.is_excluded(path, metadata.is_dir()) {
+        return;
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let extension = if metadata.is_file() {
+        path.extension().map(|e| e.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+
+    let size = if metadata.is_file() {
+        Some(metadata.len())
+    } else {
+        None
+    };
+
+    if let Some(size) = size {
+        ctx.manager.content().index_file(path, modified, size);
+    }
+
+    let entry = IndexEntry::new(file_name, extension, metadata.is_dir(), modified, size);
+
+    ctx.manager.insert(path.to_path_buf(), entry);
+}
+
+/// A pane folder's entries with their tags, as sent to the pane.
+fn tagged_listing(app: &AppHandle, path: &Path) -> Result<Vec<FileEntry>, AppError> {
+    let mut entries = list_directory(path)?;
+    app.state::<TagStore>().annotate(&mut entries);
+    Ok(entries)
+}
+
+/// Payload of `dir:changed`, describing how a watched pane folder changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryChange {
+    pub pane_id: String,
+    pub path: String,
+    pub added: Vec<FileEntry>,
+    pub removed: Vec<FileEntry>,
+    pub modified: Vec<FileEntry>,
+}
+
+struct PaneWatch {
+    _watchers: Vec<Box<dyn Watcher + Send>>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for PaneWatch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Non-recursive watchers for the folders shown in panes, keyed by pane id.
+pub struct DirectoryWatcher {
+    panes: Mutex<HashMap<String, PaneWatch>>,
+}
+
+impl Default for DirectoryWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectoryWatcher {
+    pub fn new() -> Self {
+        Self {
+            panes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts watching `path` for a pane, replacing whatever that pane watched before.
+    pub fn watch(
+        &self,
+        pane_id: String,
+        path: PathBuf,
+        settings: &WatcherSettings,
+        app: AppHandle,
+    ) -> Result<(), AppError> {
+        // A single slot is enough: it only signals that the listing is stale
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+        let (mut watcher, _) = create_watcher(
+            &path,
+            move |res: Result<Event, notify::Error>| {
+                if res.is_ok() {
+                    let _ = tx.try_send(());
+                }
+            },
+            settings,
+        )
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+        watcher
+            .watch(&path, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Io(e.to_string()))?;
+        // Listed once the watch is in place, so no change goes unreported
+        let snapshot = tagged_listing(&app, &path)?;
+
+        let task_pane_id = pane_id.clone();
+        let task = tokio::spawn(async move {
+            let mut previous = snapshot_map(snapshot);
+
+            while rx.recv().await.is_some() {
+                tokio::time::sleep(Duration::from_millis(DIR_DEBOUNCE_MS)).await;
+                while rx.try_recv().is_ok() {}
+
+                let (list_app, list_path) = (app.clone(), path.clone());
+                let current = match tokio::task::spawn_blocking(move || {
+                    tagged_listing(&list_app, &list_path)
+                })
+                .await
+                {
+                    Ok(Ok(entries)) => snapshot_map(entries),
+                    // The folder itself went away; report its contents as removed
+                    Ok(Err(AppError::NotFound(_))) => HashMap::new(),
+                    _ => continue,
+                };
+
+                if let Some(change) = diff_snapshots(&task_pane_id, &path, &previous, &current) {
+                    let _ = app.emit("dir:changed", change);
+                }
+                previous = current;
+            }
+        });
+
+        self.panes.lock().insert(
+            pane_id,
+            PaneWatch {
+                _watchers: vec![watcher],
+                task,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Keeps a pane showing `search://{name}` up to date. Searches served from the index
+    /// re-run whenever it changes; others watch their roots recursively.
+    pub fn watch_search(
+        &self,
+        pane_id: String,
+        search: SavedSearch,
+        settings: &WatcherSettings,
+        app: AppHandle,
+    ) -> Result<(), AppError> {
+        let source = saved::Source::resolve(&search, &app.state::<IndexerState>())?;
+
+        let (tx, mut rx) = mpsc::channel::<()>(1);
+        let mut watchers = Vec::new();
+        if let saved::Source::Walk(roots) = &source {
+            for root in roots.iter().filter(|r| r.is_dir()) {
+                let tx = tx.clone();
+                let (mut watcher, _) = create_watcher(
+                    root,
+                    move |res: Result<Event, notify::Error>| {
+                        if res.is_ok() {
+                            let _ = tx.try_send(());
+                        }
+                    },
+                    settings,
+                )
+                .map_err(|e| AppError::Io(e.to_string()))?;
+
+                watcher
+                    .watch(root, RecursiveMode::Recursive)
+                    .map_err(|e| AppError::Io(e.to_string()))?;
+                watchers.push(watcher);
+            }
+        }
+        drop(tx);
+        let snapshot = saved::run(&search, &source, &app.state::<TagStore>());
+
+        let path = PathBuf::from(format!("{}{}", saved::SCHEME, search.name));
+        let task_pane_id = pane_id.clone();
+        let query = Arc::new((search, source));
+        let task = tokio::spawn(async move {
+            let mut previous = snapshot_map(snapshot);
+            let mut index_changes = query.1.index_changes();
+
+            loop {
+                match &query.1 {
+                    saved::Source::Walk(_) => {
+                        if rx.recv().await.is_none() {
+                            break;
+                        }
+                        tokio::time::sleep(Duration::from_millis(DIR_DEBOUNCE_MS)).await;
+                        while rx.try_recv().is_ok() {}
+                    }
+                    // The index watcher already follows these roots
+                    saved::Source::Index(_) => {
+                        tokio::time::sleep(Duration::from_millis(SEARCH_POLL_MS)).await;
+                        let changes = query.1.index_changes();
+                        if changes == index_changes {
+                            continue;
+                        }
+                        index_changes = changes;
+                    }
+                }
+
+                let (run_query, run_app) = (query.clone(), app.clone());
+                let current = match tokio::task::spawn_blocking(move || {
+                    saved::run(&run_query.0, &run_query.1, &run_app.state::<TagStore>())
+                })
+                .await
+                {
+                    Ok(entries) => snapshot_map(entries),
+                    Err(_) => continue,
+                };
+
+                if let Some(change) = diff_snapshots(&task_pane_id, &path, &previous, &current) {
+                    let _ = app.emit("dir:changed", change);
+                }
+                previous = current;
+            }
+        });
+
+        self.panes.lock().insert(
+            pane_id,
+            PaneWatch {
+                _watchers: watchers,
+                task,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn unwatch(&self, pane_id: &str) {
+        self.panes.lock().remove(pane_id);
+    }
+}
+
+fn snapshot_map(entries: Vec<FileEntry>) -> HashMap<String, FileEntry> {
+    entries.into_iter().map(|e| (e.path.clone(), e)).collect()
+}
+
+fn diff_snapshots(
+    pane_id: &str,
+    path: &Path,
+    previous: &HashMap<String, FileEntry>,
+    current: &HashMap<String, FileEntry>,
+) -> Option<DirectoryChange> {
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+
+    for (key, entry) in current {
+        match previous.get(key) {
+            None => added.push(entry.clone()),
+            Some(old) => {
+                if old.size != entry.size || old.modified != entry.modified || old.is_dir != entry.is_dir {
+                    modified.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    let removed: Vec<FileEntry> = previous
+        .iter()
+        .filter(|(key, _)| !current.contains_key(*key))
+        .map(|(_, entry)| entry.clone())
+        .collect();
+
+    if added.is_empty() && removed.is_empty() && modified.is_empty() {
+        return None;
     }
 
-    manager.emit_progress(app, None);
+    Some(DirectoryChange {
+        pane_id: pane_id.to_string(),
+        path: path.display().to_string(),
+        added,
+        removed,
+        modified,
+    })
 }
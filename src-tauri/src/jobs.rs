@@ -0,0 +1,257 @@
+use crate::fs::AppError;
+use crate::journal::Journal;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Progress events are throttled so that copying many small files does not flood the UI.
+const EMIT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// State of a long-running operation, emitted as `job:progress` while it runs and as
+/// `job:done` once it has completed, failed or been cancelled.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress {
+    pub job_id: String,
+    /// What the job does, e.g. `create_archive`.
+    pub kind: String,
+    pub status: JobStatus,
+    /// Zero while the total is unknown.
+    pub total_bytes: u64,
+    pub processed_bytes: u64,
+    /// Zero while the total is unknown.
+    pub total_items: usize,
+    pub processed_items: usize,
+    pub current_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Handle a running job uses to report progress and notice cancellation.
+pub struct Job {
+    cancel: AtomicBool,
+    progress: Mutex<JobProgress>,
+    last_emit: Mutex<Instant>,
+    /// Set while the job copies or moves as part of an operation that can be resumed.
+    journal: Mutex<Option<Arc<Journal>>>,
+    app: AppHandle,
+}
+
+impl Job {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            Err(AppError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_totals(&self, items: usize, bytes: u64) {
+        let mut progress = self.progress.lock();
+        progress.total_items = items;
+        progress.total_bytes = bytes;
+    }
+
+    pub fn start_item(&self, path: &Path) {
+        self.progress.lock().current_path = Some(path.display().to_string());
+        self.emit(false);
+    }
+
+    pub fn finish_item(&self) {
+        self.progress.lock().processed_items += 1;
+        self.emit(false);
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.progress.lock().processed_bytes += bytes;
+        self.emit(false);
+    }
+
+    pub fn journal(&self) -> Option<Arc<Journal>> {
+        self.journal.lock().clone()
+    }
+
+    pub fn set_journal(&self, journal: Option<Arc<Journal>>) {
+        *self.journal.lock() = journal;
+    }
+
+    fn snapshot(&self) -> JobProgress {
+        self.progress.lock().clone()
+    }
+
+    fn emit(&self, force: bool) {
+        {
+            let mut last_emit = self.last_emit.lock();
+            if !force && last_emit.elapsed() < EMIT_INTERVAL {
+                return;
+            }
+            *last_emit = Instant::now();
+        }
+        let _ = self.app.emit("job:progress", self.snapshot());
+    }
+}
+
+/// Tracks running jobs so they can be listed and cancelled by id.
+pub struct JobRegistry {
+    active: Arc<Mutex<HashMap<String, Arc<Job>>>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self {
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn register(&self, app: &AppHandle, kind: &str) -> Arc<Job> {
+        let job_id = uuid::Uuid::new_v4().to_string();
+        let job = Arc::new(Job {
+            cancel: AtomicBool::new(false),
+            progress: Mutex::new(JobProgress {
+                job_id: job_id.clone(),
+                kind: kind.to_string(),
+                status: JobStatus::Running,
+                total_bytes: 0,
+                processed_bytes: 0,
+                total_items: 0,
+                processed_items: 0,
+                current_path: None,
+                error: None,
+            }),
+            last_emit: Mutex::new(Instant::now()),
+            journal: Mutex::new(None),
+            app: app.clone(),
+        });
+        self.active.lock().insert(job_id, job.clone());
+        job
+    }
+
+    /// Runs `work` on a blocking thread and returns the job's id right away.
+    pub fn spawn<F>(&self, app: &AppHandle, kind: &str, work: F) -> String
+    where
+        F: FnOnce(&Job) -> Result<(), AppError> + Send + 'static,
+    {
+        let job = self.register(app, kind);
+        let job_id = job.snapshot().job_id;
+
+        let active = self.active.clone();
+        tokio::task::spawn_blocking(move || {
+            job.emit(true);
+            let result = work(&job);
+            finish(&active, &job, result.err().map(|e| e.to_string()));
+        });
+
+        job_id
+    }
+
+    /// Runs `work` as a job like `spawn`, but waits for it, for commands that return its
+    /// result themselves.
+    pub async fn run<F, T>(&self, app: &AppHandle, kind: &str, work: F) -> Result<T, AppError>
+    where
+        F: FnOnce(&Job) -> Result<T, AppError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let job = self.register(app, kind);
+
+        let active = self.active.clone();
+        tokio::task::spawn_blocking(move || {
+            job.emit(true);
+            let result = work(&job);
+            finish(&active, &job, result.as_ref().err().map(|e| e.to_string()));
+            match result {
+                Err(_) if job.is_cancelled() => Err(AppError::Cancelled),
+                result => result,
+            }
+        })
+        .await
+        .map_err(|e| AppError::Io(e.to_string()))?
+    }
+
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.active.lock().get(job_id) {
+            Some(job) => {
+                job.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<JobProgress> {
+        self.active
+            .lock()
+            .values()
+            .map(|job| job.snapshot())
+            .collect()
+    }
+}
+
+/// Emits `job:done` with how the job ended and forgets it.
+fn finish(active: &Mutex<HashMap<String, Arc<Job>>>, job: &Job, error: Option<String>) {
+    let mut progress = job.snapshot();
+    progress.current_path = None;
+    match error {
+        // Cancellation can surface as any error from the reader that noticed it
+        _ if job.is_cancelled() => progress.status = JobStatus::Cancelled,
+        None => progress.status = JobStatus::Completed,
+        Some(e) => {
+            progress.status = JobStatus::Failed;
+            progress.error = Some(e);
+        }
+    }
+
+    active.lock().remove(&progress.job_id);
+    let _ = job.app.emit("job:done", progress);
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts the bytes read towards a job's progress and stops reading once it is cancelled.
+/// Without a job it reads straight through.
+pub struct JobReader<'a, R> {
+    inner: R,
+    job: Option<&'a Job>,
+}
+
+impl<'a, R: Read> JobReader<'a, R> {
+    pub fn new(inner: R, job: Option<&'a Job>) -> Self {
+        Self { inner, job }
+    }
+}
+
+impl<R: Read> Read for JobReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let job = match self.job {
+            Some(job) => job,
+            None => return self.inner.read(buf),
+        };
+        if job.is_cancelled() {
+            return Err(io::Error::other(AppError::Cancelled.to_string()));
+        }
+
+        let read = self.inner.read(buf)?;
+        job.add_bytes(read as u64);
+        Ok(read)
+    }
+}
@@ -0,0 +1,498 @@
+//! Journals of copies and moves in progress, so that one interrupted by a crash or
+//! cancelled can pick up where it stopped instead of starting over when it is resumed.
+//!
+//! Each operation appends to its own file: a header describing the operation, then a
+//! line for every item it places and every file it completes. The file is removed once
+//! the operation succeeds.
+
+use crate::fs::{AppError, ConflictPolicy};
+use crate::queue::OperationKind;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const JOURNAL_DIR_NAME: &str = "journals";
+const JOURNAL_EXTENSION: &str = "jsonl";
+
+/// First line of a journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Header {
+    kind: OperationKind,
+    sources: Vec<String>,
+    dest: String,
+    conflict: ConflictPolicy,
+}
+
+impl Header {
+    /// Whether both describe the same operation. Targets an earlier run picked under
+    /// another conflict policy do not carry over.
+    fn is_same(&self, other: &Header) -> bool {
+        self.kind == other.kind
+            && self.sources == other.sources
+            && self.dest == other.dest
+            && self.conflict == other.conflict
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+enum Entry {
+    /// Where a source went, so that resuming does not pick a new name for it.
+    Target { source: String, target: String },
+    /// A file written in full, with the size and modification time its source had.
+    Done {
+        path: String,
+        bytes: u64,
+        #[serde(default)]
+        modified: Option<i64>,
+    },
+    /// A source moved in full.
+    Moved { source: String },
+    /// The operation failed or was cancelled.
+    Stopped { error: String },
+    /// The operation was started again.
+    Resumed,
+}
+
+/// An operation with a journal that has not run to the end.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptedOperation {
+    pub id: String,
+    pub kind: OperationKind,
+    pub sources: Vec<String>,
+    pub dest: String,
+    pub conflict: ConflictPolicy,
+    pub completed_items: usize,
+    pub completed_bytes: u64,
+    /// Why it stopped if it failed or was cancelled, or `None` if the app exited while
+    /// it ran.
+    pub error: Option<String>,
+}
+
+/// What a journal records, replayed from its file.
+#[derive(Debug, Default)]
+struct Progress {
+    targets: HashMap<String, String>,
+    done: HashMap<String, (u64, Option<i64>)>,
+    moved: HashSet<String>,
+    error: Option<String>,
+}
+
+impl Progress {
+    fn apply(&mut self, entry: Entry) {
+        match entry {
+            Entry::Target { source, target } => {
+                self.targets.insert(source, target);
+            }
+            Entry::Done {
+                path,
+                bytes,
+                modified,
+            } => {
+                self.done.insert(path, (bytes, modified));
+            }
+            Entry::Moved { source } => {
+                self.moved.insert(source);
+            }
+            Entry::Stopped { error } => self.error = Some(error),
+            Entry::Resumed => self.error = None,
+        }
+    }
+
+    fn completed_bytes(&self) -> u64 {
+        self.done.values().map(|(bytes, _)| bytes).sum()
+    }
+}
+
+/// The journal of one running operation. Writing to it is best effort: an operation
+/// whose journal cannot be written still runs, it just cannot be resumed.
+pub struct Journal {
+    id: String,
+    path: Option<PathBuf>,
+    resumed: bool,
+    state: Mutex<(Option<File>, Progress)>,
+    open: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Journal {
+    /// Whether the operation ran before, so the destination may hold its partial files.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Where an earlier attempt put `source`.
+    pub fn target(&self, source: &str) -> Option<String> {
+        self.state.lock().1.targets.get(source).cloned()
+    }
+
+    pub fn set_target(&self, source: &str, target: &str) {
+        self.record(Entry::Target {
+            source: source.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    /// Whether `path` was completed from a source of this size and modification time,
+    /// so that one changed since is copied again.
+    pub fn is_completed(&self, path: &str, bytes: u64, modified: Option<i64>) -> bool {
+        self.state.lock().1.done.get(path) == Some(&(bytes, modified))
+    }
+
+    /// Notes `path` as written in full from a source of this size and modification
+    /// time. The file must be synced to disk first, so a crash cannot leave it short.
+    pub fn complete(&self, path: &str, bytes: u64, modified: Option<i64>) {
+        self.record(Entry::Done {
+            path: path.to_string(),
+            bytes,
+            modified,
+        });
+    }
+
+    pub fn is_moved(&self, source: &str) -> bool {
+        self.state.lock().1.moved.contains(source)
+    }
+
+    pub fn set_moved(&self, source: &str) {
+        self.record(Entry::Moved {
+            source: source.to_string(),
+        });
+    }
+
+    /// Bytes of the files completed so far, which are already at the destination.
+    pub fn completed_bytes(&self) -> u64 {
+        self.state.lock().1.completed_bytes()
+    }
+
+    /// Removes the journal once the operation succeeded, or keeps it for resuming.
+    pub fn end(&self, result: &Result<(), AppError>) {
+        match result {
+            Ok(()) => {
+                let mut state = self.state.lock();
+                state.0 = None;
+                if let Some(path) = &self.path {
+                    let _ = fs::remove_file(path);
+                }
+            }
+            Err(e) => self.record(Entry::Stopped {
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    fn record(&self, entry: Entry) {
+        let mut state = self.state.lock();
+        if let Some(file) = &mut state.0 {
+            if let Ok(mut line) = serde_json::to_vec(&entry) {
+                line.push(b'\n');
+                // One write per line, so a crash leaves at most the last line cut short
+                let _ = file.write_all(&line);
+            }
+        }
+        state.1.apply(entry);
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        self.open.lock().remove(&self.id);
+    }
+}
+
+/// Keeps track of journals on disk and which of them running operations hold.
+pub struct Journals {
+    open: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Journals {
+    pub fn new() -> Self {
+        Self {
+            open: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Opens the journal for an operation. With `resume` it continues the journal with
+    /// that id, if it is of the same operation and not in use, so that the operation
+    /// skips what the interrupted run did; otherwise it starts a new one.
+    pub fn begin(
+        &self,
+        kind: OperationKind,
+        sources: &[String],
+        dest: &str,
+        conflict: ConflictPolicy,
+        resume: Option<&str>,
+    ) -> Arc<Journal> {
+        let header = Header {
+            kind,
+            sources: sources.to_vec(),
+            dest: dest.to_string(),
+            conflict,
+        };
+
+        let mut open = self.open.lock();
+        let earlier = resume.and_then(|resume| {
+            journal_files().into_iter().find(|(id, path)| {
+                id == resume
+                    && !open.contains(id)
+                    && read_header(path).is_some_and(|h| h.is_same(&header))
+            })
+        });
+
+        let journal = match earlier {
+            Some((id, path)) => {
+                let progress = read_journal(&path).map(|(_, p)| p).unwrap_or_default();
+                let file = OpenOptions::new().append(true).open(&path).ok();
+                let journal = Journal {
+                    id,
+                    path: Some(path),
+                    resumed: true,
+                    state: Mutex::new((file, progress)),
+                    open: self.open.clone(),
+                };
+                journal.record(Entry::Resumed);
+                journal
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                let path =
+                    journal_dir().map(|dir| dir.join(format!("{}.{}", id, JOURNAL_EXTENSION)));
+                let file = path.as_ref().and_then(|path| create_journal(path, &header));
+                Journal {
+                    id,
+                    path,
+                    resumed: false,
+                    state: Mutex::new((file, Progress::default())),
+                    open: self.open.clone(),
+                }
+            }
+        };
+        open.insert(journal.id.clone());
+        Arc::new(journal)
+    }
+
+    /// Operations whose journal is left over and not held by a running operation.
+    pub fn interrupted(&self) -> Vec<InterruptedOperation> {
+        let open = self.open.lock();
+        journal_files()
+            .into_iter()
+            .filter(|(id, _)| !open.contains(id))
+            .filter_map(|(id, path)| {
+                let (header, progress) = read_journal(&path)?;
+                Some(InterruptedOperation {
+                    id,
+                    kind: header.kind,
+                    sources: header.sources,
+                    dest: header.dest,
+                    conflict: header.conflict,
+                    completed_items: progress.done.len(),
+                    completed_bytes: progress.completed_bytes(),
+                    error: progress.error,
+                })
+            })
+            .collect()
+    }
+
+    /// Forgets an interrupted operation. Whatever it already wrote stays.
+    pub fn discard(&self, id: &str) -> Result<(), AppError> {
+        if self.open.lock().contains(id) {
+            return Err(AppError::InvalidOperation(
+                "The operation is running".into(),
+            ));
+        }
+        let path = journal_files()
+            .into_iter()
+            .find(|(journal_id, _)| journal_id == id)
+            .map(|(_, path)| path)
+            .ok_or_else(|| AppError::NotFound(format!("Interrupted operation {}", id)))?;
+        fs::remove_file(&path).map_err(|e| AppError::Io(e.to_string()))
+    }
+}
+
+impl Default for Journals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn create_journal(path: &PathBuf, header: &Header) -> Option<File> {
+    fs::create_dir_all(path.parent()?).ok()?;
+    let mut line = serde_json::to_vec(header).ok()?;
+    line.push(b'\n');
+    let mut file = File::create(path).ok()?;
+    file.write_all(&line).ok()?;
+    Some(file)
+}
+
+fn read_header(path: &PathBuf) -> Option<Header> {
+    let mut line = String::new();
+    BufReader::new(File::open(path).ok()?)
+        .read_line(&mut line)
+        .ok()?;
+    serde_json::from_str(&line).ok()
+}
+
+/// Replays a journal. A last line cut short by a crash is ignored.
+fn read_journal(path: &PathBuf) -> Option<(Header, Progress)> {
+    let mut lines = BufReader::new(File::open(path).ok()?).lines();
+    let header: Header = serde_json::from_str(&lines.next()?.ok()?).ok()?;
+    let mut progress = Progress::default();
+    for line in lines.map_while(Result::ok) {
+        if let Ok(entry) = serde_json::from_str(&line) {
+            progress.apply(entry);
+        }
+    }
+    Some((header, progress))
+}
+
+/// Journal files by id, oldest first.
+fn journal_files() -> Vec<(String, PathBuf)> {
+    let Some(entries) = journal_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(JOURNAL_EXTENSION) {
+                return None;
+            }
+            let id = path.file_stem()?.to_string_lossy().to_string();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            Some((modified, id, path))
+        })
+        .collect();
+    files.sort_by_key(|file| file.0);
+    files.into_iter().map(|(_, id, path)| (id, path)).collect()
+}
+
+fn journal_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("filemanager").join(JOURNAL_DIR_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(conflict: ConflictPolicy) -> Header {
+        Header {
+            kind: OperationKind::Copy,
+            sources: vec!["/a".into(), "/b".into()],
+            dest: "/dest".into(),
+            conflict,
+        }
+    }
+
+    fn journal_at(path: &PathBuf) -> Journal {
+        Journal {
+            id: "test".into(),
+            path: Some(path.clone()),
+            resumed: false,
+            state: Mutex::new((
+                create_journal(path, &header(ConflictPolicy::Skip)),
+                Progress::default(),
+            )),
+            open: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = journal_at(&path);
+        journal.set_target("/a", "/dest/a (1)");
+        journal.complete("/dest/a (1)/x", 10, Some(1_700_000_000));
+        journal.complete("/dest/b", 5, None);
+        journal.set_moved("/b");
+
+        let (recorded, progress) = read_journal(&path).unwrap();
+        assert!(recorded.is_same(&header(ConflictPolicy::Skip)));
+        assert_eq!(progress.targets["/a"], "/dest/a (1)");
+        assert_eq!(progress.done["/dest/a (1)/x"], (10, Some(1_700_000_000)));
+        assert_eq!(progress.completed_bytes(), 15);
+        assert!(progress.moved.contains("/b"));
+        assert_eq!(progress.error, None);
+    }
+
+    #[test]
+    fn completed_only_from_the_same_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = journal_at(&dir.path().join("journal.jsonl"));
+        journal.complete("/dest/x", 10, Some(100));
+
+        assert!(journal.is_completed("/dest/x", 10, Some(100)));
+        assert!(!journal.is_completed("/dest/x", 10, Some(101)));
+        assert!(!journal.is_completed("/dest/x", 11, Some(100)));
+        assert!(!journal.is_completed("/dest/y", 10, Some(100)));
+    }
+
+    #[test]
+    fn ignores_a_last_line_cut_short() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let journal = journal_at(&path);
+        journal.complete("/dest/x", 10, Some(100));
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"entry":"done","path":"/dest/y","by"#)
+            .unwrap();
+
+        let (_, progress) = read_journal(&path).unwrap();
+        assert_eq!(progress.done.len(), 1);
+        assert!(progress.done.contains_key("/dest/x"));
+    }
+
+    #[test]
+    fn reads_done_lines_without_a_modification_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        drop(journal_at(&path));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"entry\":\"done\",\"path\":\"/dest/x\",\"bytes\":10}\n")
+            .unwrap();
+
+        let (_, progress) = read_journal(&path).unwrap();
+        assert_eq!(progress.done["/dest/x"], (10, None));
+    }
+
+    #[test]
+    fn resuming_clears_the_error_of_a_stopped_run() {
+        let mut progress = Progress::default();
+        progress.apply(Entry::Stopped {
+            error: "Cancelled".into(),
+        });
+        assert_eq!(progress.error.as_deref(), Some("Cancelled"));
+        progress.apply(Entry::Resumed);
+        assert_eq!(progress.error, None);
+        progress.apply(Entry::Stopped {
+            error: "Disk full".into(),
+        });
+        assert_eq!(progress.error.as_deref(), Some("Disk full"));
+    }
+
+    #[test]
+    fn ending_keeps_the_journal_only_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let failed = dir.path().join("failed.jsonl");
+        journal_at(&failed).end(&Err(AppError::Cancelled));
+        let (_, progress) = read_journal(&failed).unwrap();
+        assert!(progress.error.is_some());
+
+        let done = dir.path().join("done.jsonl");
+        journal_at(&done).end(&Ok(()));
+        assert!(!done.exists());
+    }
+
+    #[test]
+    fn same_operation_needs_the_same_conflict_policy() {
+        let skip = header(ConflictPolicy::Skip);
+        assert!(skip.is_same(&header(ConflictPolicy::Skip)));
+        assert!(!skip.is_same(&header(ConflictPolicy::Overwrite)));
+        let mut other_dest = header(ConflictPolicy::Skip);
+        other_dest.dest = "/elsewhere".into();
+        assert!(!skip.is_same(&other_dest));
+    }
+}
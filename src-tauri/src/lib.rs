@@ -1,20 +1,55 @@
+mod archive;
 mod commands;
+mod frecency;
 mod fs;
 mod indexer;
+mod jobs;
+mod journal;
+mod queue;
+mod search;
+mod tags;
+mod transfer;
+mod vfs;
+mod wsl;
 
 use commands::{
+    archive::{create_archive, extract_archive},
     config::{load_config, save_config},
-    filesystem::{get_home_directory, get_parent_directory, open_file, read_directory},
+    frecency::{forget_frecent_path, get_frecent_paths, jump_to},
+    filesystem::{
+        get_disk_space, get_home_directory, get_parent_directory, open_file, read_directory,
+        unwatch_directory, watch_directory,
+    },
+    jobs::{cancel_job, get_jobs},
     indexer::{
-        clear_index_cache, get_index_status, search_index, start_indexing, stop_indexing,
-        IndexerState,
+        add_index_root, clear_index_cache, get_index_scopes, get_index_status,
+        remove_index_root, remove_index_scope, search_content_index, search_index,
+        start_indexing, stop_indexing,
     },
     operations::{
-        copy_files, create_directory, delete_files, get_file_info, move_files, rename_file,
+        copy_files, create_directory, delete_files, get_file_info, move_files, plan_copy,
+        rename_file,
+    },
+    queue::{
+        discard_interrupted_operation, get_interrupted_operations, get_operation_queue,
+        queue_operation, remove_operation, reorder_operation, resume_interrupted_operations,
+        set_queue_paused,
     },
-    search::{get_available_drives, search_files},
-    wsl::{get_wsl_distros, wsl_copy},
+    remotes::{forget_remote_secret, has_remote_secret, set_remote_secret},
+    search::{cancel_search, get_available_drives, search_content, search_files},
+    tags::{add_tags, get_all_tags, list_files_by_tag, remove_tags},
+    wsl::{convert_wsl_path, get_wsl_distros, wsl_copy},
 };
+use frecency::FrecencyStore;
+use indexer::state::IndexerState;
+use indexer::watcher::DirectoryWatcher;
+use jobs::JobRegistry;
+use journal::Journals;
+use queue::OperationQueue;
+use search::SearchState;
+use tags::TagStore;
+use vfs::VfsRegistry;
+use tauri::{Manager, RunEvent};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -22,35 +57,107 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .manage(IndexerState::new())
+        .manage(SearchState::new())
+        .manage(DirectoryWatcher::new())
+        .manage(FrecencyStore::load())
+        .manage(TagStore::load())
+        .manage(VfsRegistry::new())
+        .manage(JobRegistry::new())
+        .manage(Journals::new())
+        .manage(OperationQueue::load())
+        .setup(|app| {
+            indexer::scheduler::spawn(app.handle().clone());
+            frecency::spawn_flush(app.handle().clone());
+            tags::spawn_detach(app.handle().clone());
+            tauri::async_runtime::spawn_blocking(vfs::clean_temp_dirs);
+            fs::volumes::watch(app.handle().clone());
+            // Operations left over from the last run start again, as do ones it was
+            // running without the queue
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let queue = handle.state::<OperationQueue>();
+                queue.resume_interrupted(&handle, None);
+                queue.dispatch(&handle);
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Filesystem
             read_directory,
             get_parent_directory,
             get_home_directory,
+            get_disk_space,
             open_file,
             get_file_info,
+            watch_directory,
+            unwatch_directory,
             // Config
             load_config,
             save_config,
             // WSL
             get_wsl_distros,
+            convert_wsl_path,
             wsl_copy,
             // File operations
             copy_files,
+            plan_copy,
             move_files,
             delete_files,
             create_directory,
             rename_file,
+            // Remotes
+            set_remote_secret,
+            forget_remote_secret,
+            has_remote_secret,
+            // Archives
+            create_archive,
+            extract_archive,
+            // Jobs
+            cancel_job,
+            get_jobs,
+            // Queue
+            queue_operation,
+            get_operation_queue,
+            reorder_operation,
+            remove_operation,
+            set_queue_paused,
+            get_interrupted_operations,
+            resume_interrupted_operations,
+            discard_interrupted_operation,
             // Search
             search_files,
+            search_content,
+            cancel_search,
             get_available_drives,
+            // Frecency
+            jump_to,
+            get_frecent_paths,
+            forget_frecent_path,
+            // Tags
+            add_tags,
+            remove_tags,
+            list_files_by_tag,
+            get_all_tags,
             // Indexer
             start_indexing,
             search_index,
+            search_content_index,
             get_index_status,
             stop_indexing,
             clear_index_cache,
+            add_index_root,
+            remove_index_root,
+            get_index_scopes,
+            remove_index_scope,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                app.state::<IndexerState>().save_caches();
+                let _ = app.state::<FrecencyStore>().save();
+                let _ = app.state::<TagStore>().save();
+                vfs::clean_temp_dirs();
+            }
+        });
 }
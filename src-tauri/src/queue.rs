@@ -0,0 +1,404 @@
+//! Queued copies and moves. Operations touching the same disk run one after another so
+//! they do not thrash it, while operations on separate disks run side by side.
+
+use crate::fs::volumes::device_key;
+use crate::fs::{AppError, ConflictPolicy};
+use crate::jobs::JobRegistry;
+use crate::journal::Journals;
+use crate::transfer::{copy_items, move_items};
+use crate::vfs::{ArchiveFs, VfsRegistry};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager};
+
+const STORE_FILE_NAME: &str = "queue.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Copy,
+    Move,
+}
+
+impl OperationKind {
+    fn job_kind(self) -> &'static str {
+        match self {
+            OperationKind::Copy => "copy_files",
+            OperationKind::Move => "move_files",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedOperation {
+    pub id: String,
+    pub kind: OperationKind,
+    pub sources: Vec<String>,
+    pub dest: String,
+    pub conflict: ConflictPolicy,
+    /// Go ahead despite the problems the copy's plan finds, such as too little space.
+    #[serde(default)]
+    pub ignore_problems: bool,
+    /// The journal of the interrupted run this operation continues, if it does.
+    #[serde(default)]
+    pub resume: Option<String>,
+    /// Disks and servers the operation reads or writes, worked out again before it
+    /// starts.
+    pub devices: Vec<String>,
+    /// The job running the operation, or `None` while it waits.
+    pub job_id: Option<String>,
+}
+
+/// The queue as stored, and as sent with `queue:changed`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueState {
+    /// While paused no operations are started; running ones finish.
+    pub paused: bool,
+    /// Running operations first, then waiting ones in the order they will start.
+    pub operations: Vec<QueuedOperation>,
+}
+
+/// Payload of `queue:done`, sent when an operation has run.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationDone {
+    pub id: String,
+    /// Why it failed or that it was cancelled, or `None` if it completed.
+    pub error: Option<String>,
+}
+
+/// Starts queued operations as their devices become free and keeps the queue on disk,
+/// so that operations still waiting or running when the app exits start again with it.
+pub struct OperationQueue {
+    state: Mutex<QueueState>,
+}
+
+impl OperationQueue {
+    /// Loads the queue from disk, starting empty if there is none or it cannot be read.
+    /// Operations that were running are waiting again.
+    pub fn load() -> Self {
+        let mut state: QueueState = store_path()
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        for operation in &mut state.operations {
+            operation.job_id = None;
+        }
+
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueState {
+        self.state.lock().clone()
+    }
+
+    /// Queues an operation behind the others and returns its id.
+    pub fn push(
+        &self,
+        app: &AppHandle,
+        kind: OperationKind,
+        sources: Vec<String>,
+        dest: String,
+        conflict: ConflictPolicy,
+        ignore_problems: bool,
+    ) -> String {
+        let devices = devices_of(&app.state::<VfsRegistry>(), &sources, &dest);
+        let id = uuid::Uuid::new_v4().to_string();
+        self.state.lock().operations.push(QueuedOperation {
+            id: id.clone(),
+            kind,
+            sources,
+            dest,
+            conflict,
+            ignore_problems,
+            resume: None,
+            devices,
+            job_id: None,
+        });
+        self.changed(app);
+        id
+    }
+
+    /// Moves a waiting operation to `index` in the queue. Running operations keep their
+    /// place.
+    pub fn reorder(&self, app: &AppHandle, id: &str, index: usize) -> Result<(), AppError> {
+        {
+            let mut state = self.state.lock();
+            let from = waiting_position(&state, id)?;
+            let operation = state.operations.remove(from);
+            let first_waiting = state
+                .operations
+                .iter()
+                .position(|o| o.job_id.is_none())
+                .unwrap_or(state.operations.len());
+            let to = index.clamp(first_waiting, state.operations.len());
+            state.operations.insert(to, operation);
+        }
+        self.changed(app);
+        Ok(())
+    }
+
+    /// Takes an operation off the queue, cancelling it if it is running.
+    pub fn remove(&self, app: &AppHandle, id: &str) -> Result<(), AppError> {
+        let job_id = {
+            let state = self.state.lock();
+            let operation = state
+                .operations
+                .iter()
+                .find(|o| o.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Queued operation {}", id)))?;
+            operation.job_id.clone()
+        };
+
+        match job_id {
+            // Leaves the queue once the job has stopped
+            Some(job_id) => {
+                app.state::<JobRegistry>().cancel(&job_id);
+            }
+            None => {
+                self.state.lock().operations.retain(|o| o.id != id);
+                self.changed(app);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues interrupted operations again, picking up where they stopped: those in
+    /// `ids`, or without ids those that did not fail or get cancelled but were cut short
+    /// by the app exiting. One still waiting in the queue continues its journal instead.
+    /// Returns the ids of the queued operations.
+    pub fn resume_interrupted(&self, app: &AppHandle, ids: Option<&[String]>) -> Vec<String> {
+        let interrupted = app.state::<Journals>().interrupted();
+        let vfs = app.state::<VfsRegistry>();
+        let mut queued = Vec::new();
+        {
+            let mut state = self.state.lock();
+            for operation in interrupted {
+                let wanted = match ids {
+                    Some(ids) => ids.contains(&operation.id),
+                    None => operation.error.is_none(),
+                };
+                if !wanted
+                    || state
+                        .operations
+                        .iter()
+                        .any(|o| o.resume.as_ref() == Some(&operation.id))
+                {
+                    continue;
+                }
+
+                let waiting = state.operations.iter_mut().find(|o| {
+                    o.job_id.is_none()
+                        && o.resume.is_none()
+                        && o.kind == operation.kind
+                        && o.sources == operation.sources
+                        && o.dest == operation.dest
+                        && o.conflict == operation.conflict
+                });
+                if let Some(waiting) = waiting {
+                    waiting.resume = Some(operation.id);
+                    queued.push(waiting.id.clone());
+                    continue;
+                }
+
+                let id = uuid::Uuid::new_v4().to_string();
+                state.operations.push(QueuedOperation {
+                    id: id.clone(),
+                    kind: operation.kind,
+                    devices: devices_of(&vfs, &operation.sources, &operation.dest),
+                    sources: operation.sources,
+                    dest: operation.dest,
+                    conflict: operation.conflict,
+                    ignore_problems: false,
+                    resume: Some(operation.id),
+                    job_id: None,
+                });
+                queued.push(id);
+            }
+        }
+        self.changed(app);
+        queued
+    }
+
+    pub fn set_paused(&self, app: &AppHandle, paused: bool) {
+        self.state.lock().paused = paused;
+        self.changed(app);
+    }
+
+    /// Starts every waiting operation whose devices are free. An operation waiting for a
+    /// device keeps the operations behind it from taking that device first.
+    pub fn dispatch(&self, app: &AppHandle) {
+        // Disks may have been mounted elsewhere since an operation was queued, possibly
+        // in an earlier run. Looked up outside the lock, as that touches the disks.
+        let waiting: Vec<_> = {
+            let state = self.state.lock();
+            if state.paused {
+                return;
+            }
+            state
+                .operations
+                .iter()
+                .filter(|o| o.job_id.is_none())
+                .map(|o| (o.id.clone(), o.sources.clone(), o.dest.clone()))
+                .collect()
+        };
+        let vfs = app.state::<VfsRegistry>();
+        let mut devices: HashMap<String, Vec<String>> = waiting
+            .into_iter()
+            .map(|(id, sources, dest)| (id, devices_of(&vfs, &sources, &dest)))
+            .collect();
+
+        let started = {
+            let mut state = self.state.lock();
+            if state.paused {
+                return;
+            }
+            for operation in state.operations.iter_mut() {
+                if let Some(found) = devices.remove(&operation.id) {
+                    operation.devices = found;
+                }
+            }
+
+            let mut claimed: HashSet<String> = state
+                .operations
+                .iter()
+                .filter(|o| o.job_id.is_some())
+                .flat_map(|o| o.devices.iter().cloned())
+                .collect();
+            let mut started = false;
+            for operation in state.operations.iter_mut() {
+                if operation.job_id.is_some() {
+                    continue;
+                }
+                let free = operation.devices.iter().all(|d| !claimed.contains(d));
+                claimed.extend(operation.devices.iter().cloned());
+                if free {
+                    operation.job_id = Some(start(app, operation.clone()));
+                    started = true;
+                }
+            }
+
+            // Keep running operations in front
+            state.operations.sort_by_key(|o| o.job_id.is_none());
+            started
+        };
+
+        if started {
+            self.changed(app);
+        }
+    }
+
+    /// Writes the queue, which happens on every change.
+    pub fn save(&self) -> io::Result<()> {
+        let path = store_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let state = self.snapshot();
+        let writer = BufWriter::new(fs::File::create(&path)?);
+        serde_json::to_writer(writer, &state).map_err(io::Error::other)
+    }
+
+    fn finished(&self, app: &AppHandle, id: &str, error: Option<String>) {
+        self.state.lock().operations.retain(|o| o.id != id);
+        let _ = app.emit(
+            "queue:done",
+            OperationDone {
+                id: id.to_string(),
+                error,
+            },
+        );
+        self.changed(app);
+    }
+
+    fn changed(&self, app: &AppHandle) {
+        let _ = self.save();
+        let _ = app.emit("queue:changed", self.snapshot());
+        self.dispatch(app);
+    }
+}
+
+/// Runs an operation as a job and returns the job's id.
+fn start(app: &AppHandle, operation: QueuedOperation) -> String {
+    let handle = app.clone();
+    app.state::<JobRegistry>()
+        .spawn(app, operation.kind.job_kind(), move |job| {
+            let result = match operation.kind {
+                OperationKind::Copy => copy_items(
+                    &handle,
+                    &operation.sources,
+                    &operation.dest,
+                    operation.conflict,
+                    operation.ignore_problems,
+                    operation.resume.as_deref(),
+                    job,
+                ),
+                OperationKind::Move => move_items(
+                    &handle,
+                    &operation.sources,
+                    &operation.dest,
+                    operation.conflict,
+                    operation.ignore_problems,
+                    operation.resume.as_deref(),
+                    job,
+                ),
+            };
+            handle.state::<OperationQueue>().finished(
+                &handle,
+                &operation.id,
+                result.as_ref().err().map(|e| e.to_string()),
+            );
+            result
+        })
+}
+
+fn waiting_position(state: &QueueState, id: &str) -> Result<usize, AppError> {
+    match state.operations.iter().position(|o| o.id == id) {
+        Some(index) if state.operations[index].job_id.is_none() => Ok(index),
+        Some(_) => Err(AppError::InvalidOperation(
+            "The operation is already running".into(),
+        )),
+        None => Err(AppError::NotFound(format!("Queued operation {}", id))),
+    }
+}
+
+/// The devices of `sources` and `dest`, each once.
+fn devices_of(vfs: &VfsRegistry, sources: &[String], dest: &str) -> Vec<String> {
+    let mut devices: Vec<String> = sources
+        .iter()
+        .map(String::as_str)
+        .chain(Some(dest))
+        .map(|path| device_of(vfs, path))
+        .collect();
+    devices.sort();
+    devices.dedup();
+    devices
+}
+
+/// The disk a local path is on, the archive file for paths inside archives, and the
+/// server for remote paths.
+fn device_of(vfs: &VfsRegistry, path: &str) -> String {
+    let local = vfs
+        .resolve(path)
+        .local_path(path)
+        .or_else(|| ArchiveFs::parse(path).map(|archive| archive.archive));
+    if let Some(local) = local {
+        return device_key(&local).unwrap_or_else(|| local.display().to_string());
+    }
+
+    match path.split_once("://") {
+        Some((scheme, rest)) => format!("{}://{}", scheme, rest.split('/').next().unwrap_or("")),
+        None => path.to_string(),
+    }
+}
+
+fn store_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("filemanager").join(STORE_FILE_NAME))
+}
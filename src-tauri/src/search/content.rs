@@ -0,0 +1,220 @@
+use super::SearchSummary;
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_MATCHES: usize = 1000;
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const BINARY_SNIFF_LEN: usize = 8192;
+const MAX_LINE_CHARS: usize = 500;
+const BATCH_SIZE: usize = 50;
+const BATCH_INTERVAL_MS: u64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ContentSearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub include_hidden: bool,
+    pub respect_gitignore: bool,
+    pub max_file_size: u64,
+    pub max_matches: usize,
+    pub timeout_ms: u64,
+    pub context_lines: usize,
+}
+
+impl Default for ContentSearchOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: false,
+            include_hidden: false,
+            respect_gitignore: true,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_matches: DEFAULT_MAX_MATCHES,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            context_lines: DEFAULT_CONTEXT_LINES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentMatch {
+    pub path: String,
+    pub line_number: usize,
+    pub line: String,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ContentSearchBatch {
+    search_id: String,
+    matches: Vec<ContentMatch>,
+}
+
+/// Builds the matcher up front so an invalid pattern is reported before the search starts.
+pub fn build_matcher(query: &str, options: &ContentSearchOptions) -> Result<Regex, regex::Error> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+}
+
+pub fn search_content(
+    search_id: String,
+    matcher: Regex,
+    roots: Vec<PathBuf>,
+    options: ContentSearchOptions,
+    cancel: Arc<AtomicBool>,
+    app: AppHandle,
+) -> SearchSummary {
+    let started = Instant::now();
+    let deadline = started + Duration::from_millis(options.timeout_ms);
+
+    let mut scanned_count = 0;
+    let mut match_count = 0;
+    let mut truncated = false;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut last_flush = Instant::now();
+
+    let roots: Vec<PathBuf> = roots.into_iter().filter(|r| r.exists()).collect();
+
+    if let Some((first, rest)) = roots.split_first() {
+        let mut builder = WalkBuilder::new(first);
+        for root in rest {
+            builder.add(root);
+        }
+        builder
+            .follow_links(false)
+            .hidden(!options.include_hidden)
+            .git_ignore(options.respect_gitignore)
+            .git_exclude(options.respect_gitignore)
+            .git_global(options.respect_gitignore)
+            .ignore(options.respect_gitignore)
+            .require_git(false);
+
+        'walk: for entry in builder.build().filter_map(|e| e.ok()) {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if Instant::now() >= deadline {
+                truncated = true;
+                break;
+            }
+
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+
+            let size = match entry.metadata() {
+                Ok(m) => m.len(),
+                Err(_) => continue,
+            };
+
+            if size > options.max_file_size {
+                continue;
+            }
+
+            scanned_count += 1;
+
+            let text = match read_text_file(entry.path(), size) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            let lines: Vec<&str> = text.lines().collect();
+            let path = entry.path().display().to_string();
+
+            for (i, line) in lines.iter().enumerate() {
+                if !matcher.is_match(line) {
+                    continue;
+                }
+
+                let before_start = i.saturating_sub(options.context_lines);
+                let after_end = (i + 1 + options.context_lines).min(lines.len());
+
+                batch.push(ContentMatch {
+                    path: path.clone(),
+                    line_number: i + 1,
+                    line: truncate_line(line),
+                    context_before: lines[before_start..i].iter().map(|l| truncate_line(l)).collect(),
+                    context_after: lines[i + 1..after_end].iter().map(|l| truncate_line(l)).collect(),
+                });
+                match_count += 1;
+
+                if match_count >= options.max_matches {
+                    truncated = true;
+                    break 'walk;
+                }
+            }
+
+            if batch.len() >= BATCH_SIZE
+                || (!batch.is_empty() && last_flush.elapsed() >= Duration::from_millis(BATCH_INTERVAL_MS))
+            {
+                emit_batch(&app, &search_id, std::mem::take(&mut batch));
+                last_flush = Instant::now();
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        emit_batch(&app, &search_id, batch);
+    }
+
+    let summary = SearchSummary {
+        search_id,
+        scanned_count,
+        match_count,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        truncated,
+        cancelled: cancel.load(Ordering::Relaxed),
+    };
+    let _ = app.emit("search:content:done", summary.clone());
+    summary
+}
+
+fn emit_batch(app: &AppHandle, search_id: &str, matches: Vec<ContentMatch>) {
+    let _ = app.emit(
+        "search:content",
+        ContentSearchBatch {
+            search_id: search_id.to_string(),
+            matches,
+        },
+    );
+}
+
+/// Reads a file as text, returning `None` for binaries (NUL byte in the first block).
+fn read_text_file(path: &std::path::Path, size: u64) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut bytes = Vec::with_capacity(size as usize);
+    file.read_to_end(&mut bytes).ok()?;
+
+    let sniff_len = bytes.len().min(BINARY_SNIFF_LEN);
+    if bytes[..sniff_len].contains(&0) {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((idx, _)) => format!("{}…", &line[..idx]),
+        None => line.to_string(),
+    }
+}
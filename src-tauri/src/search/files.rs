@@ -0,0 +1,129 @@
+use super::query::SearchQuery;
+use super::SearchSummary;
+use crate::fs::FileEntry;
+use crate::indexer::exclusions::ExclusionMatcher;
+use crate::tags::{self, TagStore};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+const BATCH_SIZE: usize = 100;
+const BATCH_INTERVAL_MS: u64 = 100;
+
+#[derive(Debug, Clone, Serialize)]
+struct FileSearchBatch {
+    search_id: String,
+    entries: Vec<FileEntry>,
+}
+
+/// Walks the matcher's roots for names containing the query text and carrying its `tag:`
+/// filters, streaming matches as `search:files` batches.
+pub fn search_files(
+    search_id: String,
+    query: SearchQuery,
+    exclusions: Arc<ExclusionMatcher>,
+    limit: usize,
+    cancel: Arc<AtomicBool>,
+    app: AppHandle,
+) -> SearchSummary {
+    let started = Instant::now();
+    let tag_store = app.state::<TagStore>();
+
+    let mut scanned_count = 0;
+    let mut match_count = 0;
+    let mut truncated = false;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut last_flush = Instant::now();
+
+    if let Some(builder) = exclusions.walk_builder() {
+        for entry in builder.build().filter_map(|e| e.ok()) {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            scanned_count += 1;
+
+            let file_name = entry.file_name().to_string_lossy().to_string();
+
+            // Matched like saved searches and the index, then the tag filters
+            let path = entry.path().display().to_string();
+            if query.matches_text(&path) {
+                let file_tags = tag_store.tags_for(&path);
+
+                let metadata = match entry.metadata() {
+                    Ok(m) if tags::has_all_tags(&file_tags, &query.tags) => m,
+                    _ => continue,
+                };
+
+                let extension = if metadata.is_file() {
+                    PathBuf::from(&file_name)
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_string())
+                } else {
+                    None
+                };
+
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64);
+
+                let is_hidden = file_name.starts_with('.');
+
+                batch.push(FileEntry {
+                    name: file_name,
+                    path,
+                    extension,
+                    size: if metadata.is_file() { Some(metadata.len()) } else { None },
+                    modified,
+                    is_dir: metadata.is_dir(),
+                    is_hidden,
+                    is_symlink: entry.path_is_symlink(),
+                    tags: file_tags,
+                });
+                match_count += 1;
+
+                if match_count >= limit {
+                    truncated = true;
+                    break;
+                }
+            }
+
+            if batch.len() >= BATCH_SIZE
+                || (!batch.is_empty() && last_flush.elapsed() >= Duration::from_millis(BATCH_INTERVAL_MS))
+            {
+                emit_batch(&app, &search_id, std::mem::take(&mut batch));
+                last_flush = Instant::now();
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        emit_batch(&app, &search_id, batch);
+    }
+
+    let summary = SearchSummary {
+        search_id,
+        scanned_count,
+        match_count,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        truncated,
+        cancelled: cancel.load(Ordering::Relaxed),
+    };
+    let _ = app.emit("search:files:done", summary.clone());
+    summary
+}
+
+fn emit_batch(app: &AppHandle, search_id: &str, entries: Vec<FileEntry>) {
+    let _ = app.emit(
+        "search:files",
+        FileSearchBatch {
+            search_id: search_id.to_string(),
+            entries,
+        },
+    );
+}
@@ -0,0 +1,61 @@
+pub mod content;
+pub mod files;
+pub mod query;
+pub mod saved;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Summary emitted once a streaming search has finished, been cancelled or hit a limit.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSummary {
+    pub search_id: String,
+    pub scanned_count: usize,
+    pub match_count: usize,
+    pub elapsed_ms: u64,
+    pub truncated: bool,
+    pub cancelled: bool,
+}
+
+/// Tracks running searches so they can be cancelled by id.
+pub struct SearchState {
+    active: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self {
+            active: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn register(&self) -> (String, Arc<AtomicBool>) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.active.lock().insert(id.clone(), cancel.clone());
+        (id, cancel)
+    }
+
+    pub fn cancel(&self, search_id: &str) -> bool {
+        match self.active.lock().get(search_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, search_id: &str) {
+        self.active.lock().remove(search_id);
+    }
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,50 @@
+/// A search query split into `tag:` filters and the remaining name text, e.g.
+/// `tag:invoice tag:final 2024` finds names containing "2024" tagged with both.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Name text, lowercased, with the words joined by single spaces.
+    pub text: String,
+    /// Lowercased, like stored tags.
+    pub tags: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut words = Vec::new();
+        let mut tags = Vec::new();
+
+        for word in query.split_whitespace() {
+            match word.strip_prefix("tag:") {
+                Some(tag) if !tag.is_empty() => tags.push(tag.to_lowercase()),
+                _ => words.push(word.to_lowercase()),
+            }
+        }
+
+        Self {
+            text: words.join(" "),
+            tags,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.tags.is_empty()
+    }
+
+    /// Matches the text against the full path if it contains a separator, like
+    /// `IndexManager::search`, and against the name otherwise.
+    pub fn matches_text(&self, path: &str) -> bool {
+        if self.text.is_empty() {
+            return true;
+        }
+
+        let path = path.to_lowercase();
+        if self.text.contains('/') || self.text.contains('\\') {
+            path.contains(&self.text)
+        } else {
+            path.rsplit(['/', '\\'])
+                .next()
+                .unwrap_or(&path)
+                .contains(&self.text)
+        }
+    }
+}
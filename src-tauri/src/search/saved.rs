@@ -0,0 +1,227 @@
+use super::query::SearchQuery;
+use crate::commands::config::load_config;
+use crate::indexer::state::IndexerState;
+use crate::fs::{expand_home, AppError, FileEntry, SavedSearch};
+use crate::indexer::entry::IndexStatus;
+use crate::indexer::exclusions::ExclusionMatcher;
+use crate::indexer::scope::IndexScope;
+use crate::tags::{self, TagStore};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Prefix of the virtual folders that show saved searches.
+pub const SCHEME: &str = "search://";
+
+const MAX_RESULTS: usize = 5000;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Name of the saved search a `search://{name}` path refers to.
+pub fn name_from_path(path: &str) -> Option<&str> {
+    path.strip_prefix(SCHEME)
+        .map(|name| name.trim_end_matches(['/', '\\']))
+}
+
+pub fn find(name: &str) -> Result<SavedSearch, AppError> {
+    load_config()
+        .saved_searches
+        .into_iter()
+        .find(|search| search.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("{}{}", SCHEME, name)))
+}
+
+/// Where the results of a saved search come from.
+pub enum Source {
+    /// Live index scopes covering every search root, each paired with the root to read.
+    Index(Vec<(Arc<IndexScope>, PathBuf)>),
+    /// Roots the index does not fully cover are walked instead.
+    Walk(Vec<PathBuf>),
+}
+
+impl Source {
+    pub fn resolve(search: &SavedSearch, indexer: &IndexerState) -> Result<Self, AppError> {
+        let live: Vec<Arc<IndexScope>> = indexer
+            .scopes()
+            .into_iter()
+            .filter(|scope| scope.manager().get_status() == IndexStatus::Watching)
+            .collect();
+
+        if search.roots.is_empty() {
+            let everything: Vec<_> = live
+                .iter()
+                .flat_map(|scope| {
+                    scope
+                        .manager()
+                        .get_roots()
+                        .into_iter()
+                        .map(move |root| (scope.clone(), root))
+                })
+                .collect();
+
+            if everything.is_empty() {
+                return Err(AppError::InvalidOperation(format!(
+                    "Saved search '{}' has no roots and nothing is indexed",
+                    search.name
+                )));
+            }
+            return Ok(Source::Index(everything));
+        }
+
+        let roots: Vec<PathBuf> = search.roots.iter().map(|r| expand_home(r)).collect();
+        let covered: Option<Vec<_>> = roots
+            .iter()
+            .map(|root| {
+                live.iter()
+                    .find(|scope| {
+                        scope
+                            .manager()
+                            .get_roots()
+                            .iter()
+                            .any(|r| root.starts_with(r))
+                    })
+                    .map(|scope| (scope.clone(), root.clone()))
+            })
+            .collect();
+
+        Ok(match covered {
+            Some(covered) => Source::Index(covered),
+            None => Source::Walk(roots),
+        })
+    }
+
+    /// Sum of the covering scopes' change counters; results can only differ once it moves.
+    pub fn index_changes(&self) -> u64 {
+        match self {
+            Source::Index(covered) => covered
+                .iter()
+                .map(|(scope, _)| scope.manager().changes())
+                .sum(),
+            Source::Walk(_) => 0,
+        }
+    }
+}
+
+struct Filter {
+    query: SearchQuery,
+    extensions: Vec<String>,
+    modified_after: Option<i64>,
+    include_dirs: bool,
+}
+
+impl Filter {
+    fn new(search: &SavedSearch) -> Self {
+        let modified_after = search.modified_within_days.map(|days| {
+            let now = chrono::Utc::now().timestamp();
+            now - days.saturating_mul(SECS_PER_DAY) as i64
+        });
+
+        Self {
+            query: SearchQuery::parse(&search.query),
+            extensions: search
+                .extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect(),
+            modified_after,
+            include_dirs: search.include_dirs,
+        }
+    }
+
+    fn matches(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        extension: Option<&str>,
+        modified: Option<i64>,
+        tag_store: &TagStore,
+    ) -> bool {
+        if is_dir && !self.include_dirs {
+            return false;
+        }
+        if !is_dir && !self.extensions.is_empty() {
+            let extension = extension.map(str::to_lowercase);
+            if !extension.is_some_and(|e| self.extensions.contains(&e)) {
+                return false;
+            }
+        }
+        if let Some(after) = self.modified_after {
+            if modified.is_none_or(|m| m < after) {
+                return false;
+            }
+        }
+
+        let path = path.display().to_string();
+        self.query.matches_text(&path)
+            && (self.query.tags.is_empty()
+                || tags::has_all_tags(&tag_store.tags_for(&path), &self.query.tags))
+    }
+}
+
+/// Current results of a saved search in path order.
+pub fn run(search: &SavedSearch, source: &Source, tag_store: &TagStore) -> Vec<FileEntry> {
+    let filter = Filter::new(search);
+
+    let mut results = match source {
+        Source::Index(covered) => {
+            let mut results = Vec::new();
+            for (scope, root) in covered {
+                let limit = MAX_RESULTS.saturating_sub(results.len());
+                results.extend(scope.manager().entries_below(root, limit, |path, entry| {
+                    filter.matches(
+                        path,
+                        entry.is_dir,
+                        entry.extension.as_deref(),
+                        entry.modified,
+                        tag_store,
+                    )
+                }));
+            }
+            results
+        }
+        Source::Walk(roots) => walk(roots, &filter, tag_store),
+    };
+
+    // Roots may overlap, e.g. when every indexed scope is searched
+    let mut seen = HashSet::new();
+    results.retain(|entry| seen.insert(entry.path.clone()));
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+
+    tag_store.annotate(&mut results);
+    results
+}
+
+fn walk(roots: &[PathBuf], filter: &Filter, tag_store: &TagStore) -> Vec<FileEntry> {
+    let exclusions = Arc::new(ExclusionMatcher::new(load_config().index_exclusions, roots));
+    let builder = match exclusions.walk_builder() {
+        Some(builder) => builder,
+        None => return Vec::new(),
+    };
+
+    let mut results = Vec::new();
+    for entry in builder.build().filter_map(|e| e.ok()) {
+        if entry.depth() == 0 {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_entry = FileEntry::from_metadata(name, entry.path(), &metadata);
+        if filter.matches(
+            entry.path(),
+            file_entry.is_dir,
+            file_entry.extension.as_deref(),
+            file_entry.modified,
+            tag_store,
+        ) {
+            results.push(file_entry);
+            if results.len() >= MAX_RESULTS {
+                break;
+            }
+        }
+    }
+
+    results
+}
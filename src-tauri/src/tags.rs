@@ -0,0 +1,535 @@
+use crate::fs::FileEntry;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const STORE_FILE_NAME: &str = "tags.json";
+const MAX_TAG_LEN: usize = 64;
+/// How often records of files that disappeared are detached, off the search path.
+const DETACH_INTERVAL_SECS: u64 = 60;
+
+/// Identifies a file independently of its path, so tags can follow renames that happen
+/// outside the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileId {
+    volume: u64,
+    index: u64,
+}
+
+/// Size and modification time of a tagged file, telling a file that was renamed apart
+/// from a new one that reuses its id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TagRecord {
+    tags: Vec<String>,
+    file_id: Option<FileId>,
+    #[serde(default)]
+    stamp: Option<FileStamp>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Sidecar database of user tags, keyed by path with the file id as a fallback.
+pub struct TagStore {
+    records: RwLock<HashMap<String, TagRecord>>,
+    /// Records whose path disappeared, waiting to be found again under a new name.
+    detached: RwLock<HashMap<FileId, (String, TagRecord)>>,
+    dirty: AtomicBool,
+}
+
+impl TagStore {
+    /// Loads the store from disk, starting empty if there is none or it cannot be read.
+    /// Missing files are detached by `spawn_detach`, as checking them can take a while.
+    pub fn load() -> Self {
+        let mut records: HashMap<String, TagRecord> = store_path()
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        // Stores written before tags were lowercased may hold the same tag twice
+        for record in records.values_mut() {
+            for tag in &mut record.tags {
+                *tag = tag.to_lowercase();
+            }
+            record.tags.sort();
+            record.tags.dedup();
+        }
+
+        Self {
+            records: RwLock::new(records),
+            detached: RwLock::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Adds tags, as returned by `normalize_tag`, to a file and returns its full tag list.
+    pub fn add(&self, path: &Path, tags: &[String]) -> io::Result<Vec<String>> {
+        let id = file_id(path)?;
+        let stamp = file_stamp(path);
+        let key = path.display().to_string();
+
+        let result = {
+            let mut records = self.records.write();
+            let record = records.entry(key).or_insert_with(|| TagRecord {
+                tags: Vec::new(),
+                file_id: None,
+                stamp: None,
+            });
+            record.file_id = Some(id);
+            record.stamp = stamp;
+            for tag in tags {
+                if !record.tags.contains(tag) {
+                    record.tags.push(tag.clone());
+                }
+            }
+            record.tags.sort();
+            record.tags.clone()
+        };
+
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(result)
+    }
+
+    /// Removes tags, as returned by `normalize_tag`, from a file and returns what is left.
+    pub fn remove(&self, path: &Path, tags: &[String]) -> Vec<String> {
+        let key = path.display().to_string();
+        let mut records = self.records.write();
+
+        let remaining = match records.get_mut(&key) {
+            Some(record) => {
+                record.tags.retain(|t| !tags.contains(t));
+                record.tags.clone()
+            }
+            None => return Vec::new(),
+        };
+        if remaining.is_empty() {
+            records.remove(&key);
+        }
+
+        self.dirty.store(true, Ordering::Relaxed);
+        remaining
+    }
+
+    pub fn tags_for(&self, path: &str) -> Vec<String> {
+        self.records
+            .read()
+            .get(path)
+            .map(|r| r.tags.clone())
+            .unwrap_or_default()
+    }
+
+    /// Fills in `tags` for listed entries, picking up files that were renamed elsewhere.
+    pub fn annotate(&self, entries: &mut [FileEntry]) {
+        let resolve = !self.detached.read().is_empty();
+
+        for entry in entries.iter_mut() {
+            entry.tags = self.tags_for(&entry.path);
+            if entry.tags.is_empty() && resolve {
+                entry.tags = self.reattach(Path::new(&entry.path));
+            }
+        }
+    }
+
+    /// Moves a detached record to `path` if it is the same file under a new name. Ids are
+    /// reused once a file is deleted, so the name, or the size and modification time, must
+    /// match too.
+    fn reattach(&self, path: &Path) -> Vec<String> {
+        let id = match file_id(path) {
+            Ok(id) => id,
+            Err(_) => return Vec::new(),
+        };
+        let record = {
+            let mut detached = self.detached.write();
+            let same_file = detached.get(&id).is_some_and(|(old_path, record)| {
+                Path::new(old_path).file_name() == path.file_name()
+                    || record
+                        .stamp
+                        .is_some_and(|stamp| file_stamp(path) == Some(stamp))
+            });
+            match detached.remove(&id) {
+                Some((_, record)) if same_file => record,
+                _ => return Vec::new(),
+            }
+        };
+
+        let tags = record.tags.clone();
+        self.records
+            .write()
+            .insert(path.display().to_string(), record);
+        self.dirty.store(true, Ordering::Relaxed);
+        tags
+    }
+
+    /// Paths carrying every one of `tags`, which are lowercase like the stored ones. May
+    /// include paths that no longer exist, which callers skip.
+    pub fn paths_with_tags(&self, tags: &[String]) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .records
+            .read()
+            .iter()
+            .filter(|(_, record)| has_all_tags(&record.tags, tags))
+            .map(|(path, _)| path.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn all_tags(&self) -> Vec<TagCount> {
+        let mut counts: BTreeMap<String, TagCount> = BTreeMap::new();
+        for record in self.records.read().values() {
+            for tag in &record.tags {
+                counts
+                    .entry(tag.clone())
+                    .or_insert_with(|| TagCount {
+                        tag: tag.clone(),
+                        count: 0,
+                    })
+                    .count += 1;
+            }
+        }
+        counts.into_values().collect()
+    }
+
+    /// Follows a rename or move made by the app, including everything below a directory.
+    pub fn rename(&self, from: &Path, to: &Path) {
+        let mut records = self.records.write();
+        let moved: Vec<String> = records
+            .keys()
+            .filter(|key| Path::new(key).starts_with(from))
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return;
+        }
+
+        for key in moved {
+            if let Some(mut record) = records.remove(&key) {
+                let new_path = match Path::new(&key).strip_prefix(from) {
+                    Ok(rest) if rest.as_os_str().is_empty() => to.to_path_buf(),
+                    Ok(rest) => to.join(rest),
+                    Err(_) => continue,
+                };
+                // A copy across volumes gives the file a new id
+                record.file_id = file_id(&new_path).ok().or(record.file_id);
+                record.stamp = file_stamp(&new_path).or(record.stamp);
+                records.insert(new_path.display().to_string(), record);
+            }
+        }
+        drop(records);
+
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Drops the tags of a deleted file, or of everything below a deleted directory, so
+    /// that a new file reusing its id does not pick them up.
+    pub fn forget(&self, path: &Path) {
+        let mut removed = false;
+        self.records.write().retain(|key, _| {
+            let keep = !Path::new(key).starts_with(path);
+            removed |= !keep;
+            keep
+        });
+        self.detached.write().retain(|_, (old_path, _)| {
+            let keep = !Path::new(old_path).starts_with(path);
+            removed |= !keep;
+            keep
+        });
+
+        if removed {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Detaches records whose path no longer exists so they can be found again by file id,
+    /// whether the file was renamed or sits on a drive that is unplugged for now, and
+    /// notes the current size and modification time of the others. Checks the paths
+    /// without holding the lock, which can take a while on slow drives.
+    fn detach_missing(&self) {
+        let candidates: Vec<(String, Option<FileStamp>)> = self
+            .records
+            .read()
+            .iter()
+            .filter(|(_, record)| record.file_id.is_some())
+            .map(|(path, record)| (path.clone(), record.stamp))
+            .collect();
+        let mut missing = Vec::new();
+        let mut changed = Vec::new();
+        for (path, stamp) in candidates {
+            if !Path::new(&path).exists() {
+                missing.push(path);
+            } else if let Some(current) = file_stamp(Path::new(&path)) {
+                if stamp != Some(current) {
+                    changed.push((path, current));
+                }
+            }
+        }
+        if missing.is_empty() && changed.is_empty() {
+            return;
+        }
+
+        let mut records = self.records.write();
+        for (path, stamp) in changed {
+            if let Some(record) = records.get_mut(&path) {
+                record.stamp = Some(stamp);
+            }
+        }
+        let mut detached = self.detached.write();
+        for path in missing {
+            // It may have been tagged again since
+            if Path::new(&path).exists() {
+                continue;
+            }
+            if let Some(record) = records.remove(&path) {
+                if let Some(id) = record.file_id {
+                    detached.insert(id, (path, record));
+                }
+            }
+        }
+    }
+
+    /// Writes the store if anything changed since the last save. Detached records are
+    /// written back under their old path so they can still be found on the next start.
+    pub fn save(&self) -> io::Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let path = store_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut records = self.records.read().clone();
+        for (old_path, record) in self.detached.read().values() {
+            records
+                .entry(old_path.clone())
+                .or_insert_with(|| record.clone());
+        }
+
+        let writer = BufWriter::new(fs::File::create(&path)?);
+        serde_json::to_writer(writer, &records).map_err(io::Error::other)
+    }
+}
+
+/// Detaches records of files that disappeared, right away and then every
+/// `DETACH_INTERVAL_SECS`, so that they are found again once the file turns up under a
+/// new name.
+pub fn spawn_detach(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(DETACH_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+            let app = app.clone();
+            let _ =
+                tokio::task::spawn_blocking(move || app.state::<TagStore>().detach_missing()).await;
+        }
+    });
+}
+
+/// Trims and lowercases a tag, so that tags differing only in case are one, and rejects
+/// ones that cannot be stored or mirrored, such as commas which separate tags in
+/// `user.xdg.tags`.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && !tag.contains(',')
+        && !tag.chars().any(char::is_control);
+    valid.then_some(tag)
+}
+
+/// Whether `tags` holds every one of `wanted`, both lowercase.
+pub fn has_all_tags(tags: &[String], wanted: &[String]) -> bool {
+    wanted.iter().all(|w| tags.contains(w))
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    Some(FileStamp {
+        size: metadata.len(),
+        modified: FileEntry::from_metadata(name, path, &metadata).modified,
+    })
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::symlink_metadata(path)?;
+    Ok(FileId {
+        volume: metadata.dev(),
+        index: metadata.ino(),
+    })
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> io::Result<FileId> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_FLAG_OPEN_REPARSE_POINT,
+    };
+
+    // No access rights are needed to read the id, and backup semantics allow directories
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(path)?;
+
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle() as _, &mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(FileId {
+        volume: u64::from(info.dwVolumeSerialNumber),
+        index: (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow),
+    })
+}
+
+/// Mirrors a file's tags into the `user.xdg.tags` extended attribute so other tools see
+/// them too, removing the attribute once no tags are left.
+#[cfg(target_os = "linux")]
+pub fn mirror_to_xattr(path: &Path, tags: &[String]) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let name = c"user.xdg.tags";
+
+    let result = if tags.is_empty() {
+        let result = unsafe { libc::removexattr(c_path.as_ptr(), name.as_ptr()) };
+        if result != 0 && io::Error::last_os_error().raw_os_error() == Some(libc::ENODATA) {
+            return Ok(());
+        }
+        result
+    } else {
+        let value = tags.join(",");
+        unsafe {
+            libc::setxattr(
+                c_path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        }
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn mirror_to_xattr(_path: &Path, _tags: &[String]) -> io::Result<()> {
+    Ok(())
+}
+
+fn store_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("filemanager").join(STORE_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> TagStore {
+        TagStore {
+            records: RwLock::new(HashMap::new()),
+            detached: RwLock::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    fn entry(path: &Path) -> FileEntry {
+        let metadata = fs::symlink_metadata(path).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        FileEntry::from_metadata(name, path, &metadata)
+    }
+
+    #[test]
+    fn normalizes_tags_to_lowercase() {
+        assert_eq!(normalize_tag("  Überprüfen "), Some("überprüfen".into()));
+        assert_eq!(normalize_tag("a,b"), None);
+        assert_eq!(normalize_tag("   "), None);
+        assert!(has_all_tags(&["überprüfen".into()], &["überprüfen".into()]));
+    }
+
+    #[test]
+    fn same_tag_in_another_case_is_added_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "a").unwrap();
+        let store = store();
+
+        store
+            .add(&path, &[normalize_tag("Überprüfen").unwrap()])
+            .unwrap();
+        let tags = store
+            .add(&path, &[normalize_tag("überprüfen").unwrap()])
+            .unwrap();
+        assert_eq!(tags, vec!["überprüfen".to_string()]);
+        assert_eq!(store.all_tags().len(), 1);
+    }
+
+    #[test]
+    fn follows_a_file_renamed_elsewhere() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        fs::write(&from, "a").unwrap();
+        let store = store();
+        store.add(&from, &["work".into()]).unwrap();
+
+        fs::rename(&from, &to).unwrap();
+        store.detach_missing();
+        let mut entries = vec![entry(&to)];
+        store.annotate(&mut entries);
+        assert_eq!(entries[0].tags, vec!["work".to_string()]);
+    }
+
+    #[test]
+    fn new_file_reusing_an_id_does_not_inherit_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+        fs::write(&path, "new contents").unwrap();
+        let store = store();
+        // As if a deleted file had this id, under another name and with another size
+        let record = TagRecord {
+            tags: vec!["work".into()],
+            file_id: Some(file_id(&path).unwrap()),
+            stamp: Some(FileStamp {
+                size: 1,
+                modified: None,
+            }),
+        };
+        store.detached.write().insert(
+            record.file_id.unwrap(),
+            (dir.path().join("old.txt").display().to_string(), record),
+        );
+
+        let mut entries = vec![entry(&path)];
+        store.annotate(&mut entries);
+        assert!(entries[0].tags.is_empty());
+    }
+}
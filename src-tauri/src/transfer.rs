@@ -0,0 +1,213 @@
+//! Copies and moves between any providers, as run by `copy_files`, `move_files` and the
+//! operation queue. Both check up front that the data fits and keep a journal, so that
+//! an interrupted run can be resumed through the queue.
+
+use crate::archive;
+use crate::fs::{AppError, ConflictPolicy};
+use crate::jobs::Job;
+use crate::journal::{Journal, Journals};
+use crate::queue::OperationKind;
+use crate::tags::TagStore;
+use crate::vfs::{self, ArchiveFs, VfsRegistry};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Copies `sources` into the folder `dest` as part of `job`, after checking that they
+/// fit unless `ignore_problems` is set. Journaled; with `resume` it continues the
+/// interrupted copy whose journal has that id, skipping the files it completed.
+pub fn copy_items(
+    app: &AppHandle,
+    sources: &[String],
+    dest: &str,
+    conflict: ConflictPolicy,
+    ignore_problems: bool,
+    resume: Option<&str>,
+    job: &Job,
+) -> Result<(), AppError> {
+    let journal =
+        app.state::<Journals>()
+            .begin(OperationKind::Copy, sources, dest, conflict, resume);
+    job.set_journal(Some(journal.clone()));
+    let result = copy_each(app, sources, dest, conflict, ignore_problems, job, &journal);
+    job.set_journal(None);
+    journal.end(&result);
+    result
+}
+
+fn copy_each(
+    app: &AppHandle,
+    sources: &[String],
+    dest: &str,
+    conflict: ConflictPolicy,
+    ignore_problems: bool,
+    job: &Job,
+    journal: &Journal,
+) -> Result<(), AppError> {
+    let vfs = app.state::<VfsRegistry>();
+    let dest_fs = vfs.resolve(dest);
+    let local_dest = dest_fs.local_path(dest);
+    let sources: Vec<_> = sources
+        .iter()
+        .map(|source| (vfs.resolve(source), source.clone()))
+        .collect();
+
+    dest_fs.check_writable(dest)?;
+    vfs::ensure_dir(&*dest_fs, dest)?;
+    let mut plan = vfs::plan_copy(&sources, &*dest_fs, dest, conflict, Some(job))?;
+    plan.resume(journal.completed_bytes());
+    if !ignore_problems {
+        plan.check()?;
+    }
+    job.set_totals(plan.total_items, plan.total_bytes);
+
+    // Entries copied out of an archive onto the local disk are extracted in one pass
+    // per archive
+    let mut from_archives: BTreeMap<PathBuf, (archive::ArchiveFormat, Vec<String>)> =
+        BTreeMap::new();
+    let mut others = Vec::new();
+    for (source_fs, source) in sources {
+        match ArchiveFs::parse(&source).filter(|_| local_dest.is_some()) {
+            Some(path) => from_archives
+                .entry(path.archive)
+                .or_insert_with(|| (path.format, Vec::new()))
+                .1
+                .push(path.inner),
+            None => others.push((source_fs, source)),
+        }
+    }
+    if let Some(dest_path) = &local_dest {
+        for (archive_file, (format, inners)) in &from_archives {
+            archive::extract(
+                archive_file,
+                *format,
+                inners,
+                dest_path,
+                conflict,
+                Some(job),
+            )?;
+        }
+    }
+
+    for (source_fs, source) in others {
+        job.check_cancelled()?;
+        let target = match place(&*source_fs, &source, &*dest_fs, dest, conflict, journal)? {
+            Some(target) => target,
+            None => continue,
+        };
+
+        vfs::copy(&*source_fs, &source, &*dest_fs, &target, Some(job))?;
+    }
+    Ok(())
+}
+
+/// Moves `sources` into the folder `dest` as part of `job`, like `copy_items`. Tags
+/// follow the files that were moved, also when a later one fails.
+pub fn move_items(
+    app: &AppHandle,
+    sources: &[String],
+    dest: &str,
+    conflict: ConflictPolicy,
+    ignore_problems: bool,
+    resume: Option<&str>,
+    job: &Job,
+) -> Result<(), AppError> {
+    let tags = app.state::<TagStore>();
+    let journal =
+        app.state::<Journals>()
+            .begin(OperationKind::Move, sources, dest, conflict, resume);
+    job.set_journal(Some(journal.clone()));
+    let result = move_each(app, sources, dest, conflict, ignore_problems, job, &journal);
+    job.set_journal(None);
+    journal.end(&result);
+    let _ = tags.save();
+    result
+}
+
+fn move_each(
+    app: &AppHandle,
+    sources: &[String],
+    dest: &str,
+    conflict: ConflictPolicy,
+    ignore_problems: bool,
+    job: &Job,
+    journal: &Journal,
+) -> Result<(), AppError> {
+    let vfs = app.state::<VfsRegistry>();
+    let tags = app.state::<TagStore>();
+    let dest_fs = vfs.resolve(dest);
+    // Sources an interrupted run already moved are gone, also where it stopped before
+    // noting that. Any other trouble reaching a source fails the move.
+    let mut remaining = Vec::new();
+    for source in sources {
+        let source_fs = vfs.resolve(source);
+        if journal.is_moved(source) {
+            continue;
+        }
+        if journal.target(source).is_some() {
+            match source_fs.stat(source) {
+                Ok(_) => {}
+                Err(AppError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        remaining.push((source_fs, source.clone()));
+    }
+    let sources = remaining;
+
+    dest_fs.check_writable(dest)?;
+    for (source_fs, source) in &sources {
+        source_fs.check_writable(source)?;
+    }
+    vfs::ensure_dir(&*dest_fs, dest)?;
+
+    // Only data moved across volumes or providers needs room at the destination
+    let copied: Vec<_> = sources
+        .iter()
+        .filter(|(source_fs, source)| vfs::move_copies(&**source_fs, source, &*dest_fs, dest))
+        .cloned()
+        .collect();
+    let mut plan = vfs::plan_copy(&copied, &*dest_fs, dest, conflict, Some(job))?;
+    plan.resume(journal.completed_bytes());
+    if !ignore_problems {
+        plan.check()?;
+    }
+    job.set_totals(plan.total_items, plan.total_bytes);
+
+    for (source_fs, source) in sources {
+        job.check_cancelled()?;
+        let target = match place(&*source_fs, &source, &*dest_fs, dest, conflict, journal)? {
+            Some(target) => target,
+            None => continue,
+        };
+
+        vfs::move_item(&*source_fs, &source, &*dest_fs, &target, Some(job))?;
+        journal.set_moved(&source);
+        if let (Some(from), Some(to)) = (source_fs.local_path(&source), dest_fs.local_path(&target))
+        {
+            tags.rename(&from, &to);
+        }
+    }
+    Ok(())
+}
+
+/// Where `source` goes in `dest`: where an interrupted run put it, or else as
+/// `conflict` decides, which the journal then remembers. `None` skips it.
+fn place(
+    source_fs: &dyn vfs::Vfs,
+    source: &str,
+    dest_fs: &dyn vfs::Vfs,
+    dest: &str,
+    conflict: ConflictPolicy,
+    journal: &Journal,
+) -> Result<Option<String>, AppError> {
+    if let Some(target) = journal.target(source) {
+        return Ok(Some(target));
+    }
+    let name = source_fs.stat(source)?.name;
+    let target = vfs::resolve_target(dest_fs, dest, &name, conflict)?;
+    if let Some(target) = &target {
+        journal.set_target(source, target);
+    }
+    Ok(target)
+}
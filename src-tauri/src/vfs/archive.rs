@@ -0,0 +1,114 @@
+use super::{io_error, temp_dir, FileWriter, Vfs};
+use crate::archive::{self, ArchiveCache, ArchivePath};
+use crate::fs::{AppError, ConflictPolicy, FileEntry};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+const SCHEME_PREFIX: &str = "zip://";
+
+/// Zip, tar and 7z archives as read-only folders, addressed as `zip://{archive}!/{inner}`
+/// or simply `{archive}!/{inner}`, which is how listings name their entries.
+pub struct ArchiveFs {
+    cache: ArchiveCache,
+}
+
+impl ArchiveFs {
+    pub fn new() -> Self {
+        Self {
+            cache: ArchiveCache::new(),
+        }
+    }
+
+    /// The archive location `path` refers to, with or without the scheme.
+    pub fn parse(path: &str) -> Option<ArchivePath> {
+        ArchivePath::parse(path.strip_prefix(SCHEME_PREFIX).unwrap_or(path))
+    }
+
+    fn archive_path(&self, path: &str) -> Result<ArchivePath, AppError> {
+        Self::parse(path).ok_or_else(|| AppError::NotFound(path.to_string()))
+    }
+}
+
+impl Default for ArchiveFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs for ArchiveFs {
+    fn scheme(&self) -> &'static str {
+        "zip"
+    }
+
+    fn handles(&self, path: &str) -> bool {
+        path.starts_with(SCHEME_PREFIX) || ArchivePath::parse(path).is_some()
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError> {
+        self.cache.list(&self.archive_path(path)?)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileEntry, AppError> {
+        self.cache.file_entry(&self.archive_path(path)?)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError> {
+        let file = self.fetch(path)?;
+        let file = File::open(&file).map_err(|e| io_error(path, e))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+
+    fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError> {
+        Err(archive::read_only(path))
+    }
+
+    fn rename(&self, from: &str, _to: &str) -> Result<(), AppError> {
+        Err(archive::read_only(from))
+    }
+
+    fn remove(&self, path: &str) -> Result<(), AppError> {
+        Err(archive::read_only(path))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), AppError> {
+        Err(archive::read_only(path))
+    }
+
+    fn parent(&self, path: &str) -> Option<String> {
+        let mut path = Self::parse(path)?;
+        if path.inner.is_empty() {
+            // The archive's root sits in the folder holding the archive
+            return path.archive.parent().map(|p| p.display().to_string());
+        }
+
+        path.inner = path
+            .inner
+            .rsplit_once('/')
+            .map_or(String::new(), |(parent, _)| parent.to_string());
+        Some(path.to_path_string())
+    }
+
+    fn check_writable(&self, path: &str) -> Result<(), AppError> {
+        Err(archive::read_only(path))
+    }
+
+    /// Extracts the entry into a temp folder rather than streaming it through a copy.
+    fn fetch(&self, path: &str) -> Result<PathBuf, AppError> {
+        let path = self.archive_path(path)?;
+        if path.inner.is_empty() {
+            return Ok(path.archive);
+        }
+
+        let dir = temp_dir();
+        archive::extract(
+            &path.archive,
+            path.format,
+            std::slice::from_ref(&path.inner),
+            &dir,
+            ConflictPolicy::Overwrite,
+            None,
+        )?;
+        Ok(dir.join(path.name()))
+    }
+}
@@ -0,0 +1,350 @@
+use super::{io_error, percent_decode, FileWriter, Vfs};
+use crate::fs::{list_directory, AppError, FileEntry};
+use crate::jobs::{Job, JobReader};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// Files from this size on are copied in chunks, so that cancelling and progress take
+/// effect within them.
+const CHUNKED_COPY_SIZE: u64 = 16 * 1024 * 1024;
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The local disk, for plain paths as well as `file://` URIs.
+pub struct LocalFs;
+
+impl LocalFs {
+    fn path(&self, path: &str) -> PathBuf {
+        let path = match path.strip_prefix("file://") {
+            Some(rest) => percent_decode(rest),
+            None => return PathBuf::from(path),
+        };
+
+        // `file:///C:/dir` names the drive path `C:/dir`
+        #[cfg(windows)]
+        let path = match path.strip_prefix('/') {
+            Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_string(),
+            _ => path,
+        };
+
+        PathBuf::from(path)
+    }
+}
+
+impl Vfs for LocalFs {
+    fn scheme(&self) -> &'static str {
+        "file"
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError> {
+        list_directory(&self.path(path))
+    }
+
+    fn stat(&self, path: &str) -> Result<FileEntry, AppError> {
+        let path = self.path(path);
+        let display = path.display().to_string();
+        // Broken links are reported as the link itself
+        let metadata = fs::metadata(&path)
+            .or_else(|_| fs::symlink_metadata(&path))
+            .map_err(|e| io_error(&display, e))?;
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| display.clone());
+        let mut entry = FileEntry::from_metadata(name, &path, &metadata);
+        entry.is_symlink = fs::symlink_metadata(&path).is_ok_and(|m| m.is_symlink());
+        Ok(entry)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError> {
+        let file = File::open(self.path(path)).map_err(|e| io_error(path, e))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+
+    fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError> {
+        let file = File::create(self.path(path)).map_err(|e| io_error(path, e))?;
+        Ok(Box::new(BufWriter::new(file)))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), AppError> {
+        fs::rename(self.path(from), self.path(to)).map_err(|e| io_error(from, e))
+    }
+
+    fn remove(&self, path: &str) -> Result<(), AppError> {
+        let local = self.path(path);
+        let result = if fs::symlink_metadata(&local).is_ok_and(|m| m.is_dir()) {
+            fs::remove_dir_all(&local)
+        } else {
+            fs::remove_file(&local)
+        };
+        result.map_err(|e| io_error(path, e))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), AppError> {
+        fs::create_dir(self.path(path)).map_err(|e| io_error(path, e))
+    }
+
+    fn parent(&self, path: &str) -> Option<String> {
+        self.path(path).parent().map(|p| p.display().to_string())
+    }
+
+    fn join(&self, dir: &str, name: &str) -> String {
+        self.path(dir).join(name).display().to_string()
+    }
+
+    fn local_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.path(path))
+    }
+}
+
+impl FileWriter for BufWriter<File> {
+    /// Also syncs the file to disk, so that it is complete once the journal says so.
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.into_inner().map_err(|e| e.into_error())?.sync_data()
+    }
+}
+
+/// Copies a file, or a folder with everything below it, on the local disk, and counts
+/// each file towards `job`.
+pub fn copy(source: &Path, target: &Path, job: Option<&Job>) -> Result<(), AppError> {
+    if source.is_dir() {
+        copy_dir_recursive(source, target, job)
+    } else {
+        copy_file(source, target, job)
+    }
+}
+
+fn copy_dir_recursive(src: &Path, dest: &Path, job: Option<&Job>) -> Result<(), AppError> {
+    fs::create_dir_all(dest).map_err(|e| AppError::Io(e.to_string()))?;
+
+    for entry in fs::read_dir(src).map_err(|e| AppError::Io(e.to_string()))? {
+        let entry = entry.map_err(|e| AppError::Io(e.to_string()))?;
+        let src_path = entry.path();
+        let dest_path = dest.join(entry.file_name());
+
+        if src_path.is_dir() {
+            copy_dir_recursive(&src_path, &dest_path, job)?;
+        } else {
+            copy_file(&src_path, &dest_path, job)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies a file through `copy_contents`. Files the job's journal has as completed from
+/// the same source are skipped, and ones a resumed operation left partway are continued.
+fn copy_file(source: &Path, target: &Path, job: Option<&Job>) -> Result<(), AppError> {
+    if let Some(job) = job {
+        job.check_cancelled()?;
+        job.start_item(source);
+    }
+    let journal = job.and_then(Job::journal);
+    let key = target.display().to_string();
+    let metadata = fs::metadata(source).map_err(|e| AppError::Io(e.to_string()))?;
+    let size = metadata.len();
+    let modified = FileEntry::from_metadata(String::new(), source, &metadata).modified;
+
+    // Unless the target changed since, e.g. because it was lost with a system crash
+    let completed = journal
+        .as_ref()
+        .is_some_and(|journal| journal.is_completed(&key, size, modified))
+        && fs::metadata(target).is_ok_and(|m| m.len() == size);
+    if completed {
+        if let Some(job) = job {
+            job.add_bytes(size);
+            job.finish_item();
+        }
+        return Ok(());
+    }
+
+    let copied = if journal.as_ref().is_some_and(|journal| journal.is_resumed()) {
+        resume_file(source, target, job)?
+    } else {
+        copy_contents(source, target, size, job)?
+    };
+
+    if let Some(journal) = &journal {
+        sync_file(target)?;
+        journal.complete(&key, copied, modified);
+    }
+    if let Some(job) = job {
+        job.finish_item();
+    }
+    Ok(())
+}
+
+/// Writes what the OS still holds of `path` to disk.
+fn sync_file(path: &Path) -> Result<(), AppError> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.sync_data())
+        .map_err(|e| AppError::Io(e.to_string()))
+}
+
+/// Continues a copy an earlier attempt left partway: what is already in `target` is kept
+/// if it matches the start of `source`, and the rest appended. Otherwise the file is
+/// copied over. Counts the bytes towards `job` and returns the size of the copy.
+fn resume_file(source: &Path, target: &Path, job: Option<&Job>) -> Result<u64, AppError> {
+    let size = fs::metadata(source)
+        .map_err(|e| AppError::Io(e.to_string()))?
+        .len();
+    let written = match fs::symlink_metadata(target) {
+        Ok(metadata) if metadata.is_file() && metadata.len() <= size => metadata.len(),
+        _ => 0,
+    };
+
+    let mut reader = File::open(source).map_err(|e| AppError::Io(e.to_string()))?;
+    if written == 0 || !same_prefix(&mut reader, target, written, job)? {
+        return copy_contents(source, target, size, job);
+    }
+    if let Some(job) = job {
+        job.add_bytes(written);
+    }
+
+    // The reader is at `written` now, where the rest starts
+    let mut writer = OpenOptions::new()
+        .append(true)
+        .open(target)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    let appended = copy_chunks(reader, &mut writer, job)?;
+    if let Ok(metadata) = fs::metadata(source) {
+        let _ = fs::set_permissions(target, metadata.permissions());
+    }
+    Ok(written + appended)
+}
+
+/// Copies `source` of `size` bytes over `target`, counting the bytes towards `job`. Small
+/// files are left to the OS; large ones are copied in chunks.
+fn copy_contents(
+    source: &Path,
+    target: &Path,
+    size: u64,
+    job: Option<&Job>,
+) -> Result<u64, AppError> {
+    if size < CHUNKED_COPY_SIZE {
+        let copied = fs::copy(source, target).map_err(|e| AppError::Io(e.to_string()))?;
+        if let Some(job) = job {
+            job.add_bytes(copied);
+        }
+        return Ok(copied);
+    }
+
+    let reader = File::open(source).map_err(|e| AppError::Io(e.to_string()))?;
+    let mut writer = File::create(target).map_err(|e| AppError::Io(e.to_string()))?;
+    let copied = copy_chunks(reader, &mut writer, job)?;
+    if let Ok(metadata) = fs::metadata(source) {
+        let _ = fs::set_permissions(target, metadata.permissions());
+    }
+    Ok(copied)
+}
+
+/// Copies the rest of `reader` to `writer` a chunk at a time, counting the bytes towards
+/// `job` and stopping once it is cancelled. The part written so far stays for resuming.
+fn copy_chunks(reader: File, writer: &mut File, job: Option<&Job>) -> Result<u64, AppError> {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, JobReader::new(reader, job));
+    io::copy(&mut reader, writer).map_err(|e| match job.map(Job::check_cancelled) {
+        Some(Err(cancelled)) => cancelled,
+        _ => AppError::Io(e.to_string()),
+    })
+}
+
+/// Whether the first `len` bytes of `reader` and `target` are the same, reading both in
+/// chunks so that cancelling takes effect during large files.
+fn same_prefix(
+    reader: &mut File,
+    target: &Path,
+    len: u64,
+    job: Option<&Job>,
+) -> Result<bool, AppError> {
+    let mut written = File::open(target).map_err(|e| AppError::Io(e.to_string()))?;
+    let mut expected = vec![0; CHUNK_SIZE];
+    let mut actual = vec![0; CHUNK_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        if let Some(job) = job {
+            job.check_cancelled()?;
+        }
+        let chunk = remaining.min(CHUNK_SIZE as u64) as usize;
+        reader
+            .read_exact(&mut expected[..chunk])
+            .and_then(|_| written.read_exact(&mut actual[..chunk]))
+            .map_err(|e| AppError::Io(e.to_string()))?;
+        if expected[..chunk] != actual[..chunk] {
+            return Ok(false);
+        }
+        remaining -= chunk as u64;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(source: &[u8], target: Option<&[u8]>) -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let (source_path, target_path) = (dir.path().join("source"), dir.path().join("target"));
+        fs::write(&source_path, source).unwrap();
+        if let Some(target) = target {
+            fs::write(&target_path, target).unwrap();
+        }
+        (dir, source_path, target_path)
+    }
+
+    #[test]
+    fn resume_appends_to_a_matching_start() {
+        let (_dir, source, target) = files(b"hello world", Some(b"hello"));
+        assert_eq!(resume_file(&source, &target, None).unwrap(), 11);
+        assert_eq!(fs::read(&target).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn resume_copies_over_a_different_start() {
+        let (_dir, source, target) = files(b"hello world", Some(b"jello"));
+        assert_eq!(resume_file(&source, &target, None).unwrap(), 11);
+        assert_eq!(fs::read(&target).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn resume_copies_over_a_longer_target() {
+        let (_dir, source, target) = files(b"hello", Some(b"hello world"));
+        assert_eq!(resume_file(&source, &target, None).unwrap(), 5);
+        assert_eq!(fs::read(&target).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn resume_copies_when_nothing_was_written() {
+        let (_dir, source, target) = files(b"hello", None);
+        assert_eq!(resume_file(&source, &target, None).unwrap(), 5);
+        assert_eq!(fs::read(&target).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn copies_large_files_in_chunks() {
+        let contents: Vec<u8> = (0..CHUNKED_COPY_SIZE + 12345).map(|i| i as u8).collect();
+        let (_dir, source, target) = files(&contents, Some(b"old"));
+        let copied = copy_contents(&source, &target, contents.len() as u64, None).unwrap();
+        assert_eq!(copied, contents.len() as u64);
+        assert_eq!(fs::read(&target).unwrap(), contents);
+    }
+
+    #[test]
+    fn same_prefix_compares_only_the_start() {
+        let (_dir, source, target) = files(b"hello world", Some(b"hello there"));
+        assert!(same_prefix(&mut File::open(&source).unwrap(), &target, 6, None).unwrap());
+        assert!(!same_prefix(&mut File::open(&source).unwrap(), &target, 7, None).unwrap());
+    }
+
+    #[test]
+    fn same_prefix_leaves_the_reader_after_the_start() {
+        let (_dir, source, target) = files(b"hello world", Some(b"hello"));
+        let mut reader = File::open(&source).unwrap();
+        assert!(same_prefix(&mut reader, &target, 5, None).unwrap());
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, " world");
+    }
+}
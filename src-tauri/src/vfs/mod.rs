@@ -0,0 +1,448 @@
+mod archive;
+mod local;
+mod plan;
+mod remote;
+mod s3;
+mod sftp;
+mod webdav;
+
+pub use archive::ArchiveFs;
+pub use local::LocalFs;
+pub use plan::{move_copies, plan_copy, CopyPlan};
+pub use remote::Credentials;
+pub use s3::S3Fs;
+pub use sftp::SftpFs;
+pub use webdav::WebDavFs;
+
+use crate::fs::{AppError, ConflictPolicy, FileEntry};
+use crate::jobs::{Job, JobReader};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long files opened from archives and remote providers are kept in the temp folder.
+const TEMP_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A filesystem the commands can work on, addressed by URI-style paths such as
+/// `sftp://user@host/dir`. Paths are passed through as the user sees them, scheme
+/// included, and each provider parses its own.
+pub trait Vfs: Send + Sync {
+    /// URI scheme without `://`, e.g. `sftp`.
+    fn scheme(&self) -> &'static str;
+
+    /// Whether `path` belongs to this provider.
+    fn handles(&self, path: &str) -> bool {
+        path.strip_prefix(self.scheme())
+            .is_some_and(|rest| rest.starts_with("://"))
+    }
+
+    /// Entries of a folder, sorted like `list_directory` sorts them.
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError>;
+
+    fn stat(&self, path: &str) -> Result<FileEntry, AppError>;
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError>;
+
+    /// Creates or truncates a file, which is only complete once its writer is finished.
+    fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError>;
+
+    /// Renames within this provider; moves between providers copy instead.
+    fn rename(&self, from: &str, to: &str) -> Result<(), AppError>;
+
+    /// Removes a file, or a folder with everything in it.
+    fn remove(&self, path: &str) -> Result<(), AppError>;
+
+    fn mkdir(&self, path: &str) -> Result<(), AppError>;
+
+    fn parent(&self, path: &str) -> Option<String>;
+
+    fn join(&self, dir: &str, name: &str) -> String {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+
+    /// Fails for paths that cannot be changed, before an operation starts half-way.
+    fn check_writable(&self, _path: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    /// The same file on the local disk, for providers that have one. Commands use it for
+    /// what only works locally, such as watching, the trash and tags.
+    fn local_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// A local file with the contents of `path`, downloaded to the temp folder if needed.
+    fn fetch(&self, path: &str) -> Result<PathBuf, AppError> {
+        if let Some(local) = self.local_path(path) {
+            return Ok(local);
+        }
+
+        let entry = self.stat(path)?;
+        let dir = temp_dir();
+        let target = dir.join(&entry.name);
+        let create = std::fs::create_dir_all(&dir).and_then(|()| std::fs::File::create(&target));
+        let mut file = create.map_err(|e| io_error(&target.display().to_string(), e))?;
+        io::copy(&mut self.open_read(path)?, &mut file).map_err(|e| io_error(path, e))?;
+        Ok(target)
+    }
+}
+
+/// A file being written through a provider.
+pub trait FileWriter: Write + Send {
+    /// Completes the file. Providers that upload whole files do so here, so dropping a
+    /// writer without finishing it may leave nothing written at all.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// A fresh folder below the system temp folder for files opened from elsewhere.
+pub fn temp_dir() -> PathBuf {
+    temp_root().join(uuid::Uuid::new_v4().to_string())
+}
+
+/// Removes the temp folders of files opened more than `TEMP_MAX_AGE` ago. Newer ones may
+/// still be open in another app, so they are left until a later run.
+pub fn clean_temp_dirs() {
+    let Ok(entries) = std::fs::read_dir(temp_root()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= TEMP_MAX_AGE);
+        if expired {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+fn temp_root() -> PathBuf {
+    std::env::temp_dir().join("filemanager")
+}
+
+/// Maps an IO error onto the `AppError` variant the frontend can act on.
+pub fn io_error(path: &str, e: io::Error) -> AppError {
+    match e.kind() {
+        io::ErrorKind::NotFound => AppError::NotFound(path.to_string()),
+        io::ErrorKind::PermissionDenied => AppError::PermissionDenied(path.to_string()),
+        _ => AppError::Io(format!("{}: {}", path, e)),
+    }
+}
+
+/// Providers by scheme, with the local disk claiming every path no other provider does.
+pub struct VfsRegistry {
+    providers: Vec<Arc<dyn Vfs>>,
+    local: Arc<dyn Vfs>,
+    credentials: Arc<Credentials>,
+}
+
+impl VfsRegistry {
+    pub fn new() -> Self {
+        let credentials = Arc::new(Credentials::new());
+        Self {
+            providers: vec![
+                Arc::new(ArchiveFs::new()),
+                Arc::new(SftpFs::new()),
+                Arc::new(WebDavFs::new(credentials.clone())),
+                Arc::new(S3Fs::new(credentials.clone())),
+            ],
+            local: Arc::new(LocalFs),
+            credentials,
+        }
+    }
+
+    /// Secrets of the WebDAV and S3 remotes.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    pub fn resolve(&self, path: &str) -> Arc<dyn Vfs> {
+        self.providers
+            .iter()
+            .find(|provider| provider.handles(path))
+            .cloned()
+            .unwrap_or_else(|| self.local.clone())
+    }
+}
+
+impl Default for VfsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fails unless `path` is an existing folder.
+pub fn ensure_dir(vfs: &dyn Vfs, path: &str) -> Result<(), AppError> {
+    if vfs.stat(path)?.is_dir {
+        Ok(())
+    } else {
+        Err(AppError::NotADirectory(path.to_string()))
+    }
+}
+
+/// Where an item called `name` goes in `dir` under `policy`, or `None` to skip it.
+pub fn resolve_target(
+    vfs: &dyn Vfs,
+    dir: &str,
+    name: &str,
+    policy: ConflictPolicy,
+) -> Result<Option<String>, AppError> {
+    let name = policy.resolve_name(name, |candidate| {
+        vfs.stat(&vfs.join(dir, candidate)).ok().map(|e| e.is_dir)
+    })?;
+    Ok(name.map(|name| vfs.join(dir, &name)))
+}
+
+/// Copies a file or folder tree to `target`, merging into a folder that already exists,
+/// and counts each file towards `job`. Files the job's journal has as completed from the
+/// same source are skipped; other providers cannot append, so a file left partway is
+/// copied again. A file that already exists is only replaced once its copy is complete.
+pub fn copy(
+    from: &dyn Vfs,
+    source: &str,
+    to: &dyn Vfs,
+    target: &str,
+    job: Option<&Job>,
+) -> Result<(), AppError> {
+    if let (Some(source), Some(target)) = (from.local_path(source), to.local_path(target)) {
+        return local::copy(&source, &target, job);
+    }
+
+    let entry = from.stat(source)?;
+    if entry.is_dir {
+        if !to.stat(target).is_ok_and(|e| e.is_dir) {
+            to.mkdir(target)?;
+        }
+        for child in from.list(source)? {
+            copy(from, &child.path, to, &to.join(target, &child.name), job)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(job) = job {
+        job.check_cancelled()?;
+        job.start_item(Path::new(source));
+    }
+    let journal = job.and_then(Job::journal);
+    let size = entry.size.unwrap_or(0);
+    let existing = to.stat(target).ok();
+    let completed = journal
+        .as_ref()
+        .is_some_and(|journal| journal.is_completed(target, size, entry.modified))
+        && existing.as_ref().is_some_and(|e| e.size == Some(size));
+    if completed {
+        if let Some(job) = job {
+            job.add_bytes(size);
+            job.finish_item();
+        }
+        return Ok(());
+    }
+
+    // Written next to a file being overwritten, so that failing keeps the original
+    let written = match (&existing, to.parent(target)) {
+        (Some(existing), Some(parent)) if !existing.is_dir => {
+            let name = target.rsplit(['/', '\\']).next().unwrap_or(target);
+            to.join(&parent, &format!(".{}.part", name))
+        }
+        _ => target.to_string(),
+    };
+    let mut reader = JobReader::new(from.open_read(source)?, job);
+    let result = to.open_write(&written).and_then(|mut writer| {
+        let copied = io::copy(&mut reader, &mut writer).map_err(|e| io_error(source, e))?;
+        // Also drops the writer, which must be closed before the file is removed
+        writer.finish().map_err(|e| io_error(&written, e))?;
+        Ok(copied)
+    });
+    let copied = match result {
+        Ok(copied) => copied,
+        Err(e) => {
+            // A partial file would pass for a complete one later
+            let _ = to.remove(&written);
+            return Err(e);
+        }
+    };
+    if written != target {
+        replace(to, &written, target)?;
+    }
+
+    if let Some(journal) = &journal {
+        journal.complete(target, copied, entry.modified);
+    }
+    if let Some(job) = job {
+        job.finish_item();
+    }
+    Ok(())
+}
+
+/// Renames the complete copy `written` over `target`. Providers that cannot rename onto
+/// an existing file get it removed first; should the rename still fail, `written` is kept
+/// as the only complete copy.
+fn replace(to: &dyn Vfs, written: &str, target: &str) -> Result<(), AppError> {
+    if to.rename(written, target).is_ok() {
+        return Ok(());
+    }
+    to.remove(target)?;
+    to.rename(written, target)
+}
+
+/// Moves a file or folder tree, renaming where possible and copying otherwise.
+pub fn move_item(
+    from: &dyn Vfs,
+    source: &str,
+    to: &dyn Vfs,
+    target: &str,
+    job: Option<&Job>,
+) -> Result<(), AppError> {
+    // Renames fail across volumes or hosts of one scheme too, which the copy handles
+    if from.scheme() == to.scheme() && from.rename(source, target).is_ok() {
+        return Ok(());
+    }
+
+    copy(from, source, to, target, job)?;
+    from.remove(source)
+}
+
+/// Escapes everything but unreserved characters, for one segment of a URI path or a
+/// query value.
+pub fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes `%20` style escapes of a URI path, leaving malformed ones as they are.
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// The local disk as if it were a remote provider, so copies take the provider path.
+    /// With `fail_reads`, reading stops with an error partway.
+    struct Remote {
+        fail_reads: bool,
+    }
+
+    impl Vfs for Remote {
+        fn scheme(&self) -> &'static str {
+            "remote"
+        }
+
+        fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError> {
+            LocalFs.list(path)
+        }
+
+        fn stat(&self, path: &str) -> Result<FileEntry, AppError> {
+            LocalFs.stat(path)
+        }
+
+        fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError> {
+            if !self.fail_reads {
+                return LocalFs.open_read(path);
+            }
+            struct Broken;
+            impl Read for Broken {
+                fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                    Err(io::Error::other("connection lost"))
+                }
+            }
+            Ok(Box::new(io::Read::chain(&b"partial"[..], Broken)))
+        }
+
+        fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError> {
+            LocalFs.open_write(path)
+        }
+
+        fn rename(&self, from: &str, to: &str) -> Result<(), AppError> {
+            LocalFs.rename(from, to)
+        }
+
+        fn remove(&self, path: &str) -> Result<(), AppError> {
+            LocalFs.remove(path)
+        }
+
+        fn mkdir(&self, path: &str) -> Result<(), AppError> {
+            LocalFs.mkdir(path)
+        }
+
+        fn parent(&self, path: &str) -> Option<String> {
+            LocalFs.parent(path)
+        }
+    }
+
+    fn files(dir: &Path) -> (String, String) {
+        let (source, target) = (dir.join("source.txt"), dir.join("target.txt"));
+        fs::write(&source, "new contents").unwrap();
+        fs::write(&target, "original").unwrap();
+        (source.display().to_string(), target.display().to_string())
+    }
+
+    #[test]
+    fn overwrites_an_existing_file_once_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = files(dir.path());
+        let remote = Remote { fail_reads: false };
+
+        copy(&remote, &source, &remote, &target, None).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new contents");
+        assert!(!dir.path().join(".target.txt.part").exists());
+    }
+
+    #[test]
+    fn failed_copy_keeps_the_file_it_would_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = files(dir.path());
+        let remote = Remote { fail_reads: true };
+
+        assert!(copy(&remote, &source, &remote, &target, None).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "original");
+        assert!(!dir.path().join(".target.txt.part").exists());
+    }
+
+    #[test]
+    fn failed_copy_removes_the_file_it_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, _) = files(dir.path());
+        let target = dir.path().join("new.txt").display().to_string();
+        let remote = Remote { fail_reads: true };
+
+        assert!(copy(&remote, &source, &remote, &target, None).is_err());
+        assert!(!Path::new(&target).exists());
+    }
+}
@@ -0,0 +1,381 @@
+//! Pre-flight checks for copies: how much data they involve and whether the destination
+//! can hold it, before anything is written.
+
+use super::Vfs;
+use crate::fs::mounts::filesystem_type;
+use crate::fs::volumes::disk_space;
+use crate::fs::{AppError, ConflictPolicy, FileEntry};
+use crate::jobs::Job;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// What a copy involves and what stands in its way.
+#[derive(Debug, Clone, Serialize)]
+pub struct CopyPlan {
+    pub total_bytes: u64,
+    /// Files to copy; folders are not counted.
+    pub total_items: usize,
+    /// Size of the files at the destination the copy replaces, which frees their space.
+    pub overwritten_bytes: u64,
+    /// Free space at the destination, where it can be determined.
+    pub free_bytes: Option<u64>,
+    pub fs_type: Option<String>,
+    /// Largest file the destination filesystem can store, where it has a limit.
+    pub max_file_size: Option<u64>,
+    pub problems: Vec<PlanProblem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlanProblem {
+    InsufficientSpace {
+        needed_bytes: u64,
+        free_bytes: u64,
+    },
+    /// A file larger than the destination filesystem can store.
+    FileTooLarge {
+        path: String,
+        size: u64,
+        max_file_size: u64,
+    },
+}
+
+impl CopyPlan {
+    /// Space the copy takes up at the destination.
+    pub fn needed_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.overwritten_bytes)
+    }
+
+    /// Refuses the copy if anything would make it fail partway. Callers that were told
+    /// about the problems and want to go ahead anyway skip this.
+    pub fn check(&self) -> Result<(), AppError> {
+        let message = match self.problems.first() {
+            None => return Ok(()),
+            Some(PlanProblem::InsufficientSpace {
+                needed_bytes,
+                free_bytes,
+            }) => format!(
+                "Not enough free space: {} needed, {} available",
+                format_bytes(*needed_bytes),
+                format_bytes(*free_bytes)
+            ),
+            Some(PlanProblem::FileTooLarge {
+                path,
+                max_file_size,
+                ..
+            }) => format!(
+                "{} is too large for the destination, which holds files up to {}",
+                path,
+                format_bytes(*max_file_size)
+            ),
+        };
+        Err(AppError::InvalidOperation(message))
+    }
+
+    /// Accounts for `written_bytes` an earlier attempt already put at the destination,
+    /// which resuming does not need room for again. When overwriting, those files are
+    /// already counted as replaced.
+    pub fn resume(&mut self, written_bytes: u64) {
+        let needed = self
+            .total_bytes
+            .saturating_sub(self.overwritten_bytes.max(written_bytes));
+        self.problems.retain_mut(|problem| match problem {
+            PlanProblem::InsufficientSpace {
+                needed_bytes,
+                free_bytes,
+            } => {
+                *needed_bytes = needed;
+                *free_bytes < needed
+            }
+            PlanProblem::FileTooLarge { .. } => true,
+        });
+    }
+
+    /// Counts a file to copy, which replaces the file at `target` if there is one.
+    fn add_file(&mut self, path: &str, size: u64, target: Option<&Path>) {
+        self.total_items += 1;
+        self.total_bytes += size;
+        if let Some(replaced) = target.and_then(|target| target.metadata().ok()) {
+            if replaced.is_file() {
+                self.overwritten_bytes += replaced.len();
+            }
+        }
+        if let Some(max_file_size) = self.max_file_size.filter(|&max| size > max) {
+            self.problems.push(PlanProblem::FileTooLarge {
+                path: path.to_string(),
+                size,
+                max_file_size,
+            });
+        }
+    }
+}
+
+/// Totals `sources` and checks them against the space and file size limit at `dest`.
+/// Both are only known for local destinations; elsewhere the plan just has the totals.
+/// With `ConflictPolicy::Overwrite` the files a copy replaces count towards free space.
+pub fn plan_copy(
+    sources: &[(Arc<dyn Vfs>, String)],
+    dest_fs: &dyn Vfs,
+    dest: &str,
+    conflict: ConflictPolicy,
+    job: Option<&Job>,
+) -> Result<CopyPlan, AppError> {
+    let dest_path = dest_fs.local_path(dest);
+    let fs_type = dest_path.as_deref().and_then(filesystem_type);
+    let mut plan = CopyPlan {
+        total_bytes: 0,
+        total_items: 0,
+        overwritten_bytes: 0,
+        free_bytes: dest_path
+            .as_deref()
+            .and_then(disk_space)
+            .map(|space| space.free_bytes),
+        max_file_size: fs_type.as_deref().and_then(max_file_size),
+        fs_type,
+        problems: Vec::new(),
+    };
+
+    // Targets are only looked at where free space is known
+    let replaced_in = dest_path.filter(|_| conflict == ConflictPolicy::Overwrite);
+    for (source_fs, source) in sources {
+        match source_fs.local_path(source) {
+            Some(path) => {
+                let target = replaced_in
+                    .as_ref()
+                    .zip(path.file_name())
+                    .map(|(dir, name)| dir.join(name));
+                measure_local(&path, target.as_deref(), &mut plan, job)?
+            }
+            None => {
+                let entry = source_fs.stat(source)?;
+                let target = replaced_in.as_ref().map(|dir| dir.join(&entry.name));
+                measure(&**source_fs, entry, target, &mut plan, job)?
+            }
+        }
+    }
+
+    let needed_bytes = plan.needed_bytes();
+    if let Some(free_bytes) = plan.free_bytes.filter(|&free| free < needed_bytes) {
+        plan.problems.insert(
+            0,
+            PlanProblem::InsufficientSpace {
+                needed_bytes,
+                free_bytes,
+            },
+        );
+    }
+    Ok(plan)
+}
+
+/// Whether moving `source` to `dest` means copying the data rather than renaming it.
+pub fn move_copies(from: &dyn Vfs, source: &str, to: &dyn Vfs, dest: &str) -> bool {
+    match (from.local_path(source), to.local_path(dest)) {
+        (Some(source), Some(dest)) => !same_volume(&source, &dest),
+        _ => from.scheme() != to.scheme(),
+    }
+}
+
+/// Largest file size, in bytes, filesystems with a small limit can store.
+pub fn max_file_size(fs_type: &str) -> Option<u64> {
+    match fs_type.to_ascii_lowercase().as_str() {
+        "vfat" | "msdos" | "fat32" => Some(4 * GIB - 1),
+        // Windows reports FAT16 as plain `FAT`
+        "fat" | "hfs" => Some(2 * GIB - 1),
+        _ => None,
+    }
+}
+
+/// Follows links like the local copy does. Entries that cannot be read are left out;
+/// the copy reports them itself. `target` is where `path` would be copied to, if the
+/// files there are to be counted as replaced.
+fn measure_local(
+    path: &Path,
+    target: Option<&Path>,
+    plan: &mut CopyPlan,
+    job: Option<&Job>,
+) -> Result<(), AppError> {
+    if path.symlink_metadata().is_err() {
+        return Err(AppError::NotFound(path.display().to_string()));
+    }
+
+    for entry in walkdir::WalkDir::new(path)
+        .follow_links(true)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(job) = job {
+            job.check_cancelled()?;
+        }
+        if entry.file_type().is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            // Joining an empty path would add a trailing slash to a single file's target
+            let replaced = match (target, entry.path().strip_prefix(path)) {
+                (Some(target), Ok(relative)) if relative.as_os_str().is_empty() => {
+                    Some(target.to_path_buf())
+                }
+                (Some(target), Ok(relative)) => Some(target.join(relative)),
+                _ => None,
+            };
+            let name = entry.path().display().to_string();
+            plan.add_file(&name, size, replaced.as_deref());
+        }
+    }
+    Ok(())
+}
+
+fn measure(
+    vfs: &dyn Vfs,
+    entry: FileEntry,
+    target: Option<PathBuf>,
+    plan: &mut CopyPlan,
+    job: Option<&Job>,
+) -> Result<(), AppError> {
+    if let Some(job) = job {
+        job.check_cancelled()?;
+    }
+    if !entry.is_dir {
+        plan.add_file(&entry.path, entry.size.unwrap_or(0), target.as_deref());
+        return Ok(());
+    }
+
+    for child in vfs.list(&entry.path)? {
+        let child_target = target.as_ref().map(|dir| dir.join(&child.name));
+        measure(vfs, child, child_target, plan, job)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn same_volume(source: &Path, dest: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (source.symlink_metadata(), dest.metadata()) {
+        (Ok(source), Ok(dest)) => source.dev() == dest.dev(),
+        _ => false,
+    }
+}
+
+/// Compares drive letters or UNC shares, which is what renames cannot cross.
+#[cfg(not(unix))]
+fn same_volume(source: &Path, dest: &Path) -> bool {
+    let root = |path: &Path| {
+        path.components()
+            .next()
+            .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+    };
+    root(source).is_some() && root(source) == root(dest)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::LocalFs;
+    use std::fs;
+
+    fn plan(source: &Path, dest: &Path, conflict: ConflictPolicy) -> CopyPlan {
+        let sources: Vec<(Arc<dyn Vfs>, String)> =
+            vec![(Arc::new(LocalFs), source.display().to_string())];
+        plan_copy(
+            &sources,
+            &LocalFs,
+            &dest.display().to_string(),
+            conflict,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn counts_files_overwritten_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, dest) = (dir.path().join("source"), dir.path().join("dest"));
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::create_dir_all(dest.join("source").join("sub")).unwrap();
+        fs::write(source.join("a.bin"), [0; 100]).unwrap();
+        fs::write(source.join("sub").join("b.bin"), [0; 50]).unwrap();
+        fs::write(dest.join("source").join("sub").join("b.bin"), [0; 30]).unwrap();
+
+        let overwrite = plan(&source, &dest, ConflictPolicy::Overwrite);
+        assert_eq!(overwrite.total_items, 2);
+        assert_eq!(overwrite.total_bytes, 150);
+        assert_eq!(overwrite.overwritten_bytes, 30);
+        assert_eq!(overwrite.needed_bytes(), 120);
+
+        let rename = plan(&source, &dest, ConflictPolicy::Rename);
+        assert_eq!(rename.overwritten_bytes, 0);
+        assert_eq!(rename.needed_bytes(), 150);
+    }
+
+    #[test]
+    fn counts_a_single_overwritten_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.bin");
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        fs::write(&source, [0; 100]).unwrap();
+        fs::write(dest.join("a.bin"), [0; 80]).unwrap();
+
+        let plan = plan(&source, &dest, ConflictPolicy::Overwrite);
+        assert_eq!(plan.overwritten_bytes, 80);
+        assert_eq!(plan.needed_bytes(), 20);
+    }
+
+    #[test]
+    fn resuming_overwrites_does_not_count_written_files_twice() {
+        let mut plan = CopyPlan {
+            total_bytes: 1000,
+            total_items: 2,
+            overwritten_bytes: 600,
+            free_bytes: Some(300),
+            fs_type: None,
+            max_file_size: None,
+            problems: vec![PlanProblem::InsufficientSpace {
+                needed_bytes: 400,
+                free_bytes: 300,
+            }],
+        };
+        plan.resume(500);
+        assert_eq!(plan.problems.len(), 1);
+
+        plan.overwritten_bytes = 0;
+        plan.resume(800);
+        assert!(plan.problems.is_empty());
+        assert!(plan.check().is_ok());
+    }
+
+    #[test]
+    fn refuses_files_too_large_for_fat() {
+        assert_eq!(max_file_size("vfat"), Some(4 * GIB - 1));
+        assert_eq!(max_file_size("FAT"), Some(2 * GIB - 1));
+        assert_eq!(max_file_size("ext4"), None);
+
+        let mut plan = CopyPlan {
+            total_bytes: 0,
+            total_items: 0,
+            overwritten_bytes: 0,
+            free_bytes: None,
+            fs_type: Some("vfat".into()),
+            max_file_size: max_file_size("vfat"),
+            problems: Vec::new(),
+        };
+        plan.add_file("big.iso", 5 * GIB, None);
+        assert!(plan.check().is_err());
+    }
+}
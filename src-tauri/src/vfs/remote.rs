@@ -0,0 +1,261 @@
+use super::{temp_dir, FileWriter};
+use crate::commands::config::load_config;
+use crate::fs::{AppError, RemoteConfig, RemoteKind};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Keyring service the secrets of remotes are stored under, one entry per remote name.
+const KEYRING_SERVICE: &str = "com.reclamegraag.filemanager";
+
+/// Passwords and secret keys of remotes, read from the OS keyring once and then cached.
+pub struct Credentials {
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl Credentials {
+    pub fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The secret stored for `remote`, if any.
+    pub fn get(&self, remote: &str) -> Result<Option<String>, AppError> {
+        if let Some(secret) = self.cache.lock().get(remote) {
+            return Ok(secret.clone());
+        }
+
+        let secret = match entry(remote)?.get_password() {
+            Ok(secret) => Some(secret),
+            Err(keyring::Error::NoEntry) => None,
+            Err(e) => return Err(keyring_error(e)),
+        };
+        self.cache.lock().insert(remote.to_string(), secret.clone());
+        Ok(secret)
+    }
+
+    pub fn set(&self, remote: &str, secret: &str) -> Result<(), AppError> {
+        entry(remote)?.set_password(secret).map_err(keyring_error)?;
+        self.cache
+            .lock()
+            .insert(remote.to_string(), Some(secret.to_string()));
+        Ok(())
+    }
+
+    pub fn forget(&self, remote: &str) -> Result<(), AppError> {
+        match entry(remote)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(keyring_error(e)),
+        }
+        self.cache.lock().insert(remote.to_string(), None);
+        Ok(())
+    }
+}
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn entry(remote: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, remote).map_err(keyring_error)
+}
+
+fn keyring_error(e: keyring::Error) -> AppError {
+    AppError::Io(format!("Keyring: {}", e))
+}
+
+/// The configured remote called `name`, which must be of `kind`.
+pub fn find_remote(name: &str, kind: RemoteKind) -> Result<RemoteConfig, AppError> {
+    load_config()
+        .remotes
+        .into_iter()
+        .find(|remote| remote.name == name && remote.kind == kind)
+        .ok_or_else(|| AppError::NotFound(format!("No remote named {}", name)))
+}
+
+/// Splits `{scheme}://{remote}/{rest}` into the remote's name and the rest without
+/// surrounding slashes.
+pub fn split_path<'a>(path: &'a str, prefix: &str) -> Result<(&'a str, &'a str), AppError> {
+    let rest = path
+        .strip_prefix(prefix)
+        .ok_or_else(|| AppError::NotFound(path.to_string()))?;
+    let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    if name.is_empty() {
+        return Err(AppError::InvalidOperation(format!(
+            "Missing remote name in {}",
+            path
+        )));
+    }
+    Ok((name, rest.trim_matches('/')))
+}
+
+/// The parent of a `{scheme}://{remote}/{rest}` path, or `None` at the remote's root.
+pub fn parent_path(path: &str, prefix: &str) -> Option<String> {
+    let (name, rest) = split_path(path, prefix).ok()?;
+    if rest.is_empty() {
+        return None;
+    }
+    let parent = rest.rsplit_once('/').map_or("", |(parent, _)| parent);
+    Some(
+        format!("{}{}/{}", prefix, name, parent)
+            .trim_end_matches('/')
+            .to_string(),
+    )
+}
+
+/// An HTTP client that keeps connections to each server open between requests.
+pub fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(60))
+        .build()
+}
+
+/// Maps a failed request onto the `AppError` variant the frontend can act on.
+pub fn http_error(path: &str, e: ureq::Error) -> AppError {
+    match e {
+        ureq::Error::Status(404, _) => AppError::NotFound(path.to_string()),
+        ureq::Error::Status(401 | 403, _) => AppError::PermissionDenied(path.to_string()),
+        ureq::Error::Status(code, response) => {
+            AppError::Io(format!("{}: {} {}", path, code, response.status_text()))
+        }
+        ureq::Error::Transport(transport) => AppError::Io(format!("{}: {}", path, transport)),
+    }
+}
+
+/// Parses `Last-Modified` style dates into seconds since the epoch.
+pub fn parse_http_date(text: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(text.trim())
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+/// The text of the first element called `name` below `node`, at any depth.
+pub fn xml_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.descendants()
+        .filter(|child| child.tag_name().name() == name)
+        .find_map(|child| child.text())
+}
+
+type SendUpload = Box<dyn FnOnce(File, u64) -> Result<(), AppError> + Send>;
+
+/// Collects what is written in a temp file and uploads it on `finish`, as both WebDAV
+/// servers and S3 want to know an upload's length before it starts. Dropping it
+/// unfinished uploads nothing.
+pub struct Upload {
+    file: Option<File>,
+    path: PathBuf,
+    len: u64,
+    send: Option<SendUpload>,
+}
+
+impl Upload {
+    pub fn new(
+        send: impl FnOnce(File, u64) -> Result<(), AppError> + Send + 'static,
+    ) -> Result<Self, AppError> {
+        let dir = temp_dir();
+        let path = dir.join("upload");
+        let file = fs::create_dir_all(&dir)
+            .and_then(|()| {
+                File::options()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&path)
+            })
+            .map_err(|e| AppError::Io(e.to_string()))?;
+
+        Ok(Self {
+            file: Some(file),
+            path,
+            len: 0,
+            send: Some(Box::new(send)),
+        })
+    }
+
+    fn file(&mut self) -> io::Result<&mut File> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| io::Error::other("Upload already finished"))
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file()?.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
+
+impl FileWriter for Upload {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let send = self
+            .send
+            .take()
+            .ok_or_else(|| io::Error::other("Upload already finished"))?;
+        let len = self.len;
+        let file = self.file()?;
+        file.flush()?;
+        file.seek(SeekFrom::Start(0))?;
+        let file = file.try_clone()?;
+        send(file, len).map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        // Closed first, since Windows cannot remove open files
+        self.file = None;
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_remote_names_from_paths() {
+        assert_eq!(
+            split_path("s3://minio/photos/2024/", "s3://").unwrap(),
+            ("minio", "photos/2024")
+        );
+        assert_eq!(split_path("s3://minio", "s3://").unwrap(), ("minio", ""));
+        assert!(split_path("s3:///photos", "s3://").is_err());
+        assert!(split_path("webdav://cloud", "s3://").is_err());
+    }
+
+    #[test]
+    fn finds_parents() {
+        assert_eq!(
+            parent_path("s3://minio/photos/2024", "s3://").as_deref(),
+            Some("s3://minio/photos")
+        );
+        assert_eq!(
+            parent_path("s3://minio/photos", "s3://").as_deref(),
+            Some("s3://minio")
+        );
+        assert_eq!(parent_path("s3://minio", "s3://"), None);
+    }
+
+    #[test]
+    fn finds_text_at_any_depth() {
+        let document = roxmltree::Document::parse("<a><b><c/></b><b><c>text</c></b></a>").unwrap();
+        assert_eq!(xml_text(document.root_element(), "c"), Some("text"));
+        assert_eq!(xml_text(document.root_element(), "d"), None);
+    }
+}
@@ -0,0 +1,544 @@
+use super::{percent_decode, percent_encode, FileWriter, Vfs};
+use crate::commands::config::{config_modified, load_config};
+use crate::fs::{expand_home, sort_entries, AppError, FileEntry, SftpSettings};
+use parking_lot::Mutex;
+use ssh2::{CheckResult, ErrorCode, FileStat, KnownHostFileKind, Session, Sftp};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Once, Weak};
+use std::time::{Duration, Instant};
+
+const SCHEME_PREFIX: &str = "sftp://";
+const DEFAULT_PORT: u16 = 22;
+/// Keys tried after the configured ones, like OpenSSH does.
+const DEFAULT_IDENTITIES: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+// SFTP status codes from the protocol draft
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+
+type Pool = Mutex<HashMap<String, Arc<Connection>>>;
+
+/// Folders on SSH servers, addressed as `sftp://user@host:port/path`. The user defaults
+/// to the local one, the port to 22 and an empty path to the home folder on the server.
+pub struct SftpFs {
+    pool: Arc<Pool>,
+    keepalive: Once,
+}
+
+impl SftpFs {
+    pub fn new() -> Self {
+        Self {
+            pool: Arc::new(Mutex::new(HashMap::new())),
+            keepalive: Once::new(),
+        }
+    }
+
+    fn connection(&self, location: &Location) -> Result<Arc<Connection>, AppError> {
+        if let Some(connection) = self.pool.lock().get(&location.key()) {
+            return Ok(connection.clone());
+        }
+
+        // Connected outside the lock so a slow host does not hold up the others
+        let connection = Arc::new(Connection::open(location, &load_config().sftp)?);
+        self.pool.lock().insert(location.key(), connection.clone());
+        self.keepalive
+            .call_once(|| spawn_keepalive(Arc::downgrade(&self.pool)));
+        Ok(connection)
+    }
+
+    /// Runs `op` with the remote path `path` refers to, reconnecting once if the pooled
+    /// connection turns out to have been dropped by the server.
+    fn with_sftp<T>(
+        &self,
+        path: &str,
+        op: impl Fn(&Sftp, &Location, &str) -> Result<T, ssh2::Error>,
+    ) -> Result<T, AppError> {
+        let location = Location::parse(path)?;
+        let attempt = || -> Result<Result<T, ssh2::Error>, AppError> {
+            let connection = self.connection(&location)?;
+            *connection.last_used.lock() = Instant::now();
+            let remote = match location.path.as_str() {
+                "" => connection.home.as_str(),
+                remote => remote,
+            };
+            Ok(op(&connection.sftp, &location, remote))
+        };
+
+        match attempt()? {
+            Err(e) if matches!(e.code(), ErrorCode::Session(_)) => {
+                self.pool.lock().remove(&location.key());
+                attempt()?
+            }
+            result => result,
+        }
+        .map_err(|e| sftp_error(path, e))
+    }
+
+    /// The location of `path` with its remote path, which for the home folder is only
+    /// known once connected.
+    fn absolute(&self, path: &str) -> Result<(Location, String), AppError> {
+        let location = Location::parse(path)?;
+        let remote = match location.path.as_str() {
+            "" => self.with_sftp(path, |_, _, remote| Ok(remote.to_string()))?,
+            remote => remote.to_string(),
+        };
+        Ok((location, remote))
+    }
+}
+
+impl Default for SftpFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs for SftpFs {
+    fn scheme(&self) -> &'static str {
+        "sftp"
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError> {
+        let mut entries = self.with_sftp(path, |sftp, location, remote| {
+            let mut entries = Vec::new();
+            for (child, stat) in sftp.readdir(Path::new(remote))? {
+                let is_symlink = stat.file_type().is_symlink();
+                // Links are listed as themselves; their targets tell whether they open
+                // like folders
+                let stat = if is_symlink {
+                    sftp.stat(&child).unwrap_or(stat)
+                } else {
+                    stat
+                };
+                entries.push(to_entry(
+                    location,
+                    &child.to_string_lossy(),
+                    &stat,
+                    is_symlink,
+                ));
+            }
+            Ok(entries)
+        })?;
+        sort_entries(&mut entries);
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileEntry, AppError> {
+        self.with_sftp(path, |sftp, location, remote| {
+            let remote_path = Path::new(remote);
+            let link = sftp.lstat(remote_path)?;
+            let is_symlink = link.file_type().is_symlink();
+            // Broken links are reported as the link itself
+            let stat = if is_symlink {
+                sftp.stat(remote_path).unwrap_or(link)
+            } else {
+                link
+            };
+            Ok(to_entry(location, remote, &stat, is_symlink))
+        })
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError> {
+        let file = self.with_sftp(path, |sftp, _, remote| sftp.open(Path::new(remote)))?;
+        Ok(Box::new(file))
+    }
+
+    fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError> {
+        let file = self.with_sftp(path, |sftp, _, remote| sftp.create(Path::new(remote)))?;
+        Ok(Box::new(file))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), AppError> {
+        let target = Location::parse(to)?;
+        if Location::parse(from)?.key() != target.key() {
+            return Err(AppError::InvalidOperation(format!(
+                "Cannot rename across hosts: {}",
+                to
+            )));
+        }
+
+        self.with_sftp(from, |sftp, _, remote| {
+            sftp.rename(Path::new(remote), Path::new(&target.path), None)
+        })
+    }
+
+    fn remove(&self, path: &str) -> Result<(), AppError> {
+        self.with_sftp(path, |sftp, _, remote| remove_tree(sftp, Path::new(remote)))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), AppError> {
+        self.with_sftp(path, |sftp, _, remote| sftp.mkdir(Path::new(remote), 0o755))
+    }
+
+    fn parent(&self, path: &str) -> Option<String> {
+        let (location, remote) = self.absolute(path).ok()?;
+        let parent = Path::new(&remote).parent()?;
+        Some(location.uri(&parent.to_string_lossy()))
+    }
+
+    fn join(&self, dir: &str, name: &str) -> String {
+        match self.absolute(dir) {
+            Ok((location, remote)) => {
+                location.uri(&format!("{}/{}", remote.trim_end_matches('/'), name))
+            }
+            Err(_) => format!("{}/{}", dir.trim_end_matches('/'), name),
+        }
+    }
+}
+
+impl FileWriter for ssh2::File {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Where an `sftp://` path points, split into its parts.
+struct Location {
+    user: String,
+    host: String,
+    port: u16,
+    /// Absolute path on the server, or empty for the user's home folder.
+    path: String,
+}
+
+impl Location {
+    fn parse(uri: &str) -> Result<Self, AppError> {
+        let rest = uri
+            .strip_prefix(SCHEME_PREFIX)
+            .ok_or_else(|| AppError::NotFound(uri.to_string()))?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+
+        let (user, address) = match authority.rsplit_once('@') {
+            // Escaped, since user names like `jane@example.com` carry an `@` of their own
+            Some((user, address)) => (percent_decode(user), address),
+            None => (local_user(), authority),
+        };
+        // IPv6 addresses are bracketed so their colons are not taken for the port
+        let (host, port) = match address.strip_prefix('[') {
+            Some(bracketed) => match bracketed.split_once(']') {
+                Some((host, port)) => (host, port.strip_prefix(':')),
+                None => (bracketed, None),
+            },
+            None => match address.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (address, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| AppError::InvalidOperation(format!("Invalid port in {}", uri)))?,
+            None => DEFAULT_PORT,
+        };
+
+        if host.is_empty() {
+            return Err(AppError::InvalidOperation(format!(
+                "Missing host in {}",
+                uri
+            )));
+        }
+
+        // `sftp://host/` names the server's root, `sftp://host` the home folder
+        let path = match path.trim_end_matches('/') {
+            "" if !path.is_empty() => "/",
+            trimmed => trimmed,
+        };
+
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Identifies the connection in the pool.
+    fn key(&self) -> String {
+        format!("{}@{}:{}", self.user, self.host, self.port)
+    }
+
+    /// The URI of `remote` on this location's server.
+    fn uri(&self, remote: &str) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let port = match self.port {
+            DEFAULT_PORT => String::new(),
+            port => format!(":{}", port),
+        };
+        format!(
+            "{}{}@{}{}{}",
+            SCHEME_PREFIX,
+            percent_encode(&self.user),
+            host,
+            port,
+            remote
+        )
+    }
+}
+
+fn local_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// One authenticated SSH session with its SFTP channel, shared by every request to the
+/// same user, host and port.
+struct Connection {
+    session: Session,
+    sftp: Sftp,
+    home: String,
+    last_used: Mutex<Instant>,
+}
+
+impl Connection {
+    fn open(location: &Location, settings: &SftpSettings) -> Result<Self, AppError> {
+        let key = location.key();
+        let failed = |e: &dyn std::fmt::Display| AppError::Io(format!("{}: {}", key, e));
+        let timeout = Duration::from_secs(settings.timeout_secs.max(1));
+
+        let address = (location.host.as_str(), location.port)
+            .to_socket_addrs()
+            .map_err(|e| failed(&e))?
+            .next()
+            .ok_or_else(|| failed(&"Host has no address"))?;
+        let tcp = TcpStream::connect_timeout(&address, timeout).map_err(|e| failed(&e))?;
+
+        let mut session = Session::new().map_err(|e| failed(&e))?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(timeout.as_millis() as u32);
+        session.handshake().map_err(|e| failed(&e))?;
+        verify_host_key(&session, location)?;
+        authenticate(&session, location, settings)?;
+        session.set_keepalive(false, settings.keepalive_secs as u32);
+
+        let sftp = session.sftp().map_err(|e| failed(&e))?;
+        let home = sftp
+            .realpath(Path::new("."))
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "/".to_string());
+
+        Ok(Self {
+            session,
+            sftp,
+            home,
+            last_used: Mutex::new(Instant::now()),
+        })
+    }
+}
+
+/// Accepts only hosts whose key is already in `~/.ssh/known_hosts`, as `ssh` would
+/// after its first connection.
+fn verify_host_key(session: &Session, location: &Location) -> Result<(), AppError> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| AppError::Io(format!("{}: No host key", location.key())))?;
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    if let Some(home) = dirs::home_dir() {
+        let _ = known_hosts.read_file(
+            &home.join(".ssh").join("known_hosts"),
+            KnownHostFileKind::OpenSSH,
+        );
+    }
+
+    match known_hosts.check_port(&location.host, location.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(AppError::PermissionDenied(format!(
+            "Host key of {} does not match known_hosts",
+            location.host
+        ))),
+        CheckResult::NotFound | CheckResult::Failure => Err(AppError::PermissionDenied(format!(
+            "{} is not in known_hosts; connect once with ssh to trust it",
+            location.host
+        ))),
+    }
+}
+
+fn authenticate(
+    session: &Session,
+    location: &Location,
+    settings: &SftpSettings,
+) -> Result<(), AppError> {
+    if session.userauth_agent(&location.user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+
+    let ssh_dir = dirs::home_dir().map(|home| home.join(".ssh"));
+    let identities = settings
+        .identity_files
+        .iter()
+        .map(|file| expand_home(file))
+        .chain(
+            DEFAULT_IDENTITIES
+                .iter()
+                .filter_map(|name| Some(ssh_dir.as_ref()?.join(name))),
+        )
+        .filter(|file| file.is_file());
+
+    for identity in identities {
+        let accepted = session
+            .userauth_pubkey_file(&location.user, None, &identity, None)
+            .is_ok();
+        if accepted && session.authenticated() {
+            return Ok(());
+        }
+    }
+
+    Err(AppError::PermissionDenied(format!(
+        "{}: Neither the ssh-agent nor a key file was accepted",
+        location.key()
+    )))
+}
+
+/// Keeps pooled connections alive and closes those left unused, for as long as the
+/// provider exists.
+fn spawn_keepalive(pool: Weak<Pool>) {
+    std::thread::spawn(move || {
+        let mut modified = config_modified();
+        let mut settings = load_config().sftp;
+        loop {
+            std::thread::sleep(Duration::from_secs(settings.keepalive_secs.max(1)));
+            // Loaded again only when the config was saved since
+            let now_modified = config_modified();
+            if now_modified != modified {
+                modified = now_modified;
+                settings = load_config().sftp;
+            }
+            if !keep_alive(&pool, &settings) {
+                return;
+            }
+        }
+    });
+}
+
+/// One round of keepalives, or `false` once the provider is gone.
+fn keep_alive(pool: &Weak<Pool>, settings: &SftpSettings) -> bool {
+    let Some(pool) = pool.upgrade() else {
+        return false;
+    };
+    let idle_timeout = Duration::from_secs(settings.idle_timeout_secs);
+    let connections: Vec<_> = pool
+        .lock()
+        .iter()
+        .map(|(key, connection)| (key.clone(), connection.clone()))
+        .collect();
+
+    // Checked outside the lock, since a dead connection may block until it times out
+    for (key, connection) in connections {
+        let idle = connection.last_used.lock().elapsed() >= idle_timeout;
+        if idle || connection.session.keepalive_send().is_err() {
+            let mut pool = pool.lock();
+            if pool.get(&key).is_some_and(|c| Arc::ptr_eq(c, &connection)) {
+                pool.remove(&key);
+            }
+        }
+    }
+    true
+}
+
+fn to_entry(location: &Location, remote: &str, stat: &FileStat, is_symlink: bool) -> FileEntry {
+    let name = Path::new(remote)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| remote.to_string());
+    let mut entry = FileEntry::new(
+        name,
+        location.uri(remote),
+        stat.is_dir(),
+        stat.size,
+        stat.mtime.map(|t| t as i64),
+    );
+    entry.is_symlink = is_symlink;
+    entry
+}
+
+/// Removes a file, or a folder after everything in it, without following links.
+fn remove_tree(sftp: &Sftp, path: &Path) -> Result<(), ssh2::Error> {
+    if !sftp.lstat(path)?.is_dir() {
+        return sftp.unlink(path);
+    }
+
+    for (child, _) in sftp.readdir(path)? {
+        remove_tree(sftp, &child)?;
+    }
+    sftp.rmdir(path)
+}
+
+/// Maps an SSH or SFTP error onto the `AppError` variant the frontend can act on.
+fn sftp_error(path: &str, e: ssh2::Error) -> AppError {
+    match e.code() {
+        ErrorCode::SFTP(FX_NO_SUCH_FILE) => AppError::NotFound(path.to_string()),
+        ErrorCode::SFTP(FX_PERMISSION_DENIED) => AppError::PermissionDenied(path.to_string()),
+        _ => AppError::Io(format!("{}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_user_host_port_and_path() {
+        let location = Location::parse("sftp://jane@example.com:2222/srv/data").unwrap();
+        assert_eq!(location.user, "jane");
+        assert_eq!(location.host, "example.com");
+        assert_eq!(location.port, 2222);
+        assert_eq!(location.path, "/srv/data");
+        assert_eq!(location.key(), "jane@example.com:2222");
+    }
+
+    #[test]
+    fn defaults_port_and_home_folder() {
+        let location = Location::parse("sftp://jane@example.com").unwrap();
+        assert_eq!(location.port, DEFAULT_PORT);
+        assert_eq!(location.path, "");
+
+        let root = Location::parse("sftp://jane@example.com/").unwrap();
+        assert_eq!(root.path, "/");
+        let trailing = Location::parse("sftp://jane@example.com/srv/").unwrap();
+        assert_eq!(trailing.path, "/srv");
+    }
+
+    #[test]
+    fn parses_bracketed_ipv6_hosts() {
+        let location = Location::parse("sftp://jane@[::1]:2200/tmp").unwrap();
+        assert_eq!(location.host, "::1");
+        assert_eq!(location.port, 2200);
+        assert_eq!(location.uri("/tmp"), "sftp://jane@[::1]:2200/tmp");
+    }
+
+    #[test]
+    fn decodes_escaped_users() {
+        let location = Location::parse("sftp://jane%40corp.example@example.com/home").unwrap();
+        assert_eq!(location.user, "jane@corp.example");
+        assert_eq!(location.host, "example.com");
+    }
+
+    #[test]
+    fn rejects_bad_uris() {
+        assert!(Location::parse("ftp://example.com/").is_err());
+        assert!(Location::parse("sftp://jane@/srv").is_err());
+        assert!(Location::parse("sftp://jane@example.com:ssh/srv").is_err());
+    }
+
+    #[test]
+    fn uris_round_trip() {
+        for uri in [
+            "sftp://jane@example.com/srv/data",
+            "sftp://jane@example.com:2222/srv/data",
+            "sftp://jane%40corp.example@example.com/home/jane",
+            "sftp://jane@[fe80::1]:2222/srv",
+            "sftp://jane@example.com/srv/with space/100%",
+        ] {
+            let location = Location::parse(uri).unwrap();
+            assert_eq!(location.uri(&location.path), uri);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use super::remote::{
+    agent, find_remote, http_error, parent_path, parse_http_date, split_path, xml_text,
+    Credentials, Upload,
+};
+use super::{percent_decode, percent_encode, FileWriter, Vfs};
+use crate::fs::{sort_entries, AppError, FileEntry, RemoteConfig, RemoteKind};
+use base64::Engine;
+use std::io::Read;
+use std::sync::Arc;
+
+const SCHEME_PREFIX: &str = "webdav://";
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/></d:prop>
+</d:propfind>"#;
+
+/// Folders on WebDAV servers such as Nextcloud, addressed as `webdav://{remote}/path`
+/// with `remote` naming an entry of `Config.remotes`.
+pub struct WebDavFs {
+    agent: ureq::Agent,
+    credentials: Arc<Credentials>,
+}
+
+/// A request target: the remote it is on and its path below the remote's URL.
+struct Target {
+    remote: RemoteConfig,
+    /// `/`-separated, without surrounding slashes; empty for the remote's root.
+    path: String,
+    authorization: Option<String>,
+}
+
+impl Target {
+    fn url(&self) -> String {
+        let segments: Vec<_> = self.path.split('/').map(percent_encode).collect();
+        format!(
+            "{}/{}",
+            self.remote.url.trim_end_matches('/'),
+            segments.join("/")
+        )
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("{}{}/{}", SCHEME_PREFIX, self.remote.name, path)
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+impl WebDavFs {
+    pub fn new(credentials: Arc<Credentials>) -> Self {
+        Self {
+            agent: agent(),
+            credentials,
+        }
+    }
+
+    fn target(&self, path: &str) -> Result<Target, AppError> {
+        let (name, rest) = split_path(path, SCHEME_PREFIX)?;
+        let remote = find_remote(name, RemoteKind::Webdav)?;
+        let authorization = match self.credentials.get(name)? {
+            Some(password) => Some(basic_auth(&remote.username, &password)),
+            None if !remote.username.is_empty() => Some(basic_auth(&remote.username, "")),
+            None => None,
+        };
+
+        Ok(Target {
+            remote,
+            path: rest.to_string(),
+            authorization,
+        })
+    }
+
+    fn request(&self, method: &str, target: &Target) -> ureq::Request {
+        let request = self.agent.request(method, &target.url());
+        match &target.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    /// The entries PROPFIND reports for `target`, itself included when `depth` is 1.
+    fn propfind(&self, target: &Target, depth: u8) -> Result<Vec<FileEntry>, AppError> {
+        let uri = target.uri(&target.path);
+        let body = self
+            .request("PROPFIND", target)
+            .set("Depth", &depth.to_string())
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND_BODY)
+            .map_err(|e| http_error(&uri, e))?
+            .into_string()
+            .map_err(|e| AppError::Io(format!("{}: {}", uri, e)))?;
+
+        let url = target.url();
+        let resources = parse_multistatus(&body, url_path(&url), &target.path)
+            .map_err(|e| AppError::Io(format!("{}: Invalid PROPFIND response: {}", uri, e)))?;
+
+        Ok(resources
+            .into_iter()
+            .filter(|resource| depth == 0 || !resource.is_self)
+            .map(|resource| {
+                FileEntry::new(
+                    resource.name,
+                    target.uri(&resource.path),
+                    resource.is_dir,
+                    resource.size,
+                    resource.modified,
+                )
+            })
+            .collect())
+    }
+}
+
+impl Vfs for WebDavFs {
+    fn scheme(&self) -> &'static str {
+        "webdav"
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>, AppError> {
+        let mut entries = self.propfind(&self.target(path)?, 1)?;
+        sort_entries(&mut entries);
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileEntry, AppError> {
+        let target = self.target(path)?;
+        let mut entries = self.propfind(&target, 0)?;
+        if entries.is_empty() {
+            return Err(AppError::NotFound(path.to_string()));
+        }
+        let mut entry = entries.swap_remove(0);
+        if target.path.is_empty() {
+            entry.name = target.remote.name.clone();
+        }
+        Ok(entry)
+    }
+
+    fn open_read(&self, path: &str) -> Result<Box<dyn Read + Send>, AppError> {
+        let response = self
+            .request("GET", &self.target(path)?)
+            .call()
+            .map_err(|e| http_error(path, e))?;
+        Ok(Box::new(response.into_reader()))
+    }
+
+    fn open_write(&self, path: &str) -> Result<Box<dyn FileWriter>, AppError> {
+        let target = self.target(path)?;
+        let request = self.request("PUT", &target);
+        let path = path.to_string();
+        let upload = Upload::new(move |file, len| {
+            request
+                .set("Content-Length", &len.to_string())
+                .send(file)
+                .map(|_| ())
+                .map_err(|e| http_error(&path, e))
+        })?;
+        Ok(Box::new(upload))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), AppError> {
+        let source = self.target(from)?;
+        let target = self.target(to)?;
+        if source.remote.url != target.remote.url {
+            return Err(AppError::InvalidOperation(format!(
+                "Cannot rename across servers: {}",
+                to
+            )));
+        }
+
+        self.request("MOVE", &source)
+            .set("Destination", &target.url())
+            .set("Overwrite", "F")
+            .call()
+            .map(|_| ())
+            .map_err(|e| http_error(from, e))
+    }
+
+    fn remove(&self, path: &str) -> Result<(), AppError> {
+        // Folders are removed with everything in them by the server
+        self.request("DELETE", &self.target(path)?)
+            .call()
+            .map(|_| ())
+            .map_err(|e| http_error(path, e))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), AppError> {
+        self.request("MKCOL", &self.target(path)?)
+            .call()
+            .map(|_| ())
+            .map_err(|e| http_error(path, e))
+    }
+
+    fn parent(&self, path: &str) -> Option<String> {
+        parent_path(path, SCHEME_PREFIX)
+    }
+}
+
+/// An entry of a PROPFIND response.
+struct Resource {
+    /// Whether this is the folder or file that was asked about, rather than a child.
+    is_self: bool,
+    name: String,
+    /// Below the remote's URL, like `Target.path`.
+    path: String,
+    is_dir: bool,
+    size: Option<u64>,
+    modified: Option<i64>,
+}
+
+/// Reads the multistatus `body` a PROPFIND of `path` answered with, where `own_url_path`
+/// is the path of the URL that was requested.
+fn parse_multistatus(
+    body: &str,
+    own_url_path: &str,
+    path: &str,
+) -> Result<Vec<Resource>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(body)?;
+    let own_path = percent_decode(own_url_path);
+    let own_path = own_path.trim_end_matches('/');
+
+    let mut resources = Vec::new();
+    for response in document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "response")
+    {
+        let href = match xml_text(response, "href") {
+            Some(href) => percent_decode(url_path(href)),
+            None => continue,
+        };
+        let href = href.trim_end_matches('/');
+        let is_self = href == own_path;
+        let name = match href.rsplit('/').next() {
+            Some(name) if !is_self => name.to_string(),
+            _ => path.rsplit('/').next().unwrap_or_default().to_string(),
+        };
+        let resource_path = match (is_self, path) {
+            (true, _) => path.to_string(),
+            (false, "") => name.clone(),
+            (false, dir) => format!("{}/{}", dir, name),
+        };
+
+        resources.push(Resource {
+            is_self,
+            name,
+            path: resource_path,
+            is_dir: response
+                .descendants()
+                .any(|node| node.tag_name().name() == "collection"),
+            size: xml_text(response, "getcontentlength").and_then(|len| len.parse().ok()),
+            modified: xml_text(response, "getlastmodified").and_then(parse_http_date),
+        });
+    }
+    Ok(resources)
+}
+
+fn basic_auth(username: &str, password: &str) -> String {
+    let credentials = format!("{}:{}", username, password);
+    format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(credentials)
+    )
+}
+
+/// The path of a URL, which servers may also send on its own.
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |index| &rest[index..]),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As Nextcloud answers, with the properties it lacks in a 404 propstat of their own
+    const MULTISTATUS: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns">
+  <d:response>
+    <d:href>/remote.php/dav/files/me/Photos%20Old/</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype><d:collection/></d:resourcetype>
+        <d:getlastmodified>Tue, 04 Jun 2024 10:00:00 GMT</d:getlastmodified>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+    <d:propstat>
+      <d:prop><d:getcontentlength/></d:prop>
+      <d:status>HTTP/1.1 404 Not Found</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://cloud.example.com/remote.php/dav/files/me/Photos%20Old/beach.jpg</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getcontentlength>2048</d:getcontentlength>
+        <d:getlastmodified>Wed, 05 Jun 2024 12:30:00 GMT</d:getlastmodified>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/me/Photos%20Old/2023/</d:href>
+    <d:propstat>
+      <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    #[test]
+    fn parses_multistatus() {
+        let resources = parse_multistatus(
+            MULTISTATUS,
+            "/remote.php/dav/files/me/Photos%20Old",
+            "Photos Old",
+        )
+        .unwrap();
+        assert_eq!(resources.len(), 3);
+
+        let folder = &resources[0];
+        assert!(folder.is_self && folder.is_dir);
+        assert_eq!(folder.name, "Photos Old");
+        assert_eq!(folder.path, "Photos Old");
+        assert_eq!(folder.size, None);
+        assert_eq!(folder.modified, Some(1717495200));
+
+        let file = &resources[1];
+        assert!(!file.is_self && !file.is_dir);
+        assert_eq!(file.name, "beach.jpg");
+        assert_eq!(file.path, "Photos Old/beach.jpg");
+        assert_eq!(file.size, Some(2048));
+        assert_eq!(file.modified, Some(1717590600));
+
+        let child = &resources[2];
+        assert!(!child.is_self && child.is_dir);
+        assert_eq!(child.path, "Photos Old/2023");
+    }
+
+    #[test]
+    fn parses_children_of_the_root() {
+        let resources = parse_multistatus(MULTISTATUS, "/remote.php/dav/files/me", "").unwrap();
+        assert!(resources.iter().all(|resource| !resource.is_self));
+        assert_eq!(resources[1].path, "beach.jpg");
+    }
+
+    #[test]
+    fn rejects_invalid_xml() {
+        assert!(parse_multistatus("<d:multistatus", "/", "").is_err());
+    }
+
+    #[test]
+    fn takes_the_path_of_urls() {
+        assert_eq!(url_path("https://cloud.example.com/dav/a"), "/dav/a");
+        assert_eq!(url_path("https://cloud.example.com"), "/");
+        assert_eq!(url_path("/dav/a"), "/dav/a");
+    }
+}
@@ -0,0 +1,219 @@
+//! WSL integration: distro listing, path mapping and copies run inside a distro. Parsing
+//! and command building are kept free of `wsl.exe` itself so they work on any platform.
+
+mod copy;
+mod paths;
+
+pub use copy::{copy, CopyItem};
+pub use paths::{to_linux, to_windows};
+
+use crate::fs::WslDistro;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistroState {
+    Running,
+    Stopped,
+    Installing,
+    Converting,
+    Uninstalling,
+    /// A state this version does not know, or one `wsl.exe` printed in another language.
+    Unknown,
+}
+
+impl DistroState {
+    fn parse(text: &str) -> Self {
+        match text.to_ascii_lowercase().as_str() {
+            "running" => Self::Running,
+            "stopped" => Self::Stopped,
+            "installing" => Self::Installing,
+            "converting" => Self::Converting,
+            "uninstalling" => Self::Uninstalling,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// A `wsl.exe` invocation that neither opens a console window nor answers in UTF-16
+/// where it can help it.
+pub fn wsl_command() -> Command {
+    let mut command = Command::new("wsl.exe");
+    command.env("WSL_UTF8", "1");
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+}
+
+/// The installed distros, or none where WSL is unavailable.
+pub fn list_distros() -> Vec<WslDistro> {
+    match wsl_command().args(["--list", "--verbose"]).output() {
+        Ok(output) if output.status.success() => parse_list_verbose(&decode_output(&output.stdout)),
+        // Exits with an error, and prints help, while no distro is installed
+        _ => Vec::new(),
+    }
+}
+
+/// The distro a copy between `dest` and `sources` can run inside: all paths must be
+/// reachable from WSL, and those inside a distro's own filesystem in the same one.
+/// Copies between Windows folders only are left to Windows.
+pub fn copy_distro<'a>(
+    dest: &'a str,
+    sources: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let mut distro = None;
+    for path in std::iter::once(dest).chain(sources) {
+        match (to_linux(path)?.distro, &distro) {
+            (Some(found), Some(current)) if &found != current => return None,
+            (Some(found), _) => distro = Some(found),
+            (None, _) => {}
+        }
+    }
+    distro
+}
+
+/// Decodes what `wsl.exe` prints, which is UTF-16LE unless `WSL_UTF8` is honoured.
+pub fn decode_output(bytes: &[u8]) -> String {
+    let utf16 =
+        bytes.starts_with(&[0xFF, 0xFE]) || (bytes.len() >= 2 && bytes[1] == 0 && bytes[0] != 0);
+    if !utf16 {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_start_matches('\u{feff}')
+        .to_string()
+}
+
+/// Parses the table `wsl --list --verbose` prints:
+///
+/// ```text
+///   NAME            STATE           VERSION
+/// * Ubuntu-22.04    Running         2
+///   Debian          Stopped         1
+/// ```
+///
+/// Distro names cannot contain spaces, so the name is the first column and the version
+/// the last. The header, in whatever language, is told apart by not ending in a version,
+/// as are notices `wsl.exe` may print around the table.
+pub fn parse_list_verbose(output: &str) -> Vec<WslDistro> {
+    output
+        .lines()
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let (is_default, line) = match line.strip_prefix('*') {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, line),
+            };
+            let mut columns: Vec<&str> = line.split_whitespace().collect();
+            let version = columns.pop()?.parse().ok()?;
+            if columns.len() < 2 {
+                return None;
+            }
+            let name = columns[0].to_string();
+            let state = DistroState::parse(&columns[1..].join(" "));
+
+            Some(WslDistro {
+                path: to_windows(&name, "/")?,
+                name,
+                is_default,
+                state,
+                version,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "  NAME            STATE           VERSION\r\n\
+                        * Ubuntu-22.04    Running         2\r\n\
+                        \x20 Debian          Stopped         1\r\n";
+
+    fn utf16(text: &str, bom: bool) -> Vec<u8> {
+        let bom = bom.then_some(0xfeff);
+        bom.into_iter()
+            .chain(text.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn decodes_utf8_and_utf16() {
+        assert_eq!(decode_output(LIST.as_bytes()), LIST);
+        assert_eq!(decode_output(&utf16(LIST, false)), LIST);
+        assert_eq!(decode_output(&utf16(LIST, true)), LIST);
+        assert_eq!(decode_output(&utf16("Ünïcode", true)), "Ünïcode");
+        assert_eq!(decode_output(b""), "");
+    }
+
+    #[test]
+    fn parses_distros() {
+        let distros = parse_list_verbose(LIST);
+        assert_eq!(distros.len(), 2);
+
+        assert_eq!(distros[0].name, "Ubuntu-22.04");
+        assert_eq!(distros[0].path, "\\\\wsl$\\Ubuntu-22.04");
+        assert!(distros[0].is_default);
+        assert_eq!(distros[0].state, DistroState::Running);
+        assert_eq!(distros[0].version, 2);
+
+        assert_eq!(distros[1].name, "Debian");
+        assert!(!distros[1].is_default);
+        assert_eq!(distros[1].state, DistroState::Stopped);
+        assert_eq!(distros[1].version, 1);
+    }
+
+    #[test]
+    fn parses_utf16_output() {
+        let distros = parse_list_verbose(&decode_output(&utf16(LIST, false)));
+        let names: Vec<_> = distros.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["Ubuntu-22.04", "Debian"]);
+    }
+
+    #[test]
+    fn skips_headers_in_other_languages_and_notices() {
+        let output = "Windows-Subsystem für Linux wird aktualisiert.\n\n\
+                      \x20 NAME      STATUS           VERSION\n\
+                      * Ubuntu    Wird ausgeführt  2\n";
+        let distros = parse_list_verbose(output);
+        assert_eq!(distros.len(), 1);
+        assert_eq!(distros[0].name, "Ubuntu");
+        assert!(distros[0].is_default);
+        assert_eq!(distros[0].state, DistroState::Unknown);
+    }
+
+    #[test]
+    fn keeps_the_first_distro_without_a_header() {
+        let distros = parse_list_verbose("* Ubuntu Stopped 2\n");
+        assert_eq!(distros.len(), 1);
+    }
+
+    #[test]
+    fn finds_the_distro_to_copy_in() {
+        assert_eq!(
+            copy_distro("\\\\wsl$\\Ubuntu\\tmp", ["C:\\Users\\me\\a.txt"]),
+            Some("Ubuntu".to_string())
+        );
+        assert_eq!(copy_distro("D:\\backup", ["C:\\Users\\me"]), None);
+        assert_eq!(
+            copy_distro("\\\\wsl$\\Ubuntu\\tmp", ["\\\\wsl.localhost\\Debian\\tmp"]),
+            None
+        );
+        assert_eq!(copy_distro("\\\\server\\share", ["\\\\wsl$\\Ubuntu\\tmp"]), None);
+    }
+}
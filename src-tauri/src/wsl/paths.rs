@@ -0,0 +1,163 @@
+//! Mapping between Windows paths and the paths a distro sees, the way `wslpath` does.
+
+/// Where a Windows path is found from inside WSL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinuxPath {
+    /// The distro whose own filesystem holds the path, or `None` for Windows drives,
+    /// which every distro mounts below `/mnt`.
+    pub distro: Option<String>,
+    pub path: String,
+}
+
+const UNC_HOSTS: [&str; 2] = ["wsl$", "wsl.localhost"];
+
+/// Maps `\\wsl$\Ubuntu\home\me` (or `\\wsl.localhost\...`) to `/home/me` in `Ubuntu`, and
+/// `C:\Users\me` to `/mnt/c/Users/me`. Forward slashes are accepted too. Other paths,
+/// such as network shares, have no Linux equivalent.
+pub fn to_linux(windows: &str) -> Option<LinuxPath> {
+    let normalized = windows.replace('/', "\\");
+
+    if let Some(unc) = normalized.strip_prefix("\\\\") {
+        let mut segments = unc.split('\\').filter(|segment| !segment.is_empty());
+        let host = segments.next()?;
+        if !UNC_HOSTS.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+            return None;
+        }
+        let distro = segments.next()?.to_string();
+        return Some(LinuxPath {
+            distro: Some(distro),
+            path: linux_path("", segments),
+        });
+    }
+
+    let bytes = normalized.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if !has_drive || bytes.get(2).is_some_and(|&b| b != b'\\') {
+        return None;
+    }
+    let drive = format!("/mnt/{}", (bytes[0] as char).to_ascii_lowercase());
+    Some(LinuxPath {
+        distro: None,
+        path: linux_path(
+            &drive,
+            normalized[2..].split('\\').filter(|s| !s.is_empty()),
+        ),
+    })
+}
+
+/// Maps an absolute path inside `distro` to the Windows path it is reached by:
+/// `/mnt/c/Users` becomes `C:\Users` and `/home/me` becomes `\\wsl$\{distro}\home\me`.
+pub fn to_windows(distro: &str, linux: &str) -> Option<String> {
+    if !linux.starts_with('/') {
+        return None;
+    }
+    let segments: Vec<&str> = linux.split('/').filter(|s| !s.is_empty()).collect();
+
+    if let ["mnt", drive, rest @ ..] = segments.as_slice() {
+        if drive.len() == 1 && drive.as_bytes()[0].is_ascii_alphabetic() {
+            return Some(format!(
+                "{}:\\{}",
+                drive.to_ascii_uppercase(),
+                rest.join("\\")
+            ));
+        }
+    }
+
+    let mut path = format!("\\\\wsl$\\{}", distro);
+    for segment in segments {
+        path.push('\\');
+        path.push_str(segment);
+    }
+    Some(path)
+}
+
+fn linux_path<'a>(root: &str, segments: impl Iterator<Item = &'a str>) -> String {
+    let mut path = root.to_string();
+    for segment in segments {
+        path.push('/');
+        path.push_str(segment);
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux(distro: Option<&str>, path: &str) -> Option<LinuxPath> {
+        Some(LinuxPath {
+            distro: distro.map(str::to_string),
+            path: path.to_string(),
+        })
+    }
+
+    #[test]
+    fn maps_shares_of_distros() {
+        assert_eq!(
+            to_linux(r"\\wsl$\Ubuntu\home\me"),
+            linux(Some("Ubuntu"), "/home/me")
+        );
+        assert_eq!(
+            to_linux(r"\\wsl.localhost\Ubuntu\home\me\"),
+            linux(Some("Ubuntu"), "/home/me")
+        );
+        assert_eq!(to_linux("//WSL$/Debian/etc"), linux(Some("Debian"), "/etc"));
+        assert_eq!(to_linux(r"\\wsl$\Ubuntu"), linux(Some("Ubuntu"), "/"));
+        assert_eq!(to_linux(r"\\wsl$"), None);
+        assert_eq!(to_linux(r"\\server\share\file"), None);
+    }
+
+    #[test]
+    fn maps_drive_letters() {
+        assert_eq!(to_linux(r"C:\Users\me"), linux(None, "/mnt/c/Users/me"));
+        assert_eq!(to_linux("d:/Data"), linux(None, "/mnt/d/Data"));
+        assert_eq!(to_linux(r"C:\"), linux(None, "/mnt/c"));
+        assert_eq!(to_linux("C:"), linux(None, "/mnt/c"));
+        assert_eq!(to_linux("C:relative"), None);
+        assert_eq!(to_linux(r"relative\path"), None);
+    }
+
+    #[test]
+    fn maps_back_to_windows() {
+        assert_eq!(
+            to_windows("Ubuntu", "/home/me").as_deref(),
+            Some(r"\\wsl$\Ubuntu\home\me")
+        );
+        assert_eq!(to_windows("Ubuntu", "/").as_deref(), Some(r"\\wsl$\Ubuntu"));
+        assert_eq!(
+            to_windows("Ubuntu", "/mnt/c/Users/me").as_deref(),
+            Some(r"C:\Users\me")
+        );
+        assert_eq!(to_windows("Ubuntu", "/mnt/c").as_deref(), Some(r"C:\"));
+        // Only single letters below /mnt are drives
+        assert_eq!(
+            to_windows("Ubuntu", "/mnt/wsl").as_deref(),
+            Some(r"\\wsl$\Ubuntu\mnt\wsl")
+        );
+        assert_eq!(to_windows("Ubuntu", "home/me"), None);
+    }
+
+    #[test]
+    fn round_trips() {
+        for windows in [
+            r"\\wsl$\Ubuntu\home\me\notes.txt",
+            r"\\wsl$\Ubuntu-22.04\tmp",
+            r"C:\Users\me\Documents",
+            r"D:\",
+        ] {
+            let linux = to_linux(windows).unwrap();
+            let distro = linux.distro.as_deref().unwrap_or("Ubuntu");
+            assert_eq!(to_windows(distro, &linux.path).as_deref(), Some(windows));
+        }
+
+        // Shares of either host lead back to the one `wsl$` is the older name of
+        let linux = to_linux(r"\\wsl.localhost\Ubuntu\home").unwrap();
+        assert_eq!(
+            to_windows("Ubuntu", &linux.path).as_deref(),
+            Some(r"\\wsl$\Ubuntu\home")
+        );
+    }
+}
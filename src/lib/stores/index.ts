@@ -4,30 +4,84 @@ import { writable } from 'svelte/store';
 
 export type IndexStatus = 'idle' | 'scanning' | 'watching' | 'error';
 
+export interface RootProgress {
+  path: string;
+  status: 'pending' | 'scanning' | 'done' | 'cancelled' | 'cached';
+  indexed_count: number;
+  watch_mode: 'native' | 'polling' | 'unwatched' | null;
+}
+
+export interface IndexStats {
+  files_scanned: number;
+  dirs_scanned: number;
+  bytes_scanned: number;
+  error_count: number;
+  permission_denied_count: number;
+  error_samples: string[];
+  elapsed_ms: number;
+  estimated_remaining_ms: number | null;
+  watch_events_per_sec: number;
+  index_memory_bytes: number;
+}
+
 export interface IndexProgress {
+  scope: string;
   status: IndexStatus;
+  // Only set when status is 'error'
+  reason?: string;
   indexed_count: number;
+  content_count: number;
   current_path: string | null;
+  roots: RootProgress[];
+  stats: IndexStats;
 }
 
-function createIndexStore() {
-  const { subscribe, set, update } = writable<IndexProgress>({
+export interface IndexStatusChange {
+  scope: string;
+  status: IndexStatus;
+  reason?: string;
+}
+
+export function emptyIndexProgress(scope = 'default'): IndexProgress {
+  return {
+    scope,
     status: 'idle',
     indexed_count: 0,
+    content_count: 0,
     current_path: null,
-  });
+    roots: [],
+    stats: {
+      files_scanned: 0,
+      dirs_scanned: 0,
+      bytes_scanned: 0,
+      error_count: 0,
+      permission_denied_count: 0,
+      error_samples: [],
+      elapsed_ms: 0,
+      estimated_remaining_ms: null,
+      watch_events_per_sec: 0,
+      index_memory_bytes: 0,
+    },
+  };
+}
+
+// Tracks the 'default' scope, which global search indexes its drives into
+function createIndexStore() {
+  const { subscribe, set, update } = writable<IndexProgress>(emptyIndexProgress());
 
   return {
     subscribe,
     init: async () => {
       // Listen for progress events
       await listen<IndexProgress>('index:progress', (event) => {
-        set(event.payload);
+        if (event.payload.scope === 'default') set(event.payload);
       });
 
       // Listen for status changes
-      await listen<IndexStatus>('index:status', (event) => {
-        update(s => ({ ...s, status: event.payload }));
+      await listen<IndexStatusChange>('index:status', (event) => {
+        if (event.payload.scope === 'default') {
+          update(s => ({ ...s, status: event.payload.status, reason: event.payload.reason }));
+        }
       });
 
       // Get initial status
@@ -39,11 +93,7 @@ function createIndexStore() {
       }
     },
     reset: () => {
-      set({
-        status: 'idle',
-        indexed_count: 0,
-        current_path: null,
-      });
+      set(emptyIndexProgress());
     }
   };
 }
@@ -1,14 +1,16 @@
 // Web-compatible IPC implementation
+import { emptyIndexProgress, type IndexProgress } from '$lib/stores';
+
 // Tauri v2: check for __TAURI_INTERNALS__ instead of __TAURI__
 const isTauri = typeof window !== 'undefined' && '__TAURI_INTERNALS__' in window;
 
 // Mock data for web version
 const mockFiles: FileEntry[] = [
-  { name: 'Documents', path: '/home/user/Documents', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false },
-  { name: 'Downloads', path: '/home/user/Downloads', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false },
-  { name: 'Pictures', path: '/home/user/Pictures', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false },
-  { name: 'README.md', path: '/home/user/README.md', extension: 'md', size: 1024, modified: Date.now(), is_dir: false, is_hidden: false, is_symlink: false },
-  { name: 'script.js', path: '/home/user/script.js', extension: 'js', size: 2048, modified: Date.now(), is_dir: false, is_hidden: false, is_symlink: false },
+  { name: 'Documents', path: '/home/user/Documents', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false, tags: [] },
+  { name: 'Downloads', path: '/home/user/Downloads', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false, tags: [] },
+  { name: 'Pictures', path: '/home/user/Pictures', extension: null, size: null, modified: Date.now(), is_dir: true, is_hidden: false, is_symlink: false, tags: [] },
+  { name: 'README.md', path: '/home/user/README.md', extension: 'md', size: 1024, modified: Date.now(), is_dir: false, is_hidden: false, is_symlink: false, tags: [] },
+  { name: 'script.js', path: '/home/user/script.js', extension: 'js', size: 2048, modified: Date.now(), is_dir: false, is_hidden: false, is_symlink: false, tags: [] },
 ];
 
 const mockConfig: Config = {
@@ -22,6 +24,29 @@ const mockConfig: Config = {
   show_hidden: false,
   recent_paths: ['/home/user', '/home/user/Documents'],
   theme: 'dark',
+  index_exclusions: {
+    patterns: [],
+    dir_names: ['node_modules', '.git', '.hg', '.svn', 'target', '__pycache__'],
+    max_depth: null,
+    include_hidden: true,
+    respect_ignore_files: true,
+    one_file_system: false,
+    skip_virtual_filesystems: true,
+  },
+  watcher: {
+    poll_interval_secs: 10,
+    force_polling: false,
+  },
+  index_scopes: [],
+  mirror_tags_to_xattrs: false,
+  saved_searches: [],
+  sftp: {
+    identity_files: [],
+    timeout_secs: 30,
+    keepalive_secs: 30,
+    idle_timeout_secs: 300,
+  },
+  remotes: [],
 };
 
 async function invoke<T>(cmd: string, args?: any): Promise<T> {
@@ -42,6 +67,19 @@ async function invoke<T>(cmd: string, args?: any): Promise<T> {
         setTimeout(() => resolve('/home/user' as T), 100);
       });
 
+    case 'get_parent_directory': {
+      const parent = args.path.split('/').slice(0, -1).join('/') || '/';
+      return new Promise(resolve => {
+        setTimeout(() => resolve((args.path === '/' ? null : parent) as T), 100);
+      });
+    }
+
+    case 'watch_directory':
+    case 'unwatch_directory':
+      return new Promise(resolve => {
+        setTimeout(() => resolve(undefined as T), 100);
+      });
+
     case 'get_wsl_distros':
       return new Promise(resolve => {
         setTimeout(() => resolve([] as T), 100);
@@ -68,30 +106,107 @@ async function invoke<T>(cmd: string, args?: any): Promise<T> {
       });
 
     case 'search_files':
+    case 'search_content':
       return new Promise(resolve => {
-        const query = (args?.query || '').toLowerCase();
-        const results = mockFiles.filter(f => f.name.toLowerCase().includes(query));
-        setTimeout(() => resolve(results as T), 200);
+        setTimeout(() => resolve('mock-search' as T), 100);
+      });
+
+    case 'create_archive':
+    case 'extract_archive':
+      console.log(`Mock job: ${cmd}`, args);
+      return new Promise(resolve => {
+        setTimeout(() => resolve('mock-job' as T), 100);
+      });
+
+    case 'get_jobs':
+      return new Promise(resolve => {
+        setTimeout(() => resolve([] as T), 100);
+      });
+
+    case 'has_remote_secret':
+      return new Promise(resolve => {
+        setTimeout(() => resolve(false as T), 100);
+      });
+
+    case 'set_remote_secret':
+    case 'forget_remote_secret':
+    case 'cancel_job':
+    case 'cancel_search':
+    case 'reorder_operation':
+    case 'remove_operation':
+    case 'set_queue_paused':
+    case 'discard_interrupted_operation':
+      return new Promise(resolve => {
+        setTimeout(() => resolve(undefined as T), 100);
+      });
+
+    case 'queue_operation':
+      return new Promise(resolve => {
+        setTimeout(() => resolve(`mock-operation-${Date.now()}` as T), 100);
+      });
+
+    case 'get_operation_queue':
+      return new Promise(resolve => {
+        const queue: QueueState = { paused: false, operations: [] };
+        setTimeout(() => resolve(queue as T), 100);
+      });
+
+    case 'get_interrupted_operations':
+    case 'resume_interrupted_operations':
+      return new Promise(resolve => {
+        setTimeout(() => resolve([] as T), 100);
+      });
+
+    case 'get_disk_space':
+      return new Promise(resolve => {
+        const space: DiskSpace = { total_bytes: 512e9, free_bytes: 128e9, used_bytes: 384e9 };
+        setTimeout(() => resolve(space as T), 100);
+      });
+
+    case 'plan_copy':
+      return new Promise(resolve => {
+        const plan: CopyPlan = {
+          total_bytes: 0,
+          total_items: 0,
+          overwritten_bytes: 0,
+          free_bytes: 128e9,
+          fs_type: 'NTFS',
+          max_file_size: null,
+          problems: [],
+        };
+        setTimeout(() => resolve(plan as T), 100);
       });
 
     case 'get_available_drives':
       return new Promise(resolve => {
-        const drives = [
-          { name: 'C: Drive', path: 'C:\\' },
-          { name: 'D: Drive', path: 'D:\\' },
-        ];
+        const drive = (name: string, path: string, removable: boolean): DriveInfo => ({
+          name,
+          path,
+          label: null,
+          fs_type: 'NTFS',
+          total_bytes: 512e9,
+          free_bytes: 128e9,
+          used_bytes: 384e9,
+          read_only: false,
+          removable,
+        });
+        const drives = [drive('C: Drive', 'C:\\', false), drive('D: Drive', 'D:\\', true)];
         setTimeout(() => resolve(drives as T), 100);
       });
 
     case 'start_indexing':
     case 'stop_indexing':
     case 'clear_index_cache':
+    case 'add_index_root':
+    case 'remove_index_root':
+    case 'remove_index_scope':
       console.log(`Mock index operation: ${cmd}`, args);
       return new Promise(resolve => {
         setTimeout(() => resolve(undefined as T), 100);
       });
 
     case 'search_index':
+    case 'search_content_index':
       return new Promise(resolve => {
         const query = (args?.query || '').toLowerCase();
         const results = mockFiles.filter(f => f.name.toLowerCase().includes(query));
@@ -101,13 +216,50 @@ async function invoke<T>(cmd: string, args?: any): Promise<T> {
     case 'get_index_status':
       return new Promise(resolve => {
         const status = {
-          status: 'idle',
+          ...emptyIndexProgress(args?.scope ?? 'default'),
           indexed_count: mockFiles.length,
-          current_path: null
         };
         setTimeout(() => resolve(status as T), 100);
       });
 
+    case 'jump_to':
+    case 'get_frecent_paths':
+      return new Promise(resolve => {
+        const query = (args?.query || '').toLowerCase();
+        const results = mockFiles
+          .filter(f => f.is_dir && f.path.toLowerCase().includes(query))
+          .map((f, i) => ({ path: f.path, kind: 'directory', score: 10 - i, last_access: Date.now() / 1000 }));
+        setTimeout(() => resolve(results as T), 100);
+      });
+
+    case 'forget_frecent_path':
+      return new Promise(resolve => {
+        setTimeout(() => resolve(true as T), 100);
+      });
+
+    case 'add_tags':
+    case 'remove_tags':
+      console.log(`Mock tag operation: ${cmd}`, args);
+      return new Promise(resolve => {
+        setTimeout(() => resolve(undefined as T), 100);
+      });
+
+    case 'list_files_by_tag':
+      return new Promise(resolve => {
+        setTimeout(() => resolve([] as T), 100);
+      });
+
+    case 'get_all_tags':
+      return new Promise(resolve => {
+        setTimeout(() => resolve([] as T), 100);
+      });
+
+    case 'get_index_scopes':
+      return new Promise(resolve => {
+        const scopes = [{ ...emptyIndexProgress(), indexed_count: mockFiles.length }];
+        setTimeout(() => resolve(scopes as T), 100);
+      });
+
     default:
       console.log(`Unknown command: ${cmd}`, args);
       return new Promise(resolve => {
@@ -125,12 +277,31 @@ export interface FileEntry {
   is_dir: boolean;
   is_hidden: boolean;
   is_symlink: boolean;
+  tags: string[];
 }
 
+export interface DirectoryChange {
+  pane_id: string;
+  path: string;
+  added: FileEntry[];
+  removed: FileEntry[];
+  modified: FileEntry[];
+}
+
+export type WslDistroState =
+  | 'running'
+  | 'stopped'
+  | 'installing'
+  | 'converting'
+  | 'uninstalling'
+  | 'unknown';
+
 export interface WslDistro {
   name: string;
   path: string;
   is_default: boolean;
+  state: WslDistroState;
+  version: number;
 }
 
 export interface AppError {
@@ -149,9 +320,164 @@ export interface UndoToken {
   backup_paths: string[];
 }
 
-export interface DriveInfo {
+export interface DiskSpace {
+  total_bytes: number;
+  // Available to the current user
+  free_bytes: number;
+  used_bytes: number;
+}
+
+export interface DriveInfo extends DiskSpace {
+  // The label, or else a name derived from the mount point
   name: string;
   path: string;
+  label: string | null;
+  fs_type: string;
+  read_only: boolean;
+  removable: boolean;
+}
+
+export type PlanProblem =
+  | { kind: 'insufficient_space'; needed_bytes: number; free_bytes: number }
+  | { kind: 'file_too_large'; path: string; size: number; max_file_size: number };
+
+export interface CopyPlan {
+  total_bytes: number;
+  // Files only; folders are not counted
+  total_items: number;
+  // Files at the destination that are replaced, freeing their space
+  overwritten_bytes: number;
+  // Null where the destination cannot tell, e.g. remote folders
+  free_bytes: number | null;
+  fs_type: string | null;
+  max_file_size: number | null;
+  problems: PlanProblem[];
+}
+
+// Payload of 'volumes:changed'; removed holds mount points
+export interface VolumeChange {
+  added: DriveInfo[];
+  removed: string[];
+}
+
+export interface ContentSearchOptions {
+  regex?: boolean;
+  case_sensitive?: boolean;
+  include_hidden?: boolean;
+  respect_gitignore?: boolean;
+  max_file_size?: number;
+  max_matches?: number;
+  timeout_ms?: number;
+  context_lines?: number;
+}
+
+export interface ContentMatch {
+  path: string;
+  line_number: number;
+  line: string;
+  context_before: string[];
+  context_after: string[];
+}
+
+export interface ContentSearchBatch {
+  search_id: string;
+  matches: ContentMatch[];
+}
+
+export interface FileSearchBatch {
+  search_id: string;
+  entries: FileEntry[];
+}
+
+// What copies, moves and extractions do when a target already exists
+export type ConflictPolicy = 'overwrite' | 'skip' | 'rename' | 'fail';
+
+export type OperationKind = 'copy' | 'move';
+
+export interface QueuedOperation {
+  id: string;
+  kind: OperationKind;
+  sources: string[];
+  dest: string;
+  conflict: ConflictPolicy;
+  ignore_problems: boolean;
+  resume: string | null; // journal of the interrupted run it continues
+  devices: string[];
+  job_id: string | null; // null while waiting
+}
+
+export interface QueueState {
+  paused: boolean;
+  operations: QueuedOperation[]; // running first, then waiting in start order
+}
+
+export interface InterruptedOperation {
+  id: string;
+  kind: OperationKind;
+  sources: string[];
+  dest: string;
+  conflict: ConflictPolicy;
+  completed_items: number;
+  completed_bytes: number;
+  error: string | null; // null if the app exited while it ran
+}
+
+export interface OperationDone {
+  id: string;
+  error: string | null;
+}
+
+export type ArchiveFormat = 'zip' | 'tar' | 'tar.gz' | 'tar.xz' | 'tar.zst' | '7z';
+
+export interface JobProgress {
+  job_id: string;
+  kind: string;
+  status: 'running' | 'completed' | 'failed' | 'cancelled';
+  total_bytes: number;
+  processed_bytes: number;
+  total_items: number;
+  processed_items: number;
+  current_path: string | null;
+  error: string | null;
+}
+
+export interface SearchSummary {
+  search_id: string;
+  scanned_count: number;
+  match_count: number;
+  elapsed_ms: number;
+  truncated: boolean;
+  cancelled: boolean;
+}
+
+export interface IndexExclusions {
+  patterns: string[];
+  dir_names: string[];
+  max_depth: number | null;
+  include_hidden: boolean;
+  respect_ignore_files: boolean;
+  one_file_system: boolean;
+  skip_virtual_filesystems: boolean;
+}
+
+export interface IndexSchedule {
+  // Five-field cron expression in local time, e.g. '0 3 * * *'
+  cron: string | null;
+  idle_minutes: number | null;
+  pause_on_battery: boolean;
+}
+
+export interface IndexScopeConfig {
+  name: string;
+  roots: string[];
+  exclusions?: IndexExclusions | null;
+  index_content?: boolean;
+  schedule?: IndexSchedule | null;
+}
+
+export interface WatcherSettings {
+  poll_interval_secs: number;
+  force_polling: boolean;
 }
 
 export interface Config {
@@ -180,12 +506,64 @@ export interface Config {
   show_hidden: boolean;
   recent_paths: string[];
   theme: 'light' | 'dark';
+  index_exclusions: IndexExclusions;
+  watcher: WatcherSettings;
+  index_scopes: IndexScopeConfig[];
+  mirror_tags_to_xattrs: boolean;
+  saved_searches: SavedSearch[];
+  sftp: SftpSettings;
+  remotes: RemoteConfig[];
+}
+
+// Folders on SSH servers open as 'sftp://user@host:port/path'
+export interface SftpSettings {
+  identity_files: string[];
+  timeout_secs: number;
+  keepalive_secs: number;
+  idle_timeout_secs: number;
+}
+
+// Opened as 'webdav://<name>/path' or 's3://<name>/<bucket>/key'; secrets live in the OS keyring
+export interface RemoteConfig {
+  name: string;
+  kind: 'webdav' | 's3';
+  url: string;
+  username: string;
+  region: string | null;
+}
+
+// Opened like a folder through readDirectory/watchDirectory as 'search://<name>'
+export interface SavedSearch {
+  name: string;
+  query: string;
+  roots: string[];
+  extensions: string[];
+  modified_within_days: number | null;
+  include_dirs: boolean;
+}
+
+export const SAVED_SEARCH_SCHEME = 'search://';
+
+export function savedSearchPath(name: string): string {
+  return SAVED_SEARCH_SCHEME + name;
+}
+
+// Archives are browsed like folders: '<archive>!' is the root, '<archive>!/docs' a folder inside
+const ARCHIVE_PATTERN = /\.(zip|tar|tgz|tar\.gz|txz|tar\.xz|tzst|tar\.zst|7z)$/i;
+
+export function isArchive(entry: FileEntry): boolean {
+  return !entry.is_dir && ARCHIVE_PATTERN.test(entry.name);
+}
+
+export function archiveRootPath(path: string): string {
+  return path + '!';
 }
 
 // Filesystem commands
-export async function readDirectory(path: string): Promise<FileEntry[]> {
+// recordVisit=false keeps completions and refreshes out of the frecency ranking
+export async function readDirectory(path: string, recordVisit = true): Promise<FileEntry[]> {
   const normalizedPath = normalizeWslPath(path);
-  return invoke<FileEntry[]>('read_directory', { path: normalizedPath });
+  return invoke<FileEntry[]>('read_directory', { path: normalizedPath, recordVisit });
 }
 
 export async function getParentDirectory(path: string): Promise<string | null> {
@@ -204,13 +582,139 @@ export async function getFileInfo(path: string): Promise<FileEntry> {
   return invoke<FileEntry>('get_file_info', { path });
 }
 
+// Sends debounced 'dir:changed' events with a DirectoryChange payload while watched
+export async function watchDirectory(paneId: string, path: string): Promise<void> {
+  return invoke<void>('watch_directory', { paneId, path: normalizeWslPath(path) });
+}
+
+export async function unwatchDirectory(paneId: string): Promise<void> {
+  return invoke<void>('unwatch_directory', { paneId });
+}
+
 // File operations
-export async function copyFiles(sources: string[], dest: string): Promise<void> {
-  return invoke<void>('copy_files', { sources, dest });
+// ignoreProblems goes ahead despite the problems planCopy reports
+export async function copyFiles(
+  sources: string[],
+  dest: string,
+  conflict?: ConflictPolicy,
+  ignoreProblems?: boolean
+): Promise<void> {
+  return invoke<void>('copy_files', { sources, dest, conflict, ignoreProblems });
+}
+
+// copyFiles refuses to start when the plan has problems, unless told to ignore them
+export async function planCopy(
+  sources: string[],
+  dest: string,
+  conflict?: ConflictPolicy
+): Promise<CopyPlan> {
+  return invoke<CopyPlan>('plan_copy', { sources, dest, conflict });
+}
+
+export async function moveFiles(
+  sources: string[],
+  dest: string,
+  conflict?: ConflictPolicy,
+  ignoreProblems?: boolean
+): Promise<void> {
+  return invoke<void>('move_files', { sources, dest, conflict, ignoreProblems });
+}
+
+// Operation queue: operations on the same disk run one at a time
+export async function queueOperation(
+  kind: OperationKind,
+  sources: string[],
+  dest: string,
+  conflict?: ConflictPolicy,
+  ignoreProblems?: boolean
+): Promise<string> {
+  return invoke<string>('queue_operation', { kind, sources, dest, conflict, ignoreProblems });
 }
 
-export async function moveFiles(sources: string[], dest: string): Promise<void> {
-  return invoke<void>('move_files', { sources, dest });
+// Callbacks of queued operations still to finish, and results that came in before
+// their operation's id did
+const doneCallbacks = new Map<string, (error: string | null) => void>();
+const doneEarly = new Map<string, string | null>();
+let doneListener: Promise<unknown> | null = null;
+
+// Queues an operation and resolves with its id once it is queued; onDone is
+// called with its error, or null, when it has run
+export async function runQueued(
+  kind: OperationKind,
+  sources: string[],
+  dest: string,
+  onDone: (error: string | null) => void,
+  conflict?: ConflictPolicy,
+  ignoreProblems?: boolean
+): Promise<string> {
+  if (!isTauri) {
+    const id = await queueOperation(kind, sources, dest, conflict, ignoreProblems);
+    onDone(null);
+    return id;
+  }
+  doneListener ??= import('@tauri-apps/api/event').then(({ listen }) =>
+    listen<OperationDone>('queue:done', ({ payload }) => {
+      const callback = doneCallbacks.get(payload.id);
+      if (callback) {
+        doneCallbacks.delete(payload.id);
+        callback(payload.error);
+      } else {
+        // Operations queued elsewhere also land here; only the latest matter
+        if (doneEarly.size >= 64) doneEarly.delete(doneEarly.keys().next().value!);
+        doneEarly.set(payload.id, payload.error);
+      }
+    })
+  );
+  await doneListener;
+  const id = await queueOperation(kind, sources, dest, conflict, ignoreProblems);
+  if (doneEarly.has(id)) {
+    const error = doneEarly.get(id)!;
+    doneEarly.delete(id);
+    onDone(error);
+  } else {
+    doneCallbacks.set(id, onDone);
+  }
+  return id;
+}
+
+export async function getOperationQueue(): Promise<QueueState> {
+  return invoke<QueueState>('get_operation_queue');
+}
+
+// Only waiting operations can be moved; they stay behind running ones
+export async function reorderOperation(id: string, index: number): Promise<void> {
+  return invoke<void>('reorder_operation', { id, index });
+}
+
+// Cancels the operation if it is running
+export async function removeOperation(id: string): Promise<void> {
+  return invoke<void>('remove_operation', { id });
+}
+
+export async function setQueuePaused(paused: boolean): Promise<void> {
+  return invoke<void>('set_queue_paused', { paused });
+}
+
+// Copies and moves that stopped partway; only resuming them picks up where they stopped
+export async function getInterruptedOperations(): Promise<InterruptedOperation[]> {
+  return invoke<InterruptedOperation[]>('get_interrupted_operations');
+}
+
+// Without ids, resumes those cut short by the app exiting, as happens on startup
+export async function resumeInterruptedOperations(ids?: string[]): Promise<string[]> {
+  return invoke<string[]>('resume_interrupted_operations', { ids });
+}
+
+export async function discardInterruptedOperation(id: string): Promise<void> {
+  return invoke<void>('discard_interrupted_operation', { id });
+}
+
+export async function onQueueChanged(
+  handler: (queue: QueueState) => void
+): Promise<() => void> {
+  if (!isTauri) return () => {};
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen<QueueState>('queue:changed', (event) => handler(event.payload));
 }
 
 export async function deleteFiles(paths: string[]): Promise<UndoToken> {
@@ -230,16 +734,18 @@ export async function getWslDistros(): Promise<WslDistro[]> {
   return invoke<WslDistro[]>('get_wsl_distros');
 }
 
+// Runs inside the distro when a distro's files are involved; returns the job id
 export async function wslCopy(
-  source: string,
+  sources: string[],
   dest: string,
-  useWslNative: boolean
-): Promise<void> {
-  return invoke<void>('wsl_copy', {
-    source,
-    dest,
-    useWslNative,
-  });
+  conflict?: ConflictPolicy
+): Promise<string> {
+  return invoke<string>('wsl_copy', { sources, dest, conflict });
+}
+
+// Linux paths map to Windows paths in the given or default distro, and vice versa
+export async function convertWslPath(path: string, distro?: string): Promise<string> {
+  return invoke<string>('convert_wsl_path', { path, distro });
 }
 
 // Config commands
@@ -265,37 +771,188 @@ export function normalizeWslPath(path: string): string {
 }
 
 // Search commands
+// Streams matches via 'search:files' events and finishes with 'search:files:done'
 export async function searchFiles(
   query: string,
   rootPaths: string[],
   limit?: number
-): Promise<FileEntry[]> {
-  return invoke<FileEntry[]>('search_files', {
+): Promise<string> {
+  return invoke<string>('search_files', {
     query,
     rootPaths,
     limit,
   });
 }
 
+// Streams matches via 'search:content' events and finishes with 'search:content:done'
+export async function searchContent(
+  query: string,
+  rootPaths: string[],
+  options?: ContentSearchOptions
+): Promise<string> {
+  return invoke<string>('search_content', {
+    query,
+    rootPaths,
+    options,
+  });
+}
+
+export async function cancelSearch(searchId: string): Promise<void> {
+  return invoke<void>('cancel_search', { searchId });
+}
+
+// Local paths only
+export async function getDiskSpace(path: string): Promise<DiskSpace> {
+  return invoke<DiskSpace>('get_disk_space', { path });
+}
+
 export async function getAvailableDrives(): Promise<DriveInfo[]> {
   return invoke<DriveInfo[]>('get_available_drives');
 }
 
+// Calls handler as volumes are mounted and unmounted; resolves to a function that stops it
+export async function onVolumesChanged(
+  handler: (change: VolumeChange) => void
+): Promise<() => void> {
+  if (!isTauri) return () => {};
+  const { listen } = await import('@tauri-apps/api/event');
+  return listen<VolumeChange>('volumes:changed', (event) => handler(event.payload));
+}
+
 // Indexer commands
-export async function startIndexing(roots: string[]): Promise<void> {
-  return invoke<void>('start_indexing', { roots });
+export interface ScanOptions {
+  threads?: number;
+  low_priority?: boolean;
+}
+
+// Omitting `scope` targets the 'default' scope when starting and every scope when
+// searching, stopping or clearing.
+export async function startIndexing(
+  roots: string[],
+  indexContent?: boolean,
+  scanOptions?: ScanOptions,
+  scope?: string
+): Promise<void> {
+  return invoke<void>('start_indexing', { roots, indexContent, scanOptions, scope });
+}
+
+export async function stopIndexing(scope?: string): Promise<void> {
+  return invoke<void>('stop_indexing', { scope });
+}
+
+export async function clearIndexCache(scope?: string): Promise<void> {
+  return invoke<void>('clear_index_cache', { scope });
+}
+
+export async function searchIndex(query: string, limit?: number, scope?: string): Promise<FileEntry[]> {
+  return invoke<FileEntry[]>('search_index', { query, limit, scope });
+}
+
+export async function searchContentIndex(query: string, limit?: number, scope?: string): Promise<FileEntry[]> {
+  return invoke<FileEntry[]>('search_content_index', { query, limit, scope });
+}
+
+export async function addIndexRoot(scope: string, root: string): Promise<void> {
+  return invoke<void>('add_index_root', { scope, root });
+}
+
+export async function removeIndexRoot(scope: string, root: string): Promise<void> {
+  return invoke<void>('remove_index_root', { scope, root });
+}
+
+export async function getIndexScopes(): Promise<IndexProgress[]> {
+  return invoke<IndexProgress[]>('get_index_scopes');
+}
+
+export async function removeIndexScope(scope: string): Promise<void> {
+  return invoke<void>('remove_index_scope', { scope });
+}
+
+// Frecency commands
+export interface FrecentPath {
+  path: string;
+  kind: 'file' | 'directory';
+  score: number;
+  last_access: number;
+}
+
+export async function jumpTo(query: string, limit?: number): Promise<FrecentPath[]> {
+  return invoke<FrecentPath[]>('jump_to', { query, limit });
+}
+
+export async function getFrecentPaths(kind?: 'file' | 'directory', limit?: number): Promise<FrecentPath[]> {
+  return invoke<FrecentPath[]>('get_frecent_paths', { kind, limit });
+}
+
+export async function forgetFrecentPath(path: string): Promise<boolean> {
+  return invoke<boolean>('forget_frecent_path', { path });
+}
+
+// Tag commands
+// Search queries also accept tag filters, e.g. 'tag:invoice tag:final 2024'
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
+export async function addTags(paths: string[], tags: string[]): Promise<void> {
+  return invoke<void>('add_tags', { paths, tags });
+}
+
+export async function removeTags(paths: string[], tags: string[]): Promise<void> {
+  return invoke<void>('remove_tags', { paths, tags });
+}
+
+export async function listFilesByTag(tag: string): Promise<FileEntry[]> {
+  return invoke<FileEntry[]>('list_files_by_tag', { tag });
+}
+
+export async function getAllTags(): Promise<TagCount[]> {
+  return invoke<TagCount[]>('get_all_tags');
+}
+
+// Archive commands
+// Both run as jobs reporting via 'job:progress' events and finishing with 'job:done'
+export async function createArchive(
+  sources: string[],
+  dest: string,
+  format: ArchiveFormat,
+  level?: number,
+  conflict?: ConflictPolicy
+): Promise<string> {
+  return invoke<string>('create_archive', { sources, dest, format, level, conflict });
+}
+
+// Selection entries are paths inside the archive; omit it to extract everything
+export async function extractArchive(
+  archive: string,
+  dest: string,
+  selection?: string[],
+  conflict?: ConflictPolicy
+): Promise<string> {
+  return invoke<string>('extract_archive', { archive, dest, selection, conflict });
+}
+
+// Job commands
+export async function cancelJob(jobId: string): Promise<void> {
+  return invoke<void>('cancel_job', { jobId });
+}
+
+export async function getJobs(): Promise<JobProgress[]> {
+  return invoke<JobProgress[]>('get_jobs');
 }
 
-export async function stopIndexing(): Promise<void> {
-  return invoke<void>('stop_indexing');
+// Remote commands
+export async function setRemoteSecret(name: string, secret: string): Promise<void> {
+  return invoke<void>('set_remote_secret', { name, secret });
 }
 
-export async function clearIndexCache(): Promise<void> {
-  return invoke<void>('clear_index_cache');
+export async function forgetRemoteSecret(name: string): Promise<void> {
+  return invoke<void>('forget_remote_secret', { name });
 }
 
-export async function searchIndex(query: string, limit?: number): Promise<FileEntry[]> {
-  return invoke<FileEntry[]>('search_index', { query, limit });
+export async function hasRemoteSecret(name: string): Promise<boolean> {
+  return invoke<boolean>('has_remote_secret', { name });
 }
 
 // Error parsing helper - converts AppError objects to readable strings